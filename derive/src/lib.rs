@@ -0,0 +1,68 @@
+//! Derive macros for the [`pretty_assertions_sorted`](https://docs.rs/pretty_assertions_sorted)
+//! crate, enabled with its `derive` feature.
+use proc_macro::TokenStream;
+use quote::quote;
+use syn::{parse_macro_input, Data, DeriveInput, Fields};
+
+/// Implements `pretty_assertions_sorted::SortedDebugKey` for a struct with named fields,
+/// sorting map keys of the struct by the field marked with `#[sort_key]`.
+#[proc_macro_derive(SortedDebugKey, attributes(sort_key))]
+pub fn derive_sorted_debug_key(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    sorted_debug_key(&input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+fn sorted_debug_key(input: &DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => {
+                return Err(syn::Error::new_spanned(
+                    &input.ident,
+                    "SortedDebugKey can only be derived for structs with named fields",
+                ))
+            }
+        },
+        _ => {
+            return Err(syn::Error::new_spanned(
+                &input.ident,
+                "SortedDebugKey can only be derived for structs",
+            ))
+        }
+    };
+
+    let mut sort_keys = fields.iter().filter(|field| {
+        field
+            .attrs
+            .iter()
+            .any(|attr| attr.path().is_ident("sort_key"))
+    });
+    let sort_key = match (sort_keys.next(), sort_keys.next()) {
+        (Some(field), None) => field.ident.as_ref().expect("fields are named"),
+        (None, _) => {
+            return Err(syn::Error::new_spanned(
+                &input.ident,
+                "SortedDebugKey needs a field marked with #[sort_key]",
+            ))
+        }
+        (Some(_), Some(extra)) => {
+            return Err(syn::Error::new_spanned(
+                extra,
+                "only one field can be marked with #[sort_key]",
+            ))
+        }
+    };
+
+    let ident = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    let name = ident.to_string();
+    let sort_key = sort_key.to_string();
+    Ok(quote! {
+        impl #impl_generics ::pretty_assertions_sorted::SortedDebugKey for #ident #ty_generics #where_clause {
+            const NAME: &'static str = #name;
+            const SORT_KEY: &'static str = #sort_key;
+        }
+    })
+}
@@ -0,0 +1,138 @@
+//! Aligning two sequences by their longest common subsequence, see
+//! [`assert_eq_sorted_lcs`](crate::assert_eq_sorted_lcs).
+use crate::SortedDebug;
+use std::fmt;
+use yansi::Paint;
+
+/// Returns the sorted, pretty [`Debug`](fmt::Debug) representation of every element, in
+/// order. Two elements are the same element of an alignment if these are equal.
+pub fn sorted_elements<I>(values: I) -> Vec<String>
+where
+    I: IntoIterator,
+    I::Item: fmt::Debug,
+{
+    values
+        .into_iter()
+        .map(|value| format!("{:#?}", SortedDebug::new(value)))
+        .collect()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Edit<'a> {
+    Common(&'a str),
+    Removed(&'a str),
+    Added(&'a str),
+}
+
+/// Aligns the sequences along their longest common subsequence. Removed elements are
+/// placed before added elements between two common elements.
+fn align<'a>(left: &'a [String], right: &'a [String]) -> Vec<Edit<'a>> {
+    // lengths[i][j] is the length of the longest common subsequence of left[i..] and
+    // right[j..].
+    let mut lengths = vec![vec![0usize; right.len() + 1]; left.len() + 1];
+    for i in (0..left.len()).rev() {
+        for j in (0..right.len()).rev() {
+            lengths[i][j] = if left[i] == right[j] {
+                lengths[i + 1][j + 1] + 1
+            } else {
+                lengths[i + 1][j].max(lengths[i][j + 1])
+            };
+        }
+    }
+
+    let (mut i, mut j) = (0, 0);
+    let mut edits = vec![];
+    while i < left.len() || j < right.len() {
+        if i < left.len() && j < right.len() && left[i] == right[j] {
+            edits.push(Edit::Common(&left[i]));
+            i += 1;
+            j += 1;
+        } else if j == right.len() || (i < left.len() && lengths[i + 1][j] >= lengths[i][j + 1]) {
+            edits.push(Edit::Removed(&left[i]));
+            i += 1;
+        } else {
+            edits.push(Edit::Added(&right[j]));
+            j += 1;
+        }
+    }
+    edits
+}
+
+/// A comparison of two sequences produced by [`sorted_elements`], aligned by their longest
+/// common subsequence.
+///
+/// Every element is displayed in place, prefixed with `<` if it was removed from the left
+/// sequence, `>` if it was added in the right sequence, or a space if it's in both.
+pub struct AlignedComparison<'a> {
+    left: &'a [String],
+    right: &'a [String],
+}
+
+impl<'a> AlignedComparison<'a> {
+    pub fn new(left: &'a [String], right: &'a [String]) -> Self {
+        Self { left, right }
+    }
+}
+
+impl<'a> fmt::Display for AlignedComparison<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for edit in align(self.left, self.right) {
+            match edit {
+                Edit::Common(element) => {
+                    for line in element.lines() {
+                        writeln!(f, " {}", line)?;
+                    }
+                }
+                Edit::Removed(element) => {
+                    for line in element.lines() {
+                        writeln!(f, "{}", format!("<{}", line).red())?;
+                    }
+                }
+                Edit::Added(element) => {
+                    for line in element.lines() {
+                        writeln!(f, "{}", format!(">{}", line).green())?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn elements(values: &[&str]) -> Vec<String> {
+        values.iter().map(|value| value.to_string()).collect()
+    }
+
+    #[test]
+    fn aligns_insertions_and_deletions_in_place() {
+        let left = elements(&["a", "b", "c", "d", "e"]);
+        let right = elements(&["a", "x", "c", "e", "f"]);
+        assert_eq!(
+            align(&left, &right),
+            vec![
+                Edit::Common("a"),
+                Edit::Removed("b"),
+                Edit::Added("x"),
+                Edit::Common("c"),
+                Edit::Removed("d"),
+                Edit::Common("e"),
+                Edit::Added("f"),
+            ]
+        );
+    }
+
+    #[test]
+    fn aligns_empty_sequences() {
+        let left = elements(&["a", "b"]);
+        assert_eq!(
+            align(&left, &[]),
+            vec![Edit::Removed("a"), Edit::Removed("b")]
+        );
+        assert_eq!(align(&[], &left), vec![Edit::Added("a"), Edit::Added("b")]);
+        assert_eq!(align(&[], &[]), vec![]);
+    }
+}
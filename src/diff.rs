@@ -0,0 +1,803 @@
+//! Structural diffing of two parsed (and sorted) [`Value`] trees.
+//!
+//! The two trees are walked in tandem: struct fields are matched by name, map entries by
+//! key, set elements by equality, and list and tuple elements by position. Every place
+//! where the trees differ produces a [`DiffOp`].
+use crate::sort::TextFn;
+use crate::{render, sorted_value, sorted_values, SortOptions};
+use darrentsung_debug_parser::{OrNonExhaustive, Struct, Term, Value};
+use std::borrow::Cow;
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt;
+
+/// A single step in the path from the root of a [`Value`] tree to one of its children.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum PathSegment {
+    /// A struct field.
+    Field(String),
+    /// A map key, rendered on a single line (string keys are rendered without quotes).
+    Key(String),
+    /// An element of a list, set, or tuple.
+    Index(usize),
+}
+
+impl PathSegment {
+    pub(crate) fn key(key: &Value) -> Self {
+        match key {
+            Value::Term(Term::String(s)) => PathSegment::Key(s.clone()),
+            _ => PathSegment::Key(render::render_inline(key)),
+        }
+    }
+}
+
+/// A path from the root of a [`Value`] tree, displayed in a dotted form like
+/// `response.headers.content-type` or `items[2].name`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) struct Path(pub(crate) Vec<PathSegment>);
+
+impl fmt::Display for Path {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (index, segment) in self.0.iter().enumerate() {
+            match segment {
+                PathSegment::Field(name) | PathSegment::Key(name) => {
+                    if index > 0 {
+                        f.write_str(".")?;
+                    }
+                    f.write_str(name)?;
+                }
+                PathSegment::Index(i) => write!(f, "[{}]", i)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A difference between the left and right [`Value`] trees.
+#[derive(Debug)]
+pub(crate) enum DiffOp<'a> {
+    /// The value only exists in the right tree.
+    Added { path: Path, right: &'a Value },
+    /// The value only exists in the left tree.
+    Removed { path: Path, left: &'a Value },
+    /// The value exists in both trees, but differs. This is only produced for values that
+    /// can't be walked any further, eg. two different terms or two values of a different
+    /// shape.
+    Changed {
+        path: Path,
+        left: &'a Value,
+        right: &'a Value,
+    },
+}
+
+/// Walks the two trees in tandem and returns every difference between them. Terms in the
+/// same group of [`SortOptions::term_aliases`] are considered equal, numeric map keys are
+/// matched by their value with [`SortOptions::numeric_key_normalization`], and string map
+/// keys by their normalized form with [`SortOptions::key_normalizer`].
+pub(crate) fn diff<'a>(
+    left: &'a Value,
+    right: &'a Value,
+    options: &SortOptions,
+) -> Vec<DiffOp<'a>> {
+    let mut ops = vec![];
+    walk(&mut vec![], left, right, options, &mut ops);
+    ops
+}
+
+/// The key that struct fields are matched by in the tandem walk, ignoring the case and
+/// underscores of the name with [`SortOptions::case_insensitive_fields`].
+fn field_key<'a>(ident: &'a str, options: &SortOptions) -> Cow<'a, str> {
+    if options.case_insensitive_fields {
+        Cow::Owned(ident.to_lowercase().replace('_', ""))
+    } else {
+        Cow::Borrowed(ident)
+    }
+}
+
+/// The key that map entries are matched by in the tandem walk.
+#[derive(PartialEq, Eq, PartialOrd, Ord)]
+enum MatchKey<'a> {
+    Value(&'a Value),
+    /// The canonical form of a numeric key, eg. `1` for both `1` and `1.0`.
+    Number(String),
+    /// A string key normalized by the [`SortOptions::key_normalizer`].
+    Normalized(String),
+}
+
+impl<'a> MatchKey<'a> {
+    fn of(key: &'a Value, options: &SortOptions) -> Self {
+        if let (Value::Term(Term::String(s)), Some(TextFn(normalizer))) =
+            (key, &options.key_normalizer)
+        {
+            return MatchKey::Normalized(normalizer(s));
+        }
+
+        let number = match key {
+            Value::Term(Term::UnquotedRawString(raw)) if options.numeric_key_normalization => {
+                raw.parse::<f64>().ok().filter(|number| number.is_finite())
+            }
+            _ => None,
+        };
+        match number {
+            Some(number) => MatchKey::Number(number.to_string()),
+            None => MatchKey::Value(key),
+        }
+    }
+}
+
+/// The text of a string or identifier term, eg. `OK` for both `"OK"` and `OK`.
+fn term_text(value: &Value) -> Option<&str> {
+    match value {
+        Value::Term(Term::String(s)) | Value::Term(Term::Ident(s)) => Some(s),
+        _ => None,
+    }
+}
+
+/// Whether a field or map entry that's missing on the other side can be ignored, since it's
+/// `None` and [`SortOptions::none_equals_absent`] is set.
+fn is_ignorable_none(value: &Value, options: &SortOptions) -> bool {
+    options.none_equals_absent
+        && matches!(value, Value::Term(Term::Ident(ident)) if ident == "None")
+}
+
+fn is_aliased(left: &Value, right: &Value, term_aliases: &[Vec<String>]) -> bool {
+    let (left, right) = match (term_aliases.is_empty(), term_text(left), term_text(right)) {
+        (false, Some(left), Some(right)) => (left, right),
+        _ => return false,
+    };
+    term_aliases.iter().any(|group| {
+        group.iter().any(|alias| alias == left) && group.iter().any(|alias| alias == right)
+    })
+}
+
+fn walk<'a>(
+    path: &mut Vec<PathSegment>,
+    left: &'a Value,
+    right: &'a Value,
+    options: &SortOptions,
+    ops: &mut Vec<DiffOp<'a>>,
+) {
+    match (left, right) {
+        (Value::Struct(l), Value::Struct(r)) if l.name == r.name => {
+            let right_fields = fields(r)
+                .map(|(ident, value)| (field_key(ident, options), value))
+                .collect::<BTreeMap<_, _>>();
+            for (ident, l_value) in fields(l) {
+                path.push(PathSegment::Field(ident.to_string()));
+                match right_fields.get(&field_key(ident, options)) {
+                    Some(r_value) => walk(path, l_value, r_value, options, ops),
+                    None if is_ignorable_none(l_value, options) => (),
+                    None => ops.push(DiffOp::Removed {
+                        path: Path(path.clone()),
+                        left: l_value,
+                    }),
+                }
+                path.pop();
+            }
+
+            let left_fields = fields(l)
+                .map(|(ident, value)| (field_key(ident, options), value))
+                .collect::<BTreeMap<_, _>>();
+            for (ident, r_value) in fields(r) {
+                if !left_fields.contains_key(&field_key(ident, options))
+                    && !is_ignorable_none(r_value, options)
+                {
+                    path.push(PathSegment::Field(ident.to_string()));
+                    ops.push(DiffOp::Added {
+                        path: Path(path.clone()),
+                        right: r_value,
+                    });
+                    path.pop();
+                }
+            }
+        }
+        (Value::Map(l), Value::Map(r)) => {
+            // Entries are matched by key like a multimap: keys that differ in the Debug
+            // output can still match the same (eg. with a key normalizer), and each entry
+            // matches at most one entry on the other side.
+            let mut unmatched_right = BTreeMap::<_, VecDeque<_>>::new();
+            for (index, kv) in r.values.iter().enumerate() {
+                unmatched_right
+                    .entry(MatchKey::of(&kv.key, options))
+                    .or_default()
+                    .push_back(index);
+            }
+            let mut matched_right = vec![false; r.values.len()];
+            for kv in &l.values {
+                path.push(PathSegment::key(&kv.key));
+                match unmatched_right
+                    .get_mut(&MatchKey::of(&kv.key, options))
+                    .and_then(VecDeque::pop_front)
+                {
+                    Some(index) => {
+                        matched_right[index] = true;
+                        walk(path, &kv.value, &r.values[index].value, options, ops);
+                    }
+                    None if is_ignorable_none(&kv.value, options) => (),
+                    None => ops.push(DiffOp::Removed {
+                        path: Path(path.clone()),
+                        left: &kv.value,
+                    }),
+                }
+                path.pop();
+            }
+
+            for (kv, matched) in r.values.iter().zip(matched_right) {
+                if !matched && !is_ignorable_none(&kv.value, options) {
+                    path.push(PathSegment::key(&kv.key));
+                    ops.push(DiffOp::Added {
+                        path: Path(path.clone()),
+                        right: &kv.value,
+                    });
+                    path.pop();
+                }
+            }
+        }
+        (Value::Set(l), Value::Set(r)) => {
+            // Sets are compared as multisets, each element is matched by equality against
+            // an element on the other side that hasn't been matched yet.
+            let mut unmatched_right = r.values.iter().enumerate().collect::<Vec<_>>();
+            for (index, l_value) in l.values.iter().enumerate() {
+                match unmatched_right.iter().position(|(_, r)| {
+                    *r == l_value || is_aliased(l_value, r, &options.term_aliases)
+                }) {
+                    Some(position) => {
+                        unmatched_right.remove(position);
+                    }
+                    None => {
+                        path.push(PathSegment::Index(index));
+                        ops.push(DiffOp::Removed {
+                            path: Path(path.clone()),
+                            left: l_value,
+                        });
+                        path.pop();
+                    }
+                }
+            }
+            for (index, r_value) in unmatched_right {
+                path.push(PathSegment::Index(index));
+                ops.push(DiffOp::Added {
+                    path: Path(path.clone()),
+                    right: r_value,
+                });
+                path.pop();
+            }
+        }
+        (Value::List(l), Value::List(r)) => walk_sequence(path, &l.values, &r.values, options, ops),
+        (Value::Tuple(l), Value::Tuple(r)) if l.name == r.name => {
+            walk_sequence(path, &l.values, &r.values, options, ops)
+        }
+        _ => {
+            if left != right && !is_aliased(left, right, &options.term_aliases) {
+                ops.push(DiffOp::Changed {
+                    path: Path(path.clone()),
+                    left,
+                    right,
+                });
+            }
+        }
+    }
+}
+
+fn walk_sequence<'a>(
+    path: &mut Vec<PathSegment>,
+    left: &'a [Value],
+    right: &'a [Value],
+    options: &SortOptions,
+    ops: &mut Vec<DiffOp<'a>>,
+) {
+    for index in 0..left.len().max(right.len()) {
+        path.push(PathSegment::Index(index));
+        match (left.get(index), right.get(index)) {
+            (Some(l), Some(r)) => walk(path, l, r, options, ops),
+            (Some(l), None) => ops.push(DiffOp::Removed {
+                path: Path(path.clone()),
+                left: l,
+            }),
+            (None, Some(r)) => ops.push(DiffOp::Added {
+                path: Path(path.clone()),
+                right: r,
+            }),
+            (None, None) => unreachable!(),
+        }
+        path.pop();
+    }
+}
+
+fn fields(s: &Struct) -> impl Iterator<Item = (&str, &Value)> {
+    s.values.iter().filter_map(|v| match v {
+        OrNonExhaustive::Value(ident_value) => {
+            Some((ident_value.ident.as_str(), &ident_value.value))
+        }
+        OrNonExhaustive::NonExhaustive => None,
+    })
+}
+
+/// The number of leaf values in the tree. Terms and empty collections are leaves.
+pub(crate) fn leaf_count(value: &Value) -> usize {
+    let count = match value {
+        Value::Struct(s) => fields(s).map(|(_, v)| leaf_count(v)).sum(),
+        Value::Map(map) => map.values.iter().map(|kv| leaf_count(&kv.value)).sum(),
+        Value::Set(set) => set.values.iter().map(leaf_count).sum(),
+        Value::List(list) => list.values.iter().map(leaf_count).sum(),
+        Value::Tuple(tuple) => tuple.values.iter().map(leaf_count).sum(),
+        Value::Term(_) => 1,
+    };
+    count.max(1)
+}
+
+/// Collects the path and the single-line rendering of every leaf value in the tree. Terms
+/// and empty collections are leaves.
+fn flatten(value: &Value, path: &mut Vec<PathSegment>, out: &mut Vec<(String, String)>) {
+    let mut visit = |segment, child: &Value| {
+        path.push(segment);
+        flatten(child, path, out);
+        path.pop();
+    };
+    let is_leaf = match value {
+        Value::Struct(s) => {
+            fields(s).for_each(|(ident, v)| visit(PathSegment::Field(ident.to_string()), v));
+            fields(s).next().is_none()
+        }
+        Value::Map(map) => {
+            map.values
+                .iter()
+                .for_each(|kv| visit(PathSegment::key(&kv.key), &kv.value));
+            map.values.is_empty()
+        }
+        Value::Set(set) => {
+            for (index, v) in set.values.iter().enumerate() {
+                visit(PathSegment::Index(index), v);
+            }
+            set.values.is_empty()
+        }
+        Value::List(list) => {
+            for (index, v) in list.values.iter().enumerate() {
+                visit(PathSegment::Index(index), v);
+            }
+            list.values.is_empty()
+        }
+        // The name of a tuple struct (eg. `Some`) is kept in the path, so that `Some(1)` and
+        // `Ok(1)` don't flatten to the same values.
+        Value::Tuple(tuple) if !tuple.values.is_empty() => {
+            if let Some(name) = &tuple.name {
+                path.push(PathSegment::Field(name.clone()));
+            }
+            for (index, v) in tuple.values.iter().enumerate() {
+                path.push(PathSegment::Index(index));
+                flatten(v, path, out);
+                path.pop();
+            }
+            if tuple.name.is_some() {
+                path.pop();
+            }
+            false
+        }
+        Value::Tuple(_) | Value::Term(_) => true,
+    };
+    if is_leaf {
+        out.push((Path(path.clone()).to_string(), render::render_inline(value)));
+    }
+}
+
+/// Flattens the sorted [`Debug`](fmt::Debug) representation of `value` into a list of
+/// `(path, value)` pairs, one for every leaf value (terms and empty collections), sorted
+/// by path.
+///
+/// Paths are dotted struct field names and map keys, with `[index]` for the elements of
+/// lists, sets, and tuples and the name of tuple structs like `Some` kept as a segment
+/// (eg. `limits.cpu` or `retry.Some[0]`). The names of structs aren't part of the paths.
+///
+/// ```rust
+/// use std::collections::HashMap;
+/// use pretty_assertions_sorted::sorted_flattened;
+///
+/// let config: HashMap<_, _> = vec![("b", vec![2]), ("a", vec![])].into_iter().collect();
+/// assert_eq!(
+///     sorted_flattened(&config),
+///     vec![
+///         ("a".to_string(), "[]".to_string()),
+///         ("b[0]".to_string(), "2".to_string()),
+///     ]
+/// );
+/// ```
+pub fn sorted_flattened<T: fmt::Debug + ?Sized>(value: &T) -> Vec<(String, String)> {
+    let value = sorted_value(value, &SortOptions::default());
+    let mut out = vec![];
+    flatten(&value, &mut vec![], &mut out);
+    out.sort();
+    out
+}
+
+/// Counts of the leaf values that differ between two values, see [`sorted_diff_stats`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DiffStats {
+    /// Leaf values that only exist in the right value.
+    pub added: usize,
+    /// Leaf values that only exist in the left value.
+    pub removed: usize,
+    /// Values that exist in both, but differ. A value that changed shape (eg. `Some(1)`
+    /// vs. `None`) counts as a single changed value.
+    pub changed: usize,
+}
+
+impl DiffStats {
+    pub(crate) fn from_ops(ops: &[DiffOp]) -> Self {
+        let mut stats = DiffStats::default();
+        for op in ops {
+            match op {
+                DiffOp::Added { right, .. } => stats.added += leaf_count(right),
+                DiffOp::Removed { left, .. } => stats.removed += leaf_count(left),
+                DiffOp::Changed { .. } => stats.changed += 1,
+            }
+        }
+        stats
+    }
+
+    /// The total number of leaf values that differ (added, removed, or changed).
+    pub fn total(&self) -> usize {
+        self.added + self.removed + self.changed
+    }
+}
+
+/// Returns counts of the leaf values that differ between the sorted [`Debug`](fmt::Debug)
+/// representations of `left` and `right`.
+///
+/// This gives a numeric "distance" between two values that tests can assert bounds on:
+///
+/// ```rust
+/// use std::collections::HashMap;
+/// use pretty_assertions_sorted::sorted_diff_stats;
+///
+/// let left: HashMap<_, _> = vec![("a", 1), ("b", 2)].into_iter().collect();
+/// let right: HashMap<_, _> = vec![("a", 1), ("b", 3), ("c", 4)].into_iter().collect();
+///
+/// let stats = sorted_diff_stats(&left, &right);
+/// assert_eq!((stats.added, stats.removed, stats.changed), (1, 0, 1));
+/// assert!(stats.total() <= 2);
+/// ```
+///
+/// A value whose Debug output can't be parsed is treated as a single leaf value, see
+/// [`SortedDebug`](crate::SortedDebug).
+pub fn sorted_diff_stats<L, R>(left: &L, right: &R) -> DiffStats
+where
+    L: fmt::Debug + ?Sized,
+    R: fmt::Debug + ?Sized,
+{
+    let options = SortOptions::default();
+    let (left, right) = sorted_values(left, right, &options);
+    DiffStats::from_ops(&diff(&left, &right, &options))
+}
+
+/// Returns how similar the sorted [`Debug`](fmt::Debug) representations of `left` and
+/// `right` are, from `0.0` (nothing in common) to `1.0` (equal).
+///
+/// The score is the ratio of matching leaf values to the union of the leaf values of both
+/// sides, where the union is the matching leaves plus the leaves that are added, removed,
+/// or changed (see [`sorted_diff_stats`], a changed value counts as a single leaf). Equal
+/// values have a score of `1.0`.
+///
+/// ```rust
+/// use std::collections::HashMap;
+/// use pretty_assertions_sorted::sorted_similarity;
+///
+/// let left: HashMap<_, _> = vec![("a", 1), ("b", 2), ("c", 3)].into_iter().collect();
+/// let right: HashMap<_, _> = vec![("a", 1), ("b", 2), ("c", 4)].into_iter().collect();
+///
+/// // 2 matching leaves out of 3.
+/// assert!((sorted_similarity(&left, &right) - 2.0 / 3.0).abs() < f64::EPSILON);
+/// ```
+pub fn sorted_similarity<L, R>(left: &L, right: &R) -> f64
+where
+    L: fmt::Debug + ?Sized,
+    R: fmt::Debug + ?Sized,
+{
+    let options = SortOptions::default();
+    let (left, right) = sorted_values(left, right, &options);
+    let ops = diff(&left, &right, &options);
+
+    // Every leaf of the left side either matches, is removed, or is part of a changed value.
+    let changed_left_leaves = ops
+        .iter()
+        .map(|op| match op {
+            DiffOp::Changed { left, .. } => leaf_count(left),
+            _ => 0,
+        })
+        .sum::<usize>();
+    let stats = DiffStats::from_ops(&ops);
+    let matching = leaf_count(&left) - stats.removed - changed_left_leaves;
+
+    matching as f64 / (matching + stats.total()) as f64
+}
+
+/// Counts of the map keys that differ between two maps, see [`sorted_key_summary`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct KeySummary {
+    /// Keys that only exist in the left map.
+    pub only_left: usize,
+    /// Keys that only exist in the right map.
+    pub only_right: usize,
+    /// Keys that exist in both maps, but whose values differ.
+    pub changed: usize,
+}
+
+impl KeySummary {
+    /// Describes the counts on a single line, eg. `1 key only in left, 2 keys only in
+    /// right, 0 keys with differing values`.
+    pub fn describe(&self, left_label: &str, right_label: &str) -> String {
+        let keys = |count: usize| match count {
+            1 => "1 key".to_string(),
+            _ => format!("{} keys", count),
+        };
+        format!(
+            "{} only in {}, {} only in {}, {} with differing values",
+            keys(self.only_left),
+            left_label,
+            keys(self.only_right),
+            right_label,
+            keys(self.changed),
+        )
+    }
+}
+
+/// Returns counts of the map keys that differ between the sorted [`Debug`](fmt::Debug)
+/// representations of `left` and `right`, or `None` if they aren't both maps.
+///
+/// Nested maps contribute their own keys: a key whose value is a map that differs counts
+/// the keys that differ inside of it, rather than itself.
+///
+/// ```rust
+/// use std::collections::HashMap;
+/// use pretty_assertions_sorted::{sorted_key_summary, SortOptions};
+///
+/// let left = HashMap::from([("a", 1), ("b", 2)]);
+/// let right = HashMap::from([("a", 1), ("b", 3), ("c", 4)]);
+///
+/// let summary = sorted_key_summary(&left, &right, &SortOptions::default()).unwrap();
+/// assert_eq!((summary.only_left, summary.only_right, summary.changed), (0, 1, 1));
+/// ```
+pub fn sorted_key_summary<L, R>(left: &L, right: &R, options: &SortOptions) -> Option<KeySummary>
+where
+    L: fmt::Debug + ?Sized,
+    R: fmt::Debug + ?Sized,
+{
+    let (left, right) = sorted_values(left, right, options);
+    if !matches!((&left, &right), (Value::Map(_), Value::Map(_))) {
+        return None;
+    }
+
+    let mut summary = KeySummary::default();
+    let mut changed_keys = vec![];
+    let ops = diff(&left, &right, options);
+    for op in &ops {
+        let path = match op {
+            DiffOp::Added { path, .. } | DiffOp::Removed { path, .. } => path,
+            DiffOp::Changed { path, .. } => path,
+        };
+        // The difference belongs to the innermost map key on its path.
+        let Some(key_end) = path
+            .0
+            .iter()
+            .rposition(|segment| matches!(segment, PathSegment::Key(_)))
+        else {
+            continue;
+        };
+        match op {
+            DiffOp::Removed { .. } if key_end + 1 == path.0.len() => summary.only_left += 1,
+            DiffOp::Added { .. } if key_end + 1 == path.0.len() => summary.only_right += 1,
+            _ => {
+                let key_path = &path.0[..=key_end];
+                if !changed_keys.contains(&key_path) {
+                    changed_keys.push(key_path);
+                }
+            }
+        }
+    }
+    summary.changed = changed_keys.len();
+    Some(summary)
+}
+
+/// A single difference between two values, see [`sorted_differences`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Difference {
+    /// The path to the value, eg. `response.code` or `items[2]`. Empty if the values differ
+    /// as a whole.
+    pub path: String,
+    /// The value on the left side, rendered on a single line. `None` if the value only
+    /// exists on the right side.
+    pub left: Option<String>,
+    /// The value on the right side, rendered on a single line. `None` if the value only
+    /// exists on the left side.
+    pub right: Option<String>,
+}
+
+impl fmt::Display for Difference {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if !self.path.is_empty() {
+            write!(f, "{}: ", self.path)?;
+        }
+        let missing = "<missing>";
+        write!(
+            f,
+            "{} -> {}",
+            self.left.as_deref().unwrap_or(missing),
+            self.right.as_deref().unwrap_or(missing),
+        )
+    }
+}
+
+/// Returns every difference between the sorted [`Debug`](fmt::Debug) representations of
+/// `left` and `right`, in the order of the left value.
+///
+/// ```rust
+/// use std::collections::HashMap;
+/// use pretty_assertions_sorted::sorted_differences;
+///
+/// let left: HashMap<_, _> = vec![("code", 200), ("retries", 0)].into_iter().collect();
+/// let right: HashMap<_, _> = vec![("code", 500), ("retries", 0)].into_iter().collect();
+///
+/// let differences = sorted_differences(&left, &right);
+/// assert_eq!(differences.len(), 1);
+/// assert_eq!(differences[0].to_string(), "code: 200 -> 500");
+/// ```
+pub fn sorted_differences<L, R>(left: &L, right: &R) -> Vec<Difference>
+where
+    L: fmt::Debug + ?Sized,
+    R: fmt::Debug + ?Sized,
+{
+    sorted_differences_with_options(left, right, &SortOptions::default())
+}
+
+/// Same as [`sorted_differences`], but sorts and compares the values with the given
+/// [`SortOptions`] (eg. [`SortOptions::numeric_key_normalization`]).
+pub fn sorted_differences_with_options<L, R>(
+    left: &L,
+    right: &R,
+    options: &SortOptions,
+) -> Vec<Difference>
+where
+    L: fmt::Debug + ?Sized,
+    R: fmt::Debug + ?Sized,
+{
+    let (left, right) = sorted_values(left, right, options);
+
+    diff(&left, &right, options)
+        .into_iter()
+        .map(|op| {
+            let (path, left, right) = match op {
+                DiffOp::Added { path, right } => (path, None, Some(right)),
+                DiffOp::Removed { path, left } => (path, Some(left), None),
+                DiffOp::Changed { path, left, right } => (path, Some(left), Some(right)),
+            };
+            Difference {
+                path: path.to_string(),
+                left: left.map(render::render_inline),
+                right: right.map(render::render_inline),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use darrentsung_debug_parser::parse;
+    use std::collections::HashMap;
+
+    #[derive(Debug)]
+    #[allow(unused)]
+    struct Foo {
+        name: &'static str,
+        values: HashMap<&'static str, Vec<i32>>,
+        bar: Option<Bar>,
+    }
+
+    #[derive(Debug)]
+    #[allow(unused)]
+    struct Bar {
+        elo: i32,
+    }
+
+    fn paths(left: &str, right: &str) -> Vec<String> {
+        let (left, right) = (parse(left).unwrap(), parse(right).unwrap());
+        diff(&left, &right, &SortOptions::default())
+            .iter()
+            .map(|op| match op {
+                DiffOp::Added { path, .. } => format!("+ {}", path),
+                DiffOp::Removed { path, .. } => format!("- {}", path),
+                DiffOp::Changed { path, .. } => format!("~ {}", path),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn walks_trees_in_tandem() {
+        assert_eq!(
+            paths(
+                r#"Foo { a: 1, b: {"x": [1, 2], "y": Bar { c: 2 }}, d: Some(1) }"#,
+                r#"Foo { a: 1, b: {"x": [1, 3, 4], "z": 1}, e: 2, d: None }"#,
+            ),
+            vec!["~ b.x[1]", "+ b.x[2]", "- b.y", "+ b.z", "~ d", "+ e"]
+        );
+    }
+
+    #[test]
+    fn matches_numeric_keys_by_value() {
+        let (left, right) = (r#"{1: "a", 2.5: "b"}"#, r#"{1.0: "a", 2.50: "c"}"#);
+        assert_eq!(paths(left, right), vec!["- 1", "- 2.5", "+ 1.0", "+ 2.50"]);
+
+        let options = SortOptions::default().numeric_key_normalization(true);
+        let (left, right) = (parse(left).unwrap(), parse(right).unwrap());
+        let ops = diff(&left, &right, &options);
+        assert!(matches!(&ops[..], [DiffOp::Changed { path, .. }] if path.to_string() == "2.5"));
+    }
+
+    #[test]
+    fn scores_similarity_by_matching_leaves() {
+        let left = Foo {
+            name: "foo",
+            values: vec![("a", vec![1, 2, 3]), ("b", vec![4])]
+                .into_iter()
+                .collect(),
+            bar: Some(Bar { elo: 1 }),
+        };
+        let right = Foo {
+            name: "foo",
+            values: vec![("a", vec![1, 2, 5]), ("c", vec![6])]
+                .into_iter()
+                .collect(),
+            bar: None,
+        };
+
+        assert_eq!(sorted_similarity(&left, &left), 1.0);
+        // name, a[0] and a[1] match. a[2], b, c and bar differ.
+        assert_eq!(sorted_similarity(&left, &right), 3.0 / 7.0);
+        assert_eq!(sorted_similarity(&1, &2), 0.0);
+    }
+
+    #[test]
+    fn no_stats_for_equal_values() {
+        let build = || Foo {
+            name: "foo",
+            values: vec![("a", vec![1, 2]), ("b", vec![]), ("c", vec![3])]
+                .into_iter()
+                .collect(),
+            bar: Some(Bar { elo: 1 }),
+        };
+
+        for _ in 0..100 {
+            assert_eq!(sorted_diff_stats(&build(), &build()), DiffStats::default());
+        }
+    }
+
+    #[test]
+    fn counts_differing_leaves() {
+        let left = Foo {
+            name: "foo",
+            values: vec![("a", vec![1, 2]), ("b", vec![4, 5])]
+                .into_iter()
+                .collect(),
+            bar: Some(Bar { elo: 1 }),
+        };
+        let right = Foo {
+            name: "foo2",
+            values: vec![("a", vec![1, 2, 3]), ("c", vec![])]
+                .into_iter()
+                .collect(),
+            bar: None,
+        };
+
+        let stats = sorted_diff_stats(&left, &right);
+        assert_eq!(
+            stats,
+            DiffStats {
+                // "a"[2] and "c" (an empty list is a single leaf).
+                added: 2,
+                // Both elements of "b".
+                removed: 2,
+                // name and bar.
+                changed: 2,
+            }
+        );
+        assert_eq!(stats.total(), 6);
+    }
+}
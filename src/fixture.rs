@@ -0,0 +1,93 @@
+//! Loading expected values from fixture files, see [`assert_eq_sorted_fixture`](crate::assert_eq_sorted_fixture).
+//!
+//! The format of a fixture is picked by its file extension, and each format is gated behind
+//! a feature of the same name so that the serialization crates are only pulled in when needed.
+use serde::{de::DeserializeOwned, Serialize};
+use std::fs;
+use std::path::Path;
+
+/// Environment variable that enables the update mode of
+/// [`assert_eq_sorted_fixture`](crate::assert_eq_sorted_fixture). When it's set to anything
+/// other than an empty string, fixtures are (re)generated from the actual values instead of
+/// being compared against.
+pub const UPDATE_FIXTURES_ENV_VAR: &str = "UPDATE_SORTED_FIXTURES";
+
+#[derive(Debug, Clone, Copy)]
+enum Format {
+    #[cfg(feature = "ron")]
+    Ron,
+    #[cfg(feature = "json")]
+    Json,
+}
+
+impl Format {
+    fn of(path: &Path) -> Format {
+        match path.extension().and_then(|e| e.to_str()) {
+            #[cfg(feature = "ron")]
+            Some("ron") => Format::Ron,
+            #[cfg(feature = "json")]
+            Some("json") => Format::Json,
+            _ => panic!(
+                "Unsupported fixture format for {}, expected one of the extensions enabled \
+                 by features: `ron`, `json`",
+                path.display()
+            ),
+        }
+    }
+
+    fn serialize<T: Serialize>(self, value: &T) -> Result<String, String> {
+        match self {
+            #[cfg(feature = "ron")]
+            Format::Ron => ron::ser::to_string_pretty(value, ron::ser::PrettyConfig::default())
+                .map_err(|err| err.to_string()),
+            #[cfg(feature = "json")]
+            Format::Json => serde_json::to_string_pretty(value).map_err(|err| err.to_string()),
+        }
+    }
+
+    fn deserialize<T: DeserializeOwned>(self, contents: &str) -> Result<T, String> {
+        match self {
+            #[cfg(feature = "ron")]
+            Format::Ron => ron::from_str(contents).map_err(|err| err.to_string()),
+            #[cfg(feature = "json")]
+            Format::Json => serde_json::from_str(contents).map_err(|err| err.to_string()),
+        }
+    }
+}
+
+fn update_requested() -> bool {
+    std::env::var_os(UPDATE_FIXTURES_ENV_VAR).is_some_and(|v| !v.is_empty())
+}
+
+/// Loads the expected value stored in the fixture at `path`.
+///
+/// In update mode (see [`UPDATE_FIXTURES_ENV_VAR`]) the fixture is first overwritten with
+/// the serialized `actual` value, so the loaded value round-trips through the fixture.
+///
+/// Panics if the fixture can't be read or deserialized, since this is meant to be used in
+/// tests.
+pub fn load_fixture<T: Serialize + DeserializeOwned>(path: &Path, actual: &T) -> T {
+    let format = Format::of(path);
+    if update_requested() {
+        let contents = format.serialize(actual).unwrap_or_else(|err| {
+            panic!("Failed to serialize fixture {}: {}", path.display(), err)
+        });
+        if let Some(parent) = path.parent() {
+            let _ = fs::create_dir_all(parent);
+        }
+        fs::write(path, contents + "\n")
+            .unwrap_or_else(|err| panic!("Failed to write fixture {}: {}", path.display(), err));
+    }
+
+    let contents = fs::read_to_string(path).unwrap_or_else(|err| {
+        panic!(
+            "Failed to read fixture {}: {} (run with {}=1 to generate it)",
+            path.display(),
+            err,
+            UPDATE_FIXTURES_ENV_VAR
+        )
+    });
+    format
+        .deserialize(&contents)
+        .unwrap_or_else(|err| panic!("Failed to deserialize fixture {}: {}", path.display(), err))
+}
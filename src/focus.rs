@@ -0,0 +1,183 @@
+//! Focusing on the subtrees of a [`Value`] at given paths, see
+//! [`assert_eq_sorted_only`](crate::assert_eq_sorted_only).
+use crate::{render, sorted_value, SortOptions};
+use darrentsung_debug_parser::{KeyValue, Map, OrNonExhaustive, Term, Value};
+use std::fmt;
+
+/// A single step of a path like `response.headers[0]`.
+#[derive(Debug, PartialEq)]
+enum Selector<'a> {
+    /// A struct field or a map key (string keys are matched without quotes).
+    Name(&'a str),
+    /// An element of a list, set, or tuple.
+    Index(usize),
+}
+
+fn parse_path(path: &str) -> Vec<Selector<'_>> {
+    let mut selectors = vec![];
+    for part in path.split('.') {
+        let (name, indices) = part.split_at(part.find('[').unwrap_or(part.len()));
+        if !name.is_empty() {
+            selectors.push(Selector::Name(name));
+        }
+        for index in indices.split('[').filter(|index| !index.is_empty()) {
+            let index = index.trim_end_matches(']');
+            match index.parse() {
+                Ok(index) => selectors.push(Selector::Index(index)),
+                Err(_) => selectors.push(Selector::Name(index)),
+            }
+        }
+    }
+    selectors
+}
+
+fn key_matches(key: &Value, name: &str) -> bool {
+    match key {
+        Value::Term(Term::String(key)) => key == name,
+        _ => render::render_inline(key) == name,
+    }
+}
+
+/// Returns true if the value has a subtree at the path.
+fn contains(value: &Value, path: &[Selector]) -> bool {
+    let (selector, rest) = match path.split_first() {
+        Some(split) => split,
+        None => return true,
+    };
+
+    let child = match (value, selector) {
+        (Value::Struct(s), Selector::Name(name)) => s.values.iter().find_map(|v| match v {
+            OrNonExhaustive::Value(ident_value) if ident_value.ident == *name => {
+                Some(&ident_value.value)
+            }
+            _ => None,
+        }),
+        (Value::Map(map), Selector::Name(name)) => map
+            .values
+            .iter()
+            .find(|key_value| key_matches(&key_value.key, name))
+            .map(|key_value| &key_value.value),
+        (Value::List(list), Selector::Index(index)) => list.values.get(*index),
+        (Value::Set(set), Selector::Index(index)) => set.values.get(*index),
+        (Value::Tuple(tuple), Selector::Index(index)) => tuple.values.get(*index),
+        _ => None,
+    };
+    child.is_some_and(|child| contains(child, rest))
+}
+
+/// Takes the subtree at the path out of the value, leaving a placeholder behind.
+fn take(value: &mut Value, path: &[Selector]) -> Option<Value> {
+    let (selector, rest) = match path.split_first() {
+        Some(split) => split,
+        None => {
+            return Some(std::mem::replace(
+                value,
+                Value::Term(Term::UnquotedRawString(String::new())),
+            ))
+        }
+    };
+
+    let child = match (value, selector) {
+        (Value::Struct(s), Selector::Name(name)) => s.values.iter_mut().find_map(|v| match v {
+            OrNonExhaustive::Value(ident_value) if ident_value.ident == *name => {
+                Some(&mut ident_value.value)
+            }
+            _ => None,
+        }),
+        (Value::Map(map), Selector::Name(name)) => map
+            .values
+            .iter_mut()
+            .find(|key_value| key_matches(&key_value.key, name))
+            .map(|key_value| &mut key_value.value),
+        (Value::List(list), Selector::Index(index)) => list.values.get_mut(*index),
+        (Value::Set(set), Selector::Index(index)) => set.values.get_mut(*index),
+        (Value::Tuple(tuple), Selector::Index(index)) => tuple.values.get_mut(*index),
+        _ => None,
+    };
+    child.and_then(|child| take(child, rest))
+}
+
+/// New-type wrapper around an object that only displays the subtrees at the given paths of
+/// its sorted [`Debug`](fmt::Debug) output, as a map from each path to its subtree.
+///
+/// Paths are dotted struct field names and map keys (string keys without quotes), with
+/// `[index]` for the elements of lists, sets, and tuples, eg. `response.headers[0]`. Paths
+/// that don't exist in the value are displayed as `<missing>`.
+///
+/// ```rust
+/// use pretty_assertions_sorted::FocusedDebug;
+///
+/// #[derive(Debug)]
+/// struct Response {
+///     status: u16,
+///     body: &'static str,
+/// }
+///
+/// let response = Response { status: 200, body: "ok" };
+/// assert_eq!(
+///     format!("{:?}", FocusedDebug::new(&response, &["body"])),
+///     "{\n    \"body\": \"ok\",\n}"
+/// );
+/// ```
+pub struct FocusedDebug<'a, T: ?Sized> {
+    value: &'a T,
+    paths: &'a [&'a str],
+}
+
+impl<'a, T: ?Sized> FocusedDebug<'a, T> {
+    pub fn new(value: &'a T, paths: &'a [&'a str]) -> Self {
+        Self { value, paths }
+    }
+}
+
+impl<'a, T: fmt::Debug + ?Sized> FocusedDebug<'a, T> {
+    /// The paths that don't exist in the value, which are displayed as `<missing>`.
+    pub fn missing_paths(&self) -> Vec<&'a str> {
+        let value = sorted_value(self.value, &SortOptions::default());
+        self.paths
+            .iter()
+            .copied()
+            .filter(|path| !contains(&value, &parse_path(path)))
+            .collect()
+    }
+}
+
+impl<'a, T: fmt::Debug + ?Sized> fmt::Debug for FocusedDebug<'a, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let options = SortOptions::default();
+        let mut value = sorted_value(self.value, &options);
+
+        let values = self
+            .paths
+            .iter()
+            .map(|path| KeyValue {
+                key: Value::Term(Term::String(path.to_string())),
+                value: take(&mut value, &parse_path(path)).unwrap_or_else(|| {
+                    Value::Term(Term::UnquotedRawString("<missing>".to_string()))
+                }),
+            })
+            .collect();
+
+        let focused = Value::Map(Map { values });
+        f.write_str(&render::Renderer::new(&options).render(&focused))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_paths() {
+        assert_eq!(
+            parse_path("response.items[2][0].name"),
+            vec![
+                Selector::Name("response"),
+                Selector::Name("items"),
+                Selector::Index(2),
+                Selector::Index(0),
+                Selector::Name("name"),
+            ]
+        );
+    }
+}
@@ -0,0 +1,265 @@
+//! Pairing map keys that differ slightly between two values, see
+//! [`assert_eq_sorted_fuzzy_keys`](crate::assert_eq_sorted_fuzzy_keys).
+use crate::diff::{Path, PathSegment};
+use crate::{render, sort, sorted_values, SortOptions};
+use darrentsung_debug_parser::{OrNonExhaustive, Term, Value};
+use std::fmt;
+
+/// The similarity of two strings between 0 (nothing in common) and 1 (equal): one minus
+/// their Levenshtein distance divided by the length of the longer string.
+fn similarity(a: &str, b: &str) -> f64 {
+    let (a, b) = (a.chars().collect::<Vec<_>>(), b.chars().collect::<Vec<_>>());
+    if a.is_empty() && b.is_empty() {
+        return 1.0;
+    }
+
+    // The distances from the prefixes of `a` to the current prefix of `b`.
+    let mut distances = (0..=a.len()).collect::<Vec<_>>();
+    for (j, b_char) in b.iter().enumerate() {
+        let mut diagonal = distances[0];
+        distances[0] = j + 1;
+        for (i, a_char) in a.iter().enumerate() {
+            let substitution = diagonal + usize::from(a_char != b_char);
+            diagonal = distances[i + 1];
+            distances[i + 1] = substitution.min(distances[i] + 1).min(distances[i + 1] + 1);
+        }
+    }
+    1.0 - distances[a.len()] as f64 / a.len().max(b.len()) as f64
+}
+
+/// The text of a map key that's compared for similarity: the contents of string keys, or
+/// the single-line Debug output of other keys.
+fn key_text(key: &Value) -> String {
+    match key {
+        Value::Term(Term::String(s)) => s.clone(),
+        _ => render::render_inline(key),
+    }
+}
+
+/// A key that renders the same as `key`, since [`Value`] can't be cloned.
+fn copy_key(key: &Value) -> Value {
+    match key {
+        Value::Term(Term::String(s)) => Value::Term(Term::String(s.clone())),
+        _ => Value::Term(Term::UnquotedRawString(render::render_inline(key))),
+    }
+}
+
+/// Walks the values in tandem and renames the keys of the right maps that don't exist in
+/// the left map to the most similar left key that doesn't exist in the right map, if
+/// they're at least `threshold` similar. The best matching pairs are picked first. The
+/// paths of keys that stay unpaired are collected.
+fn pair_keys(
+    path: &mut Vec<PathSegment>,
+    left: &Value,
+    right: &mut Value,
+    threshold: f64,
+    unpaired: &mut Vec<String>,
+) {
+    match (left, right) {
+        (Value::Map(l), Value::Map(r)) => {
+            let unmatched_left = l
+                .values
+                .iter()
+                .filter(|l_kv| !r.values.iter().any(|r_kv| r_kv.key == l_kv.key))
+                .collect::<Vec<_>>();
+            let unmatched_right = (0..r.values.len())
+                .filter(|&index| !l.values.iter().any(|l_kv| l_kv.key == r.values[index].key))
+                .collect::<Vec<_>>();
+
+            let mut candidates = vec![];
+            for (l_index, l_kv) in unmatched_left.iter().enumerate() {
+                for &r_index in &unmatched_right {
+                    let score = similarity(&key_text(&l_kv.key), &key_text(&r.values[r_index].key));
+                    if score >= threshold {
+                        candidates.push((score, l_index, r_index));
+                    }
+                }
+            }
+            candidates.sort_by(|a, b| b.0.total_cmp(&a.0).then((a.1, a.2).cmp(&(b.1, b.2))));
+
+            let (mut paired_left, mut paired_right) = (vec![], vec![]);
+            for (_, l_index, r_index) in candidates {
+                if !paired_left.contains(&l_index) && !paired_right.contains(&r_index) {
+                    r.values[r_index].key = copy_key(&unmatched_left[l_index].key);
+                    paired_left.push(l_index);
+                    paired_right.push(r_index);
+                }
+            }
+
+            for (l_index, l_kv) in unmatched_left.iter().enumerate() {
+                if !paired_left.contains(&l_index) {
+                    path.push(PathSegment::key(&l_kv.key));
+                    unpaired.push(format!("left: {}", Path(path.clone())));
+                    path.pop();
+                }
+            }
+            for &r_index in &unmatched_right {
+                if !paired_right.contains(&r_index) {
+                    path.push(PathSegment::key(&r.values[r_index].key));
+                    unpaired.push(format!("right: {}", Path(path.clone())));
+                    path.pop();
+                }
+            }
+
+            for l_kv in &l.values {
+                if let Some(r_kv) = r.values.iter_mut().find(|r_kv| r_kv.key == l_kv.key) {
+                    path.push(PathSegment::key(&l_kv.key));
+                    pair_keys(path, &l_kv.value, &mut r_kv.value, threshold, unpaired);
+                    path.pop();
+                }
+            }
+        }
+        (Value::Struct(l), Value::Struct(r)) => {
+            for l_v in &l.values {
+                let l_ident_value = match l_v {
+                    OrNonExhaustive::Value(ident_value) => ident_value,
+                    OrNonExhaustive::NonExhaustive => continue,
+                };
+                let r_ident_value = r.values.iter_mut().find_map(|r_v| match r_v {
+                    OrNonExhaustive::Value(ident_value)
+                        if ident_value.ident == l_ident_value.ident =>
+                    {
+                        Some(ident_value)
+                    }
+                    _ => None,
+                });
+                if let Some(r_ident_value) = r_ident_value {
+                    path.push(PathSegment::Field(l_ident_value.ident.clone()));
+                    pair_keys(
+                        path,
+                        &l_ident_value.value,
+                        &mut r_ident_value.value,
+                        threshold,
+                        unpaired,
+                    );
+                    path.pop();
+                }
+            }
+        }
+        (Value::List(l), Value::List(r)) => {
+            pair_elements(path, &l.values, &mut r.values, threshold, unpaired)
+        }
+        (Value::Set(l), Value::Set(r)) => {
+            pair_elements(path, &l.values, &mut r.values, threshold, unpaired)
+        }
+        (Value::Tuple(l), Value::Tuple(r)) => {
+            pair_elements(path, &l.values, &mut r.values, threshold, unpaired)
+        }
+        _ => (),
+    }
+}
+
+fn pair_elements(
+    path: &mut Vec<PathSegment>,
+    left: &[Value],
+    right: &mut [Value],
+    threshold: f64,
+    unpaired: &mut Vec<String>,
+) {
+    for (index, (l, r)) in left.iter().zip(right.iter_mut()).enumerate() {
+        path.push(PathSegment::Index(index));
+        pair_keys(path, l, r, threshold, unpaired);
+        path.pop();
+    }
+}
+
+/// The sorted [`Debug`](fmt::Debug) representations of two values after pairing their map
+/// keys by similarity, see [`sorted_pair_fuzzy_keys`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct FuzzyKeyPairing {
+    /// The sorted, pretty Debug representation of the left value.
+    pub left: String,
+    /// The sorted, pretty Debug representation of the right value, where the keys that
+    /// were paired with a left key are renamed to that key.
+    pub right: String,
+    /// The paths of the keys that couldn't be paired, prefixed by their side, eg.
+    /// `left: sessions.a1b2`.
+    pub unpaired: Vec<String>,
+}
+
+/// Pairs the map keys of `right` that don't exist in `left` with the most similar missing
+/// key of `left`, if their similarity is at least `threshold`, and returns the sorted
+/// representations of both values with the paired keys renamed to match.
+///
+/// Similarity is one minus the Levenshtein distance of the keys divided by the length of
+/// the longer key, so `1.0` only pairs equal keys. Keys are paired greedily, the most
+/// similar pairs first. This is meant for keys that are volatile but correspond one to
+/// one, eg. generated identifiers.
+///
+/// ```rust
+/// use std::collections::HashMap;
+/// use pretty_assertions_sorted::sorted_pair_fuzzy_keys;
+///
+/// let left = HashMap::from([("user-1a2b", 1), ("admin", 2)]);
+/// let right = HashMap::from([("user-1a2c", 1), ("root", 2)]);
+///
+/// let pairing = sorted_pair_fuzzy_keys(&left, &right, 0.8);
+/// assert_eq!(pairing.right, "{\n    \"root\": 2,\n    \"user-1a2b\": 1,\n}");
+/// assert_eq!(pairing.unpaired, vec!["left: admin", "right: root"]);
+/// ```
+pub fn sorted_pair_fuzzy_keys<L, R>(left: &L, right: &R, threshold: f64) -> FuzzyKeyPairing
+where
+    L: fmt::Debug + ?Sized,
+    R: fmt::Debug + ?Sized,
+{
+    let options = SortOptions::default();
+    let (left, mut right) = sorted_values(left, right, &options);
+
+    let mut unpaired = vec![];
+    pair_keys(&mut vec![], &left, &mut right, threshold, &mut unpaired);
+    sort::sort_maps(&mut right, &options);
+
+    let renderer = render::Renderer::new(&options);
+    FuzzyKeyPairing {
+        left: renderer.render(&left),
+        right: renderer.render(&right),
+        unpaired,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn scores_similarity_by_edit_distance() {
+        assert_eq!(similarity("abcd", "abcd"), 1.0);
+        assert_eq!(similarity("abcd", "abce"), 0.75);
+        assert_eq!(similarity("abcd", "ab"), 0.5);
+        assert_eq!(similarity("", "abcd"), 0.0);
+        assert_eq!(similarity("", ""), 1.0);
+    }
+
+    #[derive(Debug)]
+    #[allow(unused)]
+    struct Sessions {
+        by_id: HashMap<&'static str, u32>,
+    }
+
+    #[test]
+    fn pairs_most_similar_keys_first() {
+        let left = Sessions {
+            by_id: HashMap::from([("id-aaaa", 1), ("id-aabb", 2), ("shared", 3)]),
+        };
+        let right = Sessions {
+            by_id: HashMap::from([("id-aabc", 2), ("id-aaab", 1), ("shared", 3)]),
+        };
+
+        let pairing = sorted_pair_fuzzy_keys(&left, &right, 0.7);
+        assert_eq!(pairing.left, pairing.right);
+        assert!(pairing.unpaired.is_empty());
+
+        let pairing = sorted_pair_fuzzy_keys(&left, &right, 0.9);
+        assert_ne!(pairing.left, pairing.right);
+        assert_eq!(
+            pairing.unpaired,
+            vec![
+                "left: by_id.id-aaaa",
+                "left: by_id.id-aabb",
+                "right: by_id.id-aaab",
+                "right: by_id.id-aabc",
+            ]
+        );
+    }
+}
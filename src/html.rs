@@ -0,0 +1,178 @@
+//! HTML rendering of the differences found by the tandem walk of [`diff`](crate::diff), for
+//! test dashboards.
+use crate::diff::{diff, DiffOp, Path};
+use crate::{render, sorted_values, SortOptions};
+use darrentsung_debug_parser::Value;
+use std::fmt;
+use std::fmt::Write;
+
+/// Escapes the characters that have a special meaning in HTML text and attributes.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Writes the escaped path followed by `: `, or nothing for the root of the value.
+fn write_path(out: &mut String, path: &Path) {
+    let path = path.to_string();
+    if !path.is_empty() {
+        let _ = write!(out, "<span class=\"diff-path\">{}</span>: ", escape(&path));
+    }
+}
+
+fn value_html(value: &Value) -> String {
+    escape(&render::render_inline(value))
+}
+
+/// Renders the differences between the sorted [`Debug`](fmt::Debug) representations of
+/// `left` and `right` as an HTML fragment, or returns `None` if there are no differences.
+///
+/// The fragment is a `<pre class="sorted-diff">` with one line per difference. Each line is
+/// a `<span>` with one of the following classes, which can be styled by the page that
+/// embeds the fragment:
+/// * `diff-add`: a value that only exists in `right`, prefixed with `+`.
+/// * `diff-remove`: a value that only exists in `left`, prefixed with `-`.
+/// * `diff-change`: a value that differs, prefixed with `~`. It contains the old value in
+///   a `diff-remove` span and the new value in a `diff-add` span.
+///
+/// The path of each value is wrapped in a `diff-path` span. All Debug output is escaped.
+///
+/// ```rust
+/// use std::collections::HashMap;
+/// use pretty_assertions_sorted::sorted_diff_html;
+///
+/// let left: HashMap<_, _> = vec![("a", 1), ("b", 2)].into_iter().collect();
+/// let right: HashMap<_, _> = vec![("a", 1), ("b", 3)].into_iter().collect();
+///
+/// assert_eq!(
+///     sorted_diff_html(&left, &right).unwrap(),
+///     "<pre class=\"sorted-diff\">\
+///      <span class=\"diff-change\">~ <span class=\"diff-path\">b</span>: \
+///      <span class=\"diff-remove\">2</span> -&gt; <span class=\"diff-add\">3</span></span>\n\
+///      </pre>"
+/// );
+/// assert_eq!(sorted_diff_html(&left, &left), None);
+/// ```
+pub fn sorted_diff_html<L, R>(left: &L, right: &R) -> Option<String>
+where
+    L: fmt::Debug + ?Sized,
+    R: fmt::Debug + ?Sized,
+{
+    let options = SortOptions::default();
+    let (left, right) = sorted_values(left, right, &options);
+
+    let ops = diff(&left, &right, &options);
+    if ops.is_empty() {
+        return None;
+    }
+
+    let mut out = String::from("<pre class=\"sorted-diff\">");
+    for op in ops {
+        match op {
+            DiffOp::Added { path, right } => {
+                out.push_str("<span class=\"diff-add\">+ ");
+                write_path(&mut out, &path);
+                out.push_str(&value_html(right));
+            }
+            DiffOp::Removed { path, left } => {
+                out.push_str("<span class=\"diff-remove\">- ");
+                write_path(&mut out, &path);
+                out.push_str(&value_html(left));
+            }
+            DiffOp::Changed { path, left, right } => {
+                out.push_str("<span class=\"diff-change\">~ ");
+                write_path(&mut out, &path);
+                let _ = write!(
+                    out,
+                    "<span class=\"diff-remove\">{}</span> -&gt; <span class=\"diff-add\">{}</span>",
+                    value_html(left),
+                    value_html(right),
+                );
+            }
+        }
+        out.push_str("</span>\n");
+    }
+    out.push_str("</pre>");
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[derive(Debug)]
+    #[allow(unused)]
+    struct Page {
+        title: &'static str,
+        tags: Vec<&'static str>,
+        meta: HashMap<&'static str, &'static str>,
+    }
+
+    /// Checks that every opened tag is closed in order and that no stray `<`, `>`, or `&`
+    /// is left in the text.
+    fn assert_well_formed(html: &str) {
+        let mut open = vec![];
+        let mut rest = html;
+        while let Some(start) = rest.find(['<', '>', '&']) {
+            let text = &rest[start..];
+            if text.starts_with('>') {
+                panic!("Unescaped `>` in {:?}", html);
+            } else if text.starts_with('&') {
+                let end = text.find(';').expect("Unterminated entity");
+                assert!(
+                    ["&amp;", "&lt;", "&gt;", "&quot;", "&#39;"].contains(&&text[..=end]),
+                    "Unknown entity in {:?}",
+                    html
+                );
+                rest = &text[end + 1..];
+            } else {
+                let end = text.find('>').expect("Unterminated tag");
+                let tag = &text[1..end];
+                match tag.strip_prefix('/') {
+                    Some(name) => assert_eq!(open.pop(), Some(name.to_string())),
+                    None => open.push(tag.split(' ').next().unwrap().to_string()),
+                }
+                rest = &text[end + 1..];
+            }
+        }
+        assert!(open.is_empty(), "Unclosed tags {:?} in {:?}", open, html);
+    }
+
+    #[test]
+    fn renders_well_formed_escaped_html() {
+        let left = Page {
+            title: "<b>Tom & Jerry</b>",
+            tags: vec!["cartoon", "cat"],
+            meta: HashMap::from([("lang", "en"), ("<script>", "1")]),
+        };
+        let right = Page {
+            title: "Tom & Jerry",
+            tags: vec!["cartoon"],
+            meta: HashMap::from([("lang", "en"), ("year", "'40")]),
+        };
+
+        let html = sorted_diff_html(&left, &right).unwrap();
+        assert_well_formed(&html);
+        assert_eq!(
+            html.lines().collect::<Vec<_>>(),
+            vec![
+                "<pre class=\"sorted-diff\"><span class=\"diff-change\">~ <span class=\"diff-path\">title</span>: <span class=\"diff-remove\">&quot;&lt;b&gt;Tom &amp; Jerry&lt;/b&gt;&quot;</span> -&gt; <span class=\"diff-add\">&quot;Tom &amp; Jerry&quot;</span></span>",
+                "<span class=\"diff-remove\">- <span class=\"diff-path\">tags[1]</span>: &quot;cat&quot;</span>",
+                "<span class=\"diff-remove\">- <span class=\"diff-path\">meta.&lt;script&gt;</span>: &quot;1&quot;</span>",
+                "<span class=\"diff-add\">+ <span class=\"diff-path\">meta.year</span>: &quot;&#39;40&quot;</span>",
+                "</pre>",
+            ]
+        );
+    }
+}
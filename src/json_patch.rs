@@ -0,0 +1,216 @@
+//! Machine-readable diffs in the JSON Patch format, built on the tandem walk of
+//! [`diff`](crate::diff).
+use crate::diff::{diff, DiffOp, PathSegment};
+use crate::{render, sorted_values, SortOptions};
+use darrentsung_debug_parser::{OrNonExhaustive, Term, Value};
+use serde_json::{json, Map, Number};
+use std::fmt;
+
+/// Converts a parsed Debug value into JSON, in roughly the shape that serde would serialize
+/// the original value into: structs and maps become objects, lists, sets, and tuples become
+/// arrays, `None` becomes `null`, and a tuple struct with a single value (eg. `Some(1)`)
+/// becomes its value.
+pub(crate) fn to_json(value: &Value) -> serde_json::Value {
+    match value {
+        Value::Struct(s) => serde_json::Value::Object(
+            s.values
+                .iter()
+                .filter_map(|v| match v {
+                    OrNonExhaustive::Value(ident_value) => {
+                        Some((ident_value.ident.clone(), to_json(&ident_value.value)))
+                    }
+                    OrNonExhaustive::NonExhaustive => None,
+                })
+                .collect(),
+        ),
+        Value::Map(map) => serde_json::Value::Object(
+            map.values
+                .iter()
+                .map(|kv| (key_text(&kv.key), to_json(&kv.value)))
+                .collect::<Map<_, _>>(),
+        ),
+        Value::Set(set) => set.values.iter().map(to_json).collect(),
+        Value::List(list) => list.values.iter().map(to_json).collect(),
+        Value::Tuple(tuple) => match (&tuple.name, &tuple.values[..]) {
+            (Some(_), [value]) => to_json(value),
+            _ => tuple.values.iter().map(to_json).collect(),
+        },
+        Value::Term(Term::String(s)) => serde_json::Value::String(s.clone()),
+        Value::Term(Term::Ident(ident)) => match ident.as_str() {
+            "None" => serde_json::Value::Null,
+            _ => serde_json::Value::String(ident.clone()),
+        },
+        Value::Term(Term::UnquotedRawString(raw)) => match raw.as_str() {
+            "true" => json!(true),
+            "false" => json!(false),
+            _ => raw
+                .parse::<i64>()
+                .ok()
+                .map(Number::from)
+                .or_else(|| raw.parse::<u64>().ok().map(Number::from))
+                .or_else(|| raw.parse::<f64>().ok().and_then(Number::from_f64))
+                .map_or_else(
+                    || serde_json::Value::String(raw.clone()),
+                    serde_json::Value::Number,
+                ),
+        },
+    }
+}
+
+/// The key of a map entry in a JSON object: the contents of string keys, or the
+/// single-line Debug output of other keys.
+fn key_text(key: &Value) -> String {
+    match key {
+        Value::Term(Term::String(s)) => s.clone(),
+        _ => render::render_inline(key),
+    }
+}
+
+/// Formats a path as a JSON pointer (RFC 6901), eg. `/response/headers/0`.
+fn json_pointer(path: &[PathSegment]) -> String {
+    path.iter()
+        .map(|segment| {
+            let token = match segment {
+                PathSegment::Field(name) | PathSegment::Key(name) => name.clone(),
+                PathSegment::Index(index) => index.to_string(),
+            };
+            format!("/{}", token.replace('~', "~0").replace('/', "~1"))
+        })
+        .collect()
+}
+
+/// Returns a JSON Patch (RFC 6902) that transforms the JSON form of the sorted
+/// [`Debug`](fmt::Debug) representation of `left` into that of `right`.
+///
+/// The values are converted into JSON in roughly the shape that serde would serialize them
+/// into: structs and maps become objects (keyed by field name or the text of the map key),
+/// lists, sets, and tuples become arrays, `None` becomes `null`, and `Some(x)` becomes `x`.
+/// The patch consists of `add`, `remove`, and `replace` operations found by walking the
+/// values in tandem. Elements of sets are matched by equality, so the position of an added
+/// set element in the patched array may differ from its position in `right`.
+///
+/// ```rust
+/// use std::collections::HashMap;
+/// use pretty_assertions_sorted::sorted_json_patch;
+///
+/// let left: HashMap<_, _> = vec![("a", 1), ("b", 2)].into_iter().collect();
+/// let right: HashMap<_, _> = vec![("a", 1), ("b", 3), ("c", 4)].into_iter().collect();
+///
+/// assert_eq!(
+///     sorted_json_patch(&left, &right),
+///     serde_json::json!([
+///         { "op": "replace", "path": "/b", "value": 3 },
+///         { "op": "add", "path": "/c", "value": 4 },
+///     ])
+/// );
+/// ```
+pub fn sorted_json_patch<L, R>(left: &L, right: &R) -> serde_json::Value
+where
+    L: fmt::Debug + ?Sized,
+    R: fmt::Debug + ?Sized,
+{
+    let options = SortOptions::default();
+    let (left, right) = sorted_values(left, right, &options);
+
+    let mut patch: Vec<serde_json::Value> = vec![];
+    // Elements removed from the end of an array are removed from the last one backwards,
+    // so that the indices of the elements that are still to be removed don't shift.
+    let mut removed_elements: Vec<serde_json::Value> = vec![];
+    for op in diff(&left, &right, &options) {
+        if !matches!(&op, DiffOp::Removed { path, .. } if matches!(path.0.last(), Some(PathSegment::Index(_))))
+        {
+            patch.extend(removed_elements.drain(..).rev());
+        }
+
+        match op {
+            DiffOp::Added { path, right } => patch.push(json!({
+                "op": "add",
+                "path": json_pointer(&path.0),
+                "value": to_json(right),
+            })),
+            DiffOp::Removed { path, .. } => {
+                let remove = json!({ "op": "remove", "path": json_pointer(&path.0) });
+                match path.0.last() {
+                    Some(PathSegment::Index(_)) => removed_elements.push(remove),
+                    _ => patch.push(remove),
+                }
+            }
+            DiffOp::Changed { path, right, .. } => patch.push(json!({
+                "op": "replace",
+                "path": json_pointer(&path.0),
+                "value": to_json(right),
+            })),
+        }
+    }
+    patch.extend(removed_elements.drain(..).rev());
+
+    serde_json::Value::Array(patch)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sorted_value;
+    use std::collections::HashMap;
+
+    #[derive(Debug)]
+    #[allow(unused)]
+    struct Order {
+        id: u64,
+        note: Option<&'static str>,
+        items: Vec<&'static str>,
+        prices: HashMap<&'static str, f64>,
+    }
+
+    /// Applies a patch of `add`, `remove`, and `replace` operations to the document.
+    fn apply(document: &mut serde_json::Value, patch: &serde_json::Value) {
+        for op in patch.as_array().unwrap() {
+            let pointer = op["path"].as_str().unwrap();
+            let (parent, token) = pointer.rsplit_once('/').unwrap();
+            let token = token.replace("~1", "/").replace("~0", "~");
+            let parent = document.pointer_mut(parent).unwrap();
+            match (op["op"].as_str().unwrap(), parent) {
+                ("add", serde_json::Value::Array(array)) => {
+                    array.insert(token.parse().unwrap(), op["value"].clone())
+                }
+                ("add", serde_json::Value::Object(object)) => {
+                    object.insert(token, op["value"].clone());
+                }
+                ("remove", serde_json::Value::Array(array)) => {
+                    array.remove(token.parse().unwrap());
+                }
+                ("remove", serde_json::Value::Object(object)) => {
+                    object.remove(&token);
+                }
+                ("replace", _) => *document.pointer_mut(pointer).unwrap() = op["value"].clone(),
+                (op, _) => panic!("Unexpected operation: {}", op),
+            }
+        }
+    }
+
+    fn json_of<T: fmt::Debug>(value: &T) -> serde_json::Value {
+        to_json(&sorted_value(value, &SortOptions::default()))
+    }
+
+    #[test]
+    fn patch_transforms_left_into_right() {
+        let left = Order {
+            id: 1,
+            note: None,
+            items: vec!["apple", "pear", "plum", "fig"],
+            prices: HashMap::from([("apple", 1.5), ("a/b", 2.0), ("pear", 0.5)]),
+        };
+        let right = Order {
+            id: 2,
+            note: Some("gift"),
+            items: vec!["apple", "kiwi"],
+            prices: HashMap::from([("apple", 1.5), ("fig", 3.0)]),
+        };
+
+        for (left, right) in [(&left, &right), (&right, &left)].iter() {
+            let mut document = json_of(left);
+            apply(&mut document, &sorted_json_patch(left, right));
+            assert_eq!(document, json_of(right));
+        }
+    }
+}
@@ -24,18 +24,91 @@
 //! ```
 //!
 //! `assert_eq` is provided as a re-export of `pretty_assertions::assert_eq` and should
-//! be used if you don't want the Debug output to be sorted, or if the Debug output can't
-//! be sorted (not supported types, eg. f64::NEG_INFINITY, or custom Debug output).
+//! be used if you don't want the Debug output to be sorted. If the Debug output of a type
+//! can't be sorted (not supported types, eg. custom Debug output), `assert_eq_sorted` falls
+//! back to displaying the unsorted Debug output for that type.
+//!
+//! ## Ignoring all ordering
+//!
+//! `assert_eq_sorted_deep_unordered` additionally sorts every list and set, at every level
+//! of nesting. Use it for values like `HashMap<K, Vec<V>>` where none of the orderings are
+//! meaningful. **This clobbers every ordering in the compared values**, so don't use it if
+//! the order of any list matters to your test!
+//!
+//! ## Fixtures
+//!
+//! With the `ron` or `json` feature enabled, `assert_eq_sorted_fixture` compares a value
+//! against the expected value stored in a fixture file, eg.
+//! `assert_eq_sorted_fixture!(actual, "tests/fixtures/actual.ron")`. Run the tests with
+//! `UPDATE_SORTED_FIXTURES=1` to regenerate the fixtures from the actual values.
+//!
+//! The `json` feature also enables `sorted_json_patch`, which describes the differences
+//! between two values as a JSON Patch (RFC 6902) for tools that consume diffs.
+//!
+//! With the `html` feature enabled, `sorted_diff_html` renders the differences between two
+//! values as an HTML fragment for test dashboards.
+//!
+//! With the `derive` feature enabled, `#[derive(SortedDebugKey)]` picks the field that map
+//! keys of a type are sorted by, see `SortOptions::sort_key`.
 //!
 //! ## Tip
 //!
 //! Specify it as [`[dev-dependencies]`](http://doc.crates.io/specifying-dependencies.html#development-dependencies)
 //! and it will only be used for compiling tests, examples, and benchmarks.
 //! This way the compile time of `cargo build` won't be affected!
+use std::borrow::Cow;
+use std::collections::{BTreeMap, BTreeSet};
 use std::fmt;
+use std::sync::OnceLock;
 
 use darrentsung_debug_parser::*;
-pub use pretty_assertions::{assert_eq, assert_ne, Comparison};
+
+mod alignment;
+mod diff;
+#[cfg(any(feature = "ron", feature = "json"))]
+mod fixture;
+mod focus;
+mod fuzzy_keys;
+#[cfg(feature = "html")]
+mod html;
+#[cfg(feature = "json")]
+mod json_patch;
+mod multiset;
+mod preprocess;
+mod render;
+mod sections;
+mod sort;
+mod stable_order;
+mod tolerance;
+
+pub use alignment::{sorted_elements, AlignedComparison};
+pub use diff::{
+    sorted_diff_stats, sorted_differences, sorted_differences_with_options, sorted_flattened,
+    sorted_key_summary, sorted_similarity, DiffStats, Difference, KeySummary,
+};
+#[cfg(any(feature = "ron", feature = "json"))]
+pub use fixture::{load_fixture, UPDATE_FIXTURES_ENV_VAR};
+pub use focus::FocusedDebug;
+pub use fuzzy_keys::{sorted_pair_fuzzy_keys, FuzzyKeyPairing};
+#[cfg(feature = "html")]
+pub use html::sorted_diff_html;
+#[cfg(feature = "json")]
+pub use json_patch::sorted_json_patch;
+pub use multiset::{sorted_counts, MultisetComparison};
+#[cfg(feature = "derive")]
+pub use pretty_assertions_sorted_derive::SortedDebugKey;
+pub use sections::sort_map_sections;
+pub use sort::{set_global_term_normalizer, SortCriterion, SortOrder, SortedDebugKey};
+pub use stable_order::{key_order, load_golden_order, UPDATE_ORDER_ENV_VAR};
+pub use tolerance::{sorted_approx_eq, sorted_tolerance_violations, ToleranceViolation};
+
+pub use pretty_assertions::{assert_eq, assert_ne, Comparison, StrComparison};
+
+/// The parsed [`Debug`](fmt::Debug) representation of a value, see [`parse_sorted`].
+pub use darrentsung_debug_parser::{
+    IdentValue, KeyValue, List, Map, OrNonExhaustive, Set, Struct, Term, Tuple, Value,
+};
+use yansi::Paint;
 
 /// This is a wrapper with similar functionality to [`assert_eq`], however, the
 /// [`Debug`] representation is sorted to provide deterministic output.
@@ -47,28 +120,136 @@ pub use pretty_assertions::{assert_eq, assert_ne, Comparison};
 /// Don't use this if you want to test the ordering of the types that are sorted, since
 /// sorting will clobber any previous ordering.
 ///
+/// The sides of the diff can be given meaningful names instead of "left" and "right":
+///
+/// ```rust
+/// use pretty_assertions_sorted::assert_eq_sorted;
+///
+/// let actual = vec![1, 2];
+/// assert_eq_sorted!(actual => "actual", vec![1, 2] => "expected");
+/// ```
+///
+/// [`SortOptions`] can be given after the values as `option = value` pairs, each calling
+/// the builder method of the same name:
+///
+/// ```rust
+/// use pretty_assertions_sorted::assert_eq_sorted;
+///
+/// assert_eq_sorted!(vec![1, 2], vec![1, 2], max_diff_bytes = 4096);
+/// ```
+///
+/// The message can be given as a closure returning something [`Display`](std::fmt::Display)
+/// instead of format arguments, which is only called if the assertion fails:
+///
+/// ```rust
+/// use pretty_assertions_sorted::assert_eq_sorted;
+///
+/// # fn expensive_context() -> String { String::new() }
+/// assert_eq_sorted!(vec![1, 2], vec![1, 2], || format!("context: {}", expensive_context()));
+/// ```
+///
 /// Potential use-cases that aren't implemented yet:
-/// * Blocklist for field names that shouldn't be sorted
-/// * Sorting more than just maps (struct fields, lists, etc.)
+/// * Sorting struct fields (lists are sorted with [`SortOptions::sort_lists`])
 #[macro_export]
 macro_rules! assert_eq_sorted {
+    ($left:expr => $left_label:expr, $right:expr => $right_label:expr$(,)?) => ({
+        $crate::assert_eq_sorted!(@ $left, $right, $left_label, $right_label, $crate::SortOptions::default(), "", "");
+    });
+    ($left:expr => $left_label:expr, $right:expr => $right_label:expr, $($key:ident = $value:expr),+$(,)?) => ({
+        $crate::assert_eq_sorted!(@ $left, $right, $left_label, $right_label, $crate::SortOptions::default()$(.$key($value))+, "", "");
+    });
+    ($left:expr => $left_label:expr, $right:expr => $right_label:expr, $(move)? || $message:expr$(,)?) => ({
+        $crate::assert_eq_sorted!(@ $left, $right, $left_label, $right_label, $crate::SortOptions::default(), ": ", "{}", $message);
+    });
+    ($left:expr => $left_label:expr, $right:expr => $right_label:expr, $($arg:tt)*) => ({
+        $crate::assert_eq_sorted!(@ $left, $right, $left_label, $right_label, $crate::SortOptions::default(), ": ", $($arg)+);
+    });
+    ($left:expr, $right:expr$(,)?) => ({
+        $crate::assert_eq_sorted!(@ $left, $right, "left", "right", $crate::SortOptions::default(), "", "");
+    });
+    ($left:expr, $right:expr, unordered_lists$(,)?) => ({
+        $crate::assert_eq_sorted!($left, $right, unordered_lists = true);
+    });
+    ($left:expr, $right:expr, $($key:ident = $value:expr),+$(,)?) => ({
+        $crate::assert_eq_sorted!(@ $left, $right, "left", "right", $crate::SortOptions::default()$(.$key($value))+, "", "");
+    });
+    ($left:expr, $right:expr, $(move)? || $message:expr$(,)?) => ({
+        $crate::assert_eq_sorted!(@ $left, $right, "left", "right", $crate::SortOptions::default(), ": ", "{}", $message);
+    });
+    ($left:expr, $right:expr, $($arg:tt)*) => ({
+        $crate::assert_eq_sorted!(@ $left, $right, "left", "right", $crate::SortOptions::default(), ": ", $($arg)+);
+    });
+    (@ $left:expr, $right:expr, $left_label:expr, $right_label:expr, $options:expr, $maybe_semicolon:expr, $($arg:tt)*) => ({
+        match (&($left), &($right)) {
+            (left_val, right_val) => {
+                let options: $crate::SortOptions = $options;
+                if !(*left_val == *right_val || options.normalized_eq(left_val, right_val)) {
+                    let (left_label, right_label) = (&$left_label, &$right_label);
+                    // We create the comparison string outside the panic! call
+                    // because creating the comparison string could panic itself.
+                    let (left_sorted, right_sorted) =
+                        $crate::SortedDebug::pair(left_val, right_val, options.clone());
+                    let comparison_string = $crate::LabeledComparison::new(
+                        &left_sorted,
+                        &right_sorted,
+                        left_label,
+                        right_label,
+                    )
+                    .with_options(&options)
+                    .with_unsorted(left_val, right_val)
+                    .to_string();
+                    let summary_string = $crate::sorted_key_summary(left_val, right_val, &options)
+                        .map(|summary| ::std::format!("\n{}", summary.describe(left_label, right_label)))
+                        .unwrap_or_default();
+                    ::core::panic!("assertion failed: `({} == {})`{}{}{}\
+                       \n\
+                       \n{}\
+                       \n",
+                       left_label,
+                       right_label,
+                       $maybe_semicolon,
+                       format_args!($($arg)*),
+                       summary_string,
+                       comparison_string,
+                    )
+                }
+            }
+        }
+    });
+}
+
+/// This is a wrapper with similar functionality to [`assert_ne`], however, the
+/// [`Debug`] representation is sorted to provide deterministic output.
+///
+/// ```rust
+/// use std::collections::HashMap;
+/// use pretty_assertions_sorted::assert_ne_sorted;
+///
+/// let left: HashMap<_, _> = vec![("a", 1), ("b", 2)].into_iter().collect();
+/// let right: HashMap<_, _> = vec![("a", 1), ("b", 3)].into_iter().collect();
+/// assert_ne_sorted!(left, right);
+/// ```
+#[macro_export]
+macro_rules! assert_ne_sorted {
     ($left:expr, $right:expr$(,)?) => ({
-        $crate::assert_eq_sorted!(@ $left, $right, "", "");
+        $crate::assert_ne_sorted!(@ $left, $right, "", "");
     });
     ($left:expr, $right:expr, $($arg:tt)*) => ({
-        $crate::assert_eq_sorted!(@ $left, $right, ": ", $($arg)+);
+        $crate::assert_ne_sorted!(@ $left, $right, ": ", $($arg)+);
     });
     (@ $left:expr, $right:expr, $maybe_semicolon:expr, $($arg:tt)*) => ({
         match (&($left), &($right)) {
             (left_val, right_val) => {
-                if !(*left_val == *right_val) {
+                if *left_val == *right_val {
                     // We create the comparison string outside the panic! call
                     // because creating the comparison string could panic itself.
+                    let (left_sorted, right_sorted) =
+                        $crate::SortedDebug::pair(left_val, right_val, $crate::SortOptions::default());
                     let comparison_string = $crate::Comparison::new(
-                        &$crate::SortedDebug::new(left_val),
-                        &$crate::SortedDebug::new(right_val)
+                        &left_sorted,
+                        &right_sorted,
                     ).to_string();
-                    ::core::panic!("assertion failed: `(left == right)`{}{}\
+                    ::core::panic!("assertion failed: `(left != right)`{}{}\
                        \n\
                        \n{}\
                        \n",
@@ -82,416 +263,5987 @@ macro_rules! assert_eq_sorted {
     });
 }
 
-/// New-type wrapper around an object that sorts the fmt::Debug output when displayed for
-/// deterministic output.
+/// Same as [`assert_eq_sorted`], but the expected value is built by a closure, for
+/// expected values that are expensive to construct.
 ///
-/// This works through parsing the output and sorting the `debug_map()` type.
+/// The closure is called exactly once, after the actual value has been evaluated, so any
+/// side effects of building the expected value can't affect the actual value. Both values
+/// are only formatted with [`Debug`] when they aren't equal by [`PartialEq`]: to compare
+/// their sorted representations when the options call for it (see
+/// [equality](SortOptions#equality)), and to build the diff if the assertion fails.
+/// Labels aren't supported, but [`SortOptions`] and a custom message can be given like with
+/// [`assert_eq_sorted`].
 ///
-/// DISCLAIMER: This Debug implementation will panic if the inner value's Debug
-/// representation can't be sorted. This is used to notify users when used in tests. An
-/// alternative solution of falling back to non-sorted could be implemented.
+/// ```rust
+/// use pretty_assertions_sorted::assert_eq_sorted_lazy;
 ///
-/// Potential use-cases that aren't implemented yet:
-/// * Blocklist for field names that shouldn't be sorted
-/// * Sorting more than just maps (struct fields, lists, etc.)
-pub struct SortedDebug<T>(T);
-
-impl<T> SortedDebug<T> {
-    pub fn new(v: T) -> Self {
-        Self(v)
-    }
+/// let actual = vec![1, 2, 3];
+/// assert_eq_sorted_lazy!(actual, || (1..=3).collect::<Vec<_>>());
+/// ```
+#[macro_export]
+macro_rules! assert_eq_sorted_lazy {
+    ($left:expr, $right:expr$(,)?) => ({
+        $crate::assert_eq_sorted!($left, ($right)());
+    });
+    ($left:expr, $right:expr, $($arg:tt)*) => ({
+        $crate::assert_eq_sorted!($left, ($right)(), $($arg)*);
+    });
 }
 
-impl<T: fmt::Debug> fmt::Debug for SortedDebug<T> {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let mut value = match parse(&format!("{:?}", self.0)) {
-            Ok(value) => value,
-            Err(err) => {
-                ::core::panic!("Failed to parse Debug output for sorting (please use `assert_eq!` instead and/or file an issue for your use-case)!\nError: {}", err)
+/// This is a wrapper with similar functionality to [`assert_eq_sorted`], however, every
+/// collection in the [`Debug`] representation is sorted: maps, sets, and lists at all levels
+/// of nesting.
+///
+/// This is the "I don't care about any ordering anywhere" version of [`assert_eq_sorted`].
+/// It is useful for types like `HashMap<K, Vec<V>>` where both the map and the lists inside
+/// of it are built from unordered sources.
+///
+/// **Warning**: this clobbers every ordering in the compared values, including the order of
+/// `Vec`s and slices. Two values are considered equal if their fully sorted [`Debug`]
+/// representations are equal, [`PartialEq`] is not used. Don't use this if any ordering in
+/// the values is meaningful to your test!
+#[macro_export]
+macro_rules! assert_eq_sorted_deep_unordered {
+    ($left:expr, $right:expr$(,)?) => ({
+        $crate::assert_eq_sorted_deep_unordered!(@ $left, $right, "", "");
+    });
+    ($left:expr, $right:expr, $($arg:tt)*) => ({
+        $crate::assert_eq_sorted_deep_unordered!(@ $left, $right, ": ", $($arg)+);
+    });
+    (@ $left:expr, $right:expr, $maybe_semicolon:expr, $($arg:tt)*) => ({
+        match (&($left), &($right)) {
+            (left_val, right_val) => {
+                let options = $crate::SortOptions::default().sort_lists(true);
+                let (left_sorted, right_sorted) =
+                    $crate::SortedDebug::pair(left_val, right_val, options);
+                if ::std::format!("{:?}", left_sorted) != ::std::format!("{:?}", right_sorted) {
+                    // We create the comparison string outside the panic! call
+                    // because creating the comparison string could panic itself.
+                    let comparison_string = $crate::Comparison::new(
+                        &left_sorted,
+                        &right_sorted
+                    ).to_string();
+                    ::core::panic!("assertion failed: `(left == right)`{}{}\
+                       \n\
+                       \n{}\
+                       \n",
+                       $maybe_semicolon,
+                       format_args!($($arg)*),
+                       comparison_string,
+                    )
+                }
             }
-        };
-
-        sort_maps(&mut value);
-
-        // Replace one-line non-exhaustive objects with empty brackets separated by
-        // newlines. This changes output like: "Foo { .. }" with "Foo {\n}". "Foo {\n}" is
-        // more desirable because it diffs better against some multi-line output of "Foo {
-        // value: 10.0 }" (imagine the newlines please).
-        let formatted_output = format!("{:#?}", value).replace("{ .. }", "{\n}");
-        fmt::Display::fmt(&formatted_output, f)
-    }
+        }
+    });
 }
 
-fn sort_maps(v: &mut Value) {
-    match v {
-        Value::Struct(s) => {
-            for ident_value_or_non_exhaustive in &mut s.values {
-                match ident_value_or_non_exhaustive {
-                    OrNonExhaustive::Value(ident_value) => {
-                        sort_maps(&mut ident_value.value);
-                    }
-                    OrNonExhaustive::NonExhaustive => (),
+/// Asserts that the sorted [`Debug`] representation of a value is equal to the sorted
+/// representation of any of several acceptable alternatives.
+///
+/// This is useful for values that are legitimately non-deterministic beyond ordering and
+/// have a small set of acceptable forms. On failure, the sorted diff is shown against the
+/// closest alternative (the one with the fewest differing values, see [`sorted_diff_stats`]).
+///
+/// ```rust
+/// use pretty_assertions_sorted::assert_eq_sorted_any;
+///
+/// let winner = "red";
+/// assert_eq_sorted_any!(winner, ["blue", "red"]);
+/// ```
+#[macro_export]
+macro_rules! assert_eq_sorted_any {
+    ($left:expr, [$($right:expr),+$(,)?]$(,)?) => ({
+        $crate::assert_eq_sorted_any!(@ $left, [$($right),+], "", "");
+    });
+    ($left:expr, [$($right:expr),+$(,)?], $($arg:tt)*) => ({
+        $crate::assert_eq_sorted_any!(@ $left, [$($right),+], ": ", $($arg)+);
+    });
+    (@ $left:expr, [$($right:expr),+], $maybe_semicolon:expr, $($arg:tt)*) => ({
+        match (&($left), [$(&($right)),+]) {
+            (left_val, right_vals) => {
+                let matches_any = right_vals.iter().any(|right_val| {
+                    let (left_sorted, right_sorted) = $crate::SortedDebug::pair(
+                        left_val,
+                        right_val,
+                        $crate::SortOptions::default(),
+                    );
+                    ::std::format!("{:?}", left_sorted) == ::std::format!("{:?}", right_sorted)
+                });
+                if !matches_any {
+                    let (closest_index, closest_val) = right_vals
+                        .iter()
+                        .enumerate()
+                        .min_by_key(|(_, right_val)| {
+                            $crate::sorted_diff_stats(left_val, *right_val).total()
+                        })
+                        .expect("at least one alternative");
+                    // We create the comparison string outside the panic! call
+                    // because creating the comparison string could panic itself.
+                    let (left_sorted, right_sorted) =
+                        $crate::SortedDebug::pair(left_val, closest_val, $crate::SortOptions::default());
+                    let comparison_string = $crate::Comparison::new(
+                        &left_sorted,
+                        &right_sorted,
+                    ).to_string();
+                    ::core::panic!("assertion failed: `left` matches none of the {} alternatives{}{}\
+                       \n\
+                       \nDiff against the closest alternative (#{}):\
+                       \n{}\
+                       \n",
+                       right_vals.len(),
+                       $maybe_semicolon,
+                       format_args!($($arg)*),
+                       closest_index + 1,
+                       comparison_string,
+                    )
                 }
             }
         }
-        Value::Set(s) => {
-            for child_v in &mut s.values {
-                sort_maps(child_v);
+    });
+}
+
+/// Asserts that two iterators yield the same elements the same number of times, in any
+/// order.
+///
+/// Elements are identified by their sorted [`Debug`] representation, see [`sorted_counts`].
+/// This is useful for comparing event streams or query results where neither the order of
+/// the elements nor the order of the maps inside of them is meaningful. On failure, the
+/// over and under-represented elements are shown with the number of times they occur on
+/// each side.
+///
+/// ```rust
+/// use pretty_assertions_sorted::assert_eq_sorted_multiset;
+///
+/// assert_eq_sorted_multiset!(vec![1, 2, 2, 3], vec![2, 3, 2, 1]);
+/// ```
+#[macro_export]
+macro_rules! assert_eq_sorted_multiset {
+    ($left:expr, $right:expr$(,)?) => ({
+        $crate::assert_eq_sorted_multiset!(@ $left, $right, "", "");
+    });
+    ($left:expr, $right:expr, $($arg:tt)*) => ({
+        $crate::assert_eq_sorted_multiset!(@ $left, $right, ": ", $($arg)+);
+    });
+    (@ $left:expr, $right:expr, $maybe_semicolon:expr, $($arg:tt)*) => ({
+        match ($crate::sorted_counts($left), $crate::sorted_counts($right)) {
+            (left_counts, right_counts) => {
+                if left_counts != right_counts {
+                    // We create the comparison string outside the panic! call
+                    // because creating the comparison string could panic itself.
+                    let comparison_string = $crate::MultisetComparison::new(
+                        &left_counts,
+                        &right_counts
+                    ).to_string();
+                    ::core::panic!("assertion failed: `(left == right)` as multisets \
+                       ({} elements in left, {} elements in right){}{}\
+                       \n\
+                       \n{}\
+                       \n",
+                       left_counts.values().sum::<usize>(),
+                       right_counts.values().sum::<usize>(),
+                       $maybe_semicolon,
+                       format_args!($($arg)*),
+                       comparison_string,
+                    )
+                }
             }
         }
-        Value::Map(map) => {
-            map.values.sort_by(|a, b| a.key.cmp(&b.key));
+    });
+}
 
-            for key_value in &mut map.values {
-                sort_maps(&mut key_value.key);
-                sort_maps(&mut key_value.value);
+/// Asserts that a value is equal to the expected value stored in a fixture file, showing
+/// the sorted diff on failure (see [`assert_eq_sorted`]).
+///
+/// The fixture is deserialized into the type of the actual value with serde, in the format
+/// given by the file extension: `.ron` with the `ron` feature, `.json` with the `json`
+/// feature. Relative paths are resolved against the directory of the crate being tested.
+///
+/// Run the tests with `UPDATE_SORTED_FIXTURES=1` (see [`UPDATE_FIXTURES_ENV_VAR`]) to
+/// regenerate the fixtures from the actual values.
+///
+/// ```rust,ignore
+/// use pretty_assertions_sorted::assert_eq_sorted_fixture;
+///
+/// assert_eq_sorted_fixture!(load_config(), "tests/fixtures/config.ron");
+/// ```
+#[cfg(any(feature = "ron", feature = "json"))]
+#[macro_export]
+macro_rules! assert_eq_sorted_fixture {
+    ($actual:expr, $path:expr$(,)?) => ({
+        $crate::assert_eq_sorted_fixture!(@ $actual, $path, "", "");
+    });
+    ($actual:expr, $path:expr, $($arg:tt)*) => ({
+        $crate::assert_eq_sorted_fixture!(@ $actual, $path, ": ", $($arg)+);
+    });
+    (@ $actual:expr, $path:expr, $maybe_semicolon:expr, $($arg:tt)*) => ({
+        match (&($actual), &($path)) {
+            (actual_val, path) => {
+                let path: &::std::path::Path = ::std::convert::AsRef::as_ref(path);
+                let expected = $crate::load_fixture(
+                    &::std::path::Path::new(::core::env!("CARGO_MANIFEST_DIR")).join(path),
+                    actual_val,
+                );
+                let path_label = ::std::string::ToString::to_string(&path.display());
+                $crate::assert_eq_sorted!(@ *actual_val, expected, "actual", path_label.as_str(), $crate::SortOptions::default(), $maybe_semicolon, $($arg)*);
             }
         }
-        Value::List(l) => {
-            for child_v in &mut l.values {
-                sort_maps(child_v);
+    });
+}
+
+/// Asserts that the map keys and struct fields of a value are printed in the same order as
+/// on the first run, for code whose output order should be stable even though it isn't
+/// sorted.
+///
+/// On the first run the order is recorded in the golden file at the given path, see
+/// [`key_order`]. Relative paths are resolved against the directory of the crate being
+/// tested. Later runs fail with a diff of the recorded and the actual order. Run the tests
+/// with `UPDATE_SORTED_ORDER=1` (see [`UPDATE_ORDER_ENV_VAR`]) to record the order again.
+///
+/// ```rust,ignore
+/// use pretty_assertions_sorted::assert_stable_order;
+///
+/// assert_stable_order!(build_headers(), "tests/golden/headers.golden");
+/// ```
+#[macro_export]
+macro_rules! assert_stable_order {
+    ($value:expr, $path:expr$(,)?) => ({
+        $crate::assert_stable_order!(@ $value, $path, "", "");
+    });
+    ($value:expr, $path:expr, $($arg:tt)*) => ({
+        $crate::assert_stable_order!(@ $value, $path, ": ", $($arg)+);
+    });
+    (@ $value:expr, $path:expr, $maybe_semicolon:expr, $($arg:tt)*) => ({
+        match (&($value), &($path)) {
+            (value_val, path) => {
+                let actual = $crate::key_order(value_val);
+                let golden = $crate::load_golden_order(
+                    &::std::path::Path::new(::core::env!("CARGO_MANIFEST_DIR")).join(path),
+                    &actual,
+                );
+                if actual != golden {
+                    let mut actual_keys = actual.lines().collect::<::std::vec::Vec<_>>();
+                    let mut golden_keys = golden.lines().collect::<::std::vec::Vec<_>>();
+                    actual_keys.sort_unstable();
+                    golden_keys.sort_unstable();
+                    let change = if actual_keys == golden_keys {
+                        "keys were reordered"
+                    } else {
+                        "keys were added or removed"
+                    };
+                    // We create the comparison string outside the panic! call
+                    // because creating the comparison string could panic itself.
+                    let comparison_string = $crate::StrComparison::new(
+                        &golden,
+                        &actual,
+                    ).to_string();
+                    ::core::panic!("assertion failed: key order matches {}, {}{}{}\
+                       \n\
+                       \n{}\
+                       \n",
+                       path,
+                       change,
+                       $maybe_semicolon,
+                       format_args!($($arg)*),
+                       comparison_string,
+                    )
+                }
             }
         }
-        Value::Tuple(t) => {
-            for child_v in &mut t.values {
-                sort_maps(child_v);
+    });
+}
+
+/// Asserts that `right` is a permutation of `left`: both contain the same elements the same
+/// number of times, in any order.
+///
+/// This is a borrowing version of [`assert_eq_sorted_multiset`] for `Vec`s, slices, and
+/// other collections with an `iter()` method, eg. for testing shuffling or reordering
+/// functions without sorting every list with [`SortOptions::sort_lists`]. On failure, the
+/// elements that occur a different number of times are shown.
+///
+/// ```rust
+/// use pretty_assertions_sorted::assert_permutation_sorted;
+///
+/// let original = vec!["a", "b", "c"];
+/// let shuffled = vec!["c", "a", "b"];
+/// assert_permutation_sorted!(original, shuffled);
+/// ```
+#[macro_export]
+macro_rules! assert_permutation_sorted {
+    ($left:expr, $right:expr$(,)?) => ({
+        $crate::assert_permutation_sorted!(@ $left, $right, "", "");
+    });
+    ($left:expr, $right:expr, $($arg:tt)*) => ({
+        $crate::assert_permutation_sorted!(@ $left, $right, ": ", $($arg)+);
+    });
+    (@ $left:expr, $right:expr, $maybe_semicolon:expr, $($arg:tt)*) => ({
+        match (&($left), &($right)) {
+            (left_val, right_val) => {
+                let left_counts = $crate::sorted_counts(left_val.iter());
+                let right_counts = $crate::sorted_counts(right_val.iter());
+                if left_counts != right_counts {
+                    // We create the comparison string outside the panic! call
+                    // because creating the comparison string could panic itself.
+                    let comparison_string = $crate::MultisetComparison::new(
+                        &left_counts,
+                        &right_counts
+                    ).to_string();
+                    ::core::panic!("assertion failed: `right` is a permutation of `left` \
+                       ({} elements in left, {} elements in right){}{}\
+                       \n\
+                       \n{}\
+                       \n",
+                       left_counts.values().sum::<usize>(),
+                       right_counts.values().sum::<usize>(),
+                       $maybe_semicolon,
+                       format_args!($($arg)*),
+                       comparison_string,
+                    )
+                }
             }
         }
-        // No need to recurse for Term variant.
-        Value::Term(_) => (),
-    }
+    });
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use indoc::indoc;
-    use std::assert_eq;
-    use std::collections::HashMap;
-
-    const TEST_RERUNS_FOR_DETERMINISM: u32 = 100;
-
-    fn sorted_debug<T: fmt::Debug>(v: T) -> String {
-        format!("{:#?}", SortedDebug(v))
-    }
-
-    #[test]
-    fn noop_sorts() {
-        for _ in 0..TEST_RERUNS_FOR_DETERMINISM {
-            assert_eq!(sorted_debug(2), "2");
+/// Asserts that two sequences are equal, showing the elements that were inserted or
+/// removed in place on failure.
+///
+/// The elements are compared by their sorted [`Debug`] representations (see
+/// [`sorted_elements`]) and aligned by their longest common subsequence, which reads better
+/// than a diff of the whole sequences for genuinely ordered sequences with a few edits. The
+/// sequences can be `Vec`s, slices, or other collections with an `iter()` method.
+///
+/// ```rust
+/// use pretty_assertions_sorted::assert_eq_sorted_lcs;
+///
+/// let steps = vec!["fetch", "build", "test"];
+/// assert_eq_sorted_lcs!(steps, ["fetch", "build", "test"]);
+/// ```
+#[macro_export]
+macro_rules! assert_eq_sorted_lcs {
+    ($left:expr, $right:expr$(,)?) => ({
+        $crate::assert_eq_sorted_lcs!(@ $left, $right, "", "");
+    });
+    ($left:expr, $right:expr, $($arg:tt)*) => ({
+        $crate::assert_eq_sorted_lcs!(@ $left, $right, ": ", $($arg)+);
+    });
+    (@ $left:expr, $right:expr, $maybe_semicolon:expr, $($arg:tt)*) => ({
+        match (&($left), &($right)) {
+            (left_val, right_val) => {
+                let left_elements = $crate::sorted_elements(left_val.iter());
+                let right_elements = $crate::sorted_elements(right_val.iter());
+                if left_elements != right_elements {
+                    // We create the comparison string outside the panic! call
+                    // because creating the comparison string could panic itself.
+                    let comparison_string = $crate::AlignedComparison::new(
+                        &left_elements,
+                        &right_elements
+                    ).to_string();
+                    ::core::panic!("assertion failed: `(left == right)` \
+                       ({} elements in left, {} elements in right){}{}\
+                       \n\
+                       \n{}\
+                       \n",
+                       left_elements.len(),
+                       right_elements.len(),
+                       $maybe_semicolon,
+                       format_args!($($arg)*),
+                       comparison_string,
+                    )
+                }
+            }
         }
-    }
-
-    #[test]
-    fn sorts_hashmap() {
-        for _ in 0..TEST_RERUNS_FOR_DETERMINISM {
-            // Note that we have to create the HashMaps each try
-            // in order to induce non-determinism in the debug output.
-            let item = {
-                let mut map = HashMap::new();
-                map.insert(1, true);
-                map.insert(2, true);
-                map.insert(20, true);
-                map
-            };
+    });
+}
 
-            let expected = indoc!(
-                "{
-                    1: true,
-                    2: true,
-                    20: true,
-                }"
+/// Asserts that the sorted [`Debug`] representations of two values differ in at most
+/// `max_differences` leaf values, see [`sorted_diff_stats`].
+///
+/// This is useful for tests against slightly non-deterministic external systems, where a
+/// small number of differing fields is tolerable. When the threshold is exceeded, the
+/// number of differences is reported along with the full sorted diff.
+///
+/// ```rust
+/// use std::collections::HashMap;
+/// use pretty_assertions_sorted::assert_eq_sorted_within;
+///
+/// let left: HashMap<_, _> = vec![("a", 1), ("b", 2)].into_iter().collect();
+/// let right: HashMap<_, _> = vec![("a", 1), ("b", 3)].into_iter().collect();
+/// assert_eq_sorted_within!(left, right, max_differences = 1);
+/// ```
+#[macro_export]
+macro_rules! assert_eq_sorted_within {
+    ($left:expr, $right:expr, max_differences = $max:expr$(,)?) => ({
+        $crate::assert_eq_sorted_within!(@ $left, $right, $max, "", "");
+    });
+    ($left:expr, $right:expr, max_differences = $max:expr, $($arg:tt)*) => ({
+        $crate::assert_eq_sorted_within!(@ $left, $right, $max, ": ", $($arg)+);
+    });
+    (@ $left:expr, $right:expr, $max:expr, $maybe_semicolon:expr, $($arg:tt)*) => ({
+        match (&($left), &($right), $max) {
+            (left_val, right_val, max_differences) => {
+                let differences = $crate::sorted_diff_stats(left_val, right_val).total();
+                if differences > max_differences {
+                    // We create the comparison string outside the panic! call
+                    // because creating the comparison string could panic itself.
+                    let (left_sorted, right_sorted) =
+                        $crate::SortedDebug::pair(left_val, right_val, $crate::SortOptions::default());
+                    let comparison_string = $crate::Comparison::new(
+                        &left_sorted,
+                        &right_sorted,
+                    ).to_string();
+                    ::core::panic!("assertion failed: `(left == right)` within {} differences, \
+                       found {} differences{}{}\
+                       \n\
+                       \n{}\
+                       \n",
+                       max_differences,
+                       differences,
+                       $maybe_semicolon,
+                       format_args!($($arg)*),
+                       comparison_string,
+                    )
+                }
+            }
+        }
+    });
+}
+
+/// Asserts that two captured [`Debug`] representations are equal after sorting them, see
+/// [`sort_debug_str`].
+///
+/// This is useful for regression tests against archived Debug output, where the original
+/// typed value isn't available. On failure, a diff of the sorted representations is shown.
+/// If either string can't be parsed, both strings are compared as they are instead.
+///
+/// ```rust
+/// use std::collections::HashMap;
+/// use pretty_assertions_sorted::assert_debug_str_eq_sorted;
+///
+/// let fresh: HashMap<_, _> = vec![("a", 1), ("b", 2)].into_iter().collect();
+/// assert_debug_str_eq_sorted!(format!("{:?}", fresh), r#"{"b": 2, "a": 1}"#);
+/// ```
+#[macro_export]
+macro_rules! assert_debug_str_eq_sorted {
+    ($left:expr, $right:expr$(,)?) => ({
+        $crate::assert_debug_str_eq_sorted!(@ $left, $right, "", "");
+    });
+    ($left:expr, $right:expr, $($arg:tt)*) => ({
+        $crate::assert_debug_str_eq_sorted!(@ $left, $right, ": ", $($arg)+);
+    });
+    (@ $left:expr, $right:expr, $maybe_semicolon:expr, $($arg:tt)*) => ({
+        match (&($left), &($right)) {
+            (left_val, right_val) => {
+                let (left_str, right_str): (&str, &str) = (left_val.as_ref(), right_val.as_ref());
+                let (left_text, right_text, how) = match (
+                    $crate::sort_debug_str(left_str),
+                    $crate::sort_debug_str(right_str),
+                ) {
+                    (Some(left_text), Some(right_text)) => (left_text, right_text, "sorted"),
+                    _ => (left_str.to_string(), right_str.to_string(), "unparseable"),
+                };
+                if left_text != right_text {
+                    // We create the comparison string outside the panic! call
+                    // because creating the comparison string could panic itself.
+                    let comparison_string = $crate::StrComparison::new(
+                        &left_text,
+                        &right_text,
+                    ).to_string();
+                    ::core::panic!("assertion failed: `(left == right)` with {} Debug strings{}{}\
+                       \n\
+                       \n{}\
+                       \n",
+                       how,
+                       $maybe_semicolon,
+                       format_args!($($arg)*),
+                       comparison_string,
+                    )
+                }
+            }
+        }
+    });
+}
+
+/// Asserts that two text files are equal after sorting the map-shaped sections in them, see
+/// [`sort_map_sections`].
+///
+/// This is useful for comparing generated files that embed maps (eg. pretty Debug output or
+/// JSON), where the ordering of the maps would otherwise cause spurious differences. On
+/// failure, a line diff of the files with their map sections sorted is shown.
+///
+/// ```rust,no_run
+/// use pretty_assertions_sorted::assert_files_eq_sorted;
+///
+/// assert_files_eq_sorted!("target/generated.txt", "tests/expected/generated.txt");
+/// ```
+#[macro_export]
+macro_rules! assert_files_eq_sorted {
+    ($left:expr, $right:expr$(,)?) => ({
+        $crate::assert_files_eq_sorted!(@ $left, $right, "", "");
+    });
+    ($left:expr, $right:expr, $($arg:tt)*) => ({
+        $crate::assert_files_eq_sorted!(@ $left, $right, ": ", $($arg)+);
+    });
+    (@ $left:expr, $right:expr, $maybe_semicolon:expr, $($arg:tt)*) => ({
+        match (&($left), &($right)) {
+            (left_path, right_path) => {
+                let read = |path: &::std::path::Path| {
+                    ::std::fs::read_to_string(path)
+                        .map(|text| $crate::sort_map_sections(&text))
+                        .unwrap_or_else(|err| ::core::panic!("Failed to read {}: {}", path.display(), err))
+                };
+                let left_path: &::std::path::Path = left_path.as_ref();
+                let right_path: &::std::path::Path = right_path.as_ref();
+                let (left_text, right_text) = (read(left_path), read(right_path));
+                if left_text != right_text {
+                    // We create the comparison string outside the panic! call
+                    // because creating the comparison string could panic itself.
+                    let comparison_string = $crate::StrComparison::new(
+                        &left_text,
+                        &right_text,
+                    ).to_string();
+                    ::core::panic!("assertion failed: `({} == {})` with sorted map sections{}{}\
+                       \n\
+                       \n{}\
+                       \n",
+                       left_path.display(),
+                       right_path.display(),
+                       $maybe_semicolon,
+                       format_args!($($arg)*),
+                       comparison_string,
+                    )
+                }
+            }
+        }
+    });
+}
+
+/// Asserts that the sorted [`Debug`] representations of two values are equal, except for
+/// numeric values that differ by at most the tolerance given for their field or map key.
+///
+/// Tolerances are given per field or map key name (eg. `"latency"`) or per dotted path (eg.
+/// `"metrics.latency"`), see [`sorted_tolerance_violations`]. Values without a tolerance have
+/// to match exactly. On failure, the values that exceeded their tolerance are listed above
+/// the sorted diff.
+///
+/// ```rust
+/// use std::collections::HashMap;
+/// use pretty_assertions_sorted::assert_eq_sorted_tol;
+///
+/// let left: HashMap<_, _> = vec![("latency", 100.0), ("rate", 0.5)].into_iter().collect();
+/// let right: HashMap<_, _> = vec![("latency", 103.0), ("rate", 0.505)].into_iter().collect();
+/// assert_eq_sorted_tol!(left, right, { "latency" => 5.0, "rate" => 0.01 });
+/// ```
+#[macro_export]
+macro_rules! assert_eq_sorted_tol {
+    ($left:expr, $right:expr, { $($key:expr => $tolerance:expr),*$(,)? }$(,)?) => ({
+        $crate::assert_eq_sorted_tol!(@ $left, $right, [$(($key, $tolerance)),*], "", "");
+    });
+    ($left:expr, $right:expr, { $($key:expr => $tolerance:expr),*$(,)? }, $($arg:tt)*) => ({
+        $crate::assert_eq_sorted_tol!(@ $left, $right, [$(($key, $tolerance)),*], ": ", $($arg)+);
+    });
+    (@ $left:expr, $right:expr, [$($tolerances:tt)*], $maybe_semicolon:expr, $($arg:tt)*) => ({
+        match (&($left), &($right)) {
+            (left_val, right_val) => {
+                let tolerances: &[(&str, f64)] = &[$($tolerances)*];
+                let violations = $crate::sorted_tolerance_violations(left_val, right_val, tolerances);
+                if !violations.is_empty() {
+                    // We create the comparison string outside the panic! call
+                    // because creating the comparison string could panic itself.
+                    let violations_string = violations
+                        .iter()
+                        .map(|violation| ::std::format!("  {}\n", violation))
+                        .collect::<::std::string::String>();
+                    let (left_sorted, right_sorted) =
+                        $crate::SortedDebug::pair(left_val, right_val, $crate::SortOptions::default());
+                    let comparison_string = $crate::Comparison::new(
+                        &left_sorted,
+                        &right_sorted,
+                    ).to_string();
+                    ::core::panic!("assertion failed: `(left == right)` within tolerances, \
+                       {} values exceeded their tolerance{}{}\
+                       \n{}\
+                       \n{}\
+                       \n",
+                       violations.len(),
+                       $maybe_semicolon,
+                       format_args!($($arg)*),
+                       violations_string,
+                       comparison_string,
+                    )
+                }
+            }
+        }
+    });
+}
+
+/// Asserts that the sorted [`Debug`] representations of two values are equal, except for
+/// numeric values (eg. `f32` and `f64` leaves) that differ by at most an epsilon, see
+/// [`sorted_approx_eq`]. The epsilon defaults to `f64::EPSILON`.
+///
+/// Unlike [`assert_eq_sorted_tol`], the epsilon applies to every numeric value. On failure,
+/// the sorted diff is shown.
+///
+/// ```rust
+/// use std::collections::HashMap;
+/// use pretty_assertions_sorted::assert_eq_sorted_approx;
+///
+/// let left: HashMap<_, _> = vec![("total", 0.1 + 0.2), ("rate", 0.5)].into_iter().collect();
+/// let right: HashMap<_, _> = vec![("rate", 0.5), ("total", 0.3)].into_iter().collect();
+/// assert_eq_sorted_approx!(left, right);
+/// assert_eq_sorted_approx!(vec![1.0], vec![1.001], epsilon = 0.01);
+/// ```
+#[macro_export]
+macro_rules! assert_eq_sorted_approx {
+    ($left:expr, $right:expr, epsilon = $epsilon:expr$(,)?) => ({
+        $crate::assert_eq_sorted_approx!(@ $left, $right, $epsilon, "", "");
+    });
+    ($left:expr, $right:expr, epsilon = $epsilon:expr, $($arg:tt)*) => ({
+        $crate::assert_eq_sorted_approx!(@ $left, $right, $epsilon, ": ", $($arg)+);
+    });
+    ($left:expr, $right:expr$(,)?) => ({
+        $crate::assert_eq_sorted_approx!(@ $left, $right, f64::EPSILON, "", "");
+    });
+    ($left:expr, $right:expr, $($arg:tt)*) => ({
+        $crate::assert_eq_sorted_approx!(@ $left, $right, f64::EPSILON, ": ", $($arg)+);
+    });
+    (@ $left:expr, $right:expr, $epsilon:expr, $maybe_semicolon:expr, $($arg:tt)*) => ({
+        match (&($left), &($right)) {
+            (left_val, right_val) => {
+                let epsilon: f64 = $epsilon;
+                if !$crate::sorted_approx_eq(left_val, right_val, epsilon) {
+                    // We create the comparison string outside the panic! call
+                    // because creating the comparison string could panic itself.
+                    let (left_sorted, right_sorted) =
+                        $crate::SortedDebug::pair(left_val, right_val, $crate::SortOptions::default());
+                    let comparison_string = $crate::Comparison::new(
+                        &left_sorted,
+                        &right_sorted,
+                    ).to_string();
+                    ::core::panic!("assertion failed: `(left == right)` within epsilon {}{}{}\
+                       \n\
+                       \n{}\
+                       \n",
+                       epsilon,
+                       $maybe_semicolon,
+                       format_args!($($arg)*),
+                       comparison_string,
+                    )
+                }
+            }
+        }
+    });
+}
+
+/// Asserts that the sorted [`Debug`] representations of two values are equal. On failure,
+/// the sorted diff is followed by a numbered list of every difference and its path (see
+/// [`sorted_differences`]), eg. `1. response.code: 200 -> 500`.
+///
+/// ```rust
+/// use std::collections::HashMap;
+/// use pretty_assertions_sorted::assert_eq_sorted_report;
+///
+/// let left: HashMap<_, _> = vec![("code", 200), ("retries", 0)].into_iter().collect();
+/// let right: HashMap<_, _> = vec![("retries", 0), ("code", 200)].into_iter().collect();
+/// assert_eq_sorted_report!(left, right);
+/// ```
+#[macro_export]
+macro_rules! assert_eq_sorted_report {
+    ($left:expr, $right:expr$(,)?) => ({
+        $crate::assert_eq_sorted_report!(@ $left, $right, "", "");
+    });
+    ($left:expr, $right:expr, $($arg:tt)*) => ({
+        $crate::assert_eq_sorted_report!(@ $left, $right, ": ", $($arg)+);
+    });
+    (@ $left:expr, $right:expr, $maybe_semicolon:expr, $($arg:tt)*) => ({
+        match (&($left), &($right)) {
+            (left_val, right_val) => {
+                if !(*left_val == *right_val) {
+                    // We create the comparison string outside the panic! call
+                    // because creating the comparison string could panic itself.
+                    let (left_sorted, right_sorted) =
+                        $crate::SortedDebug::pair(left_val, right_val, $crate::SortOptions::default());
+                    let comparison_string = $crate::Comparison::new(
+                        &left_sorted,
+                        &right_sorted,
+                    ).to_string();
+                    let differences_string = $crate::sorted_differences(left_val, right_val)
+                        .iter()
+                        .enumerate()
+                        .map(|(index, difference)| ::std::format!("{}. {}\n", index + 1, difference))
+                        .collect::<::std::string::String>();
+                    ::core::panic!("assertion failed: `(left == right)`{}{}\
+                       \n\
+                       \n{}\
+                       \n\
+                       \nDifferences:\
+                       \n{}",
+                       $maybe_semicolon,
+                       format_args!($($arg)*),
+                       comparison_string,
+                       differences_string,
+                    )
+                }
+            }
+        }
+    });
+}
+
+/// Asserts that `enriched` is a refinement of `base`: every struct field, map entry, and
+/// element of `base` exists in `enriched` with an equal value, but `enriched` may add new
+/// ones. On failure, the values of `base` that were changed or removed are listed above
+/// the sorted diff.
+///
+/// Values are compared by walking their sorted [`Debug`] representations in tandem (see
+/// [`sorted_differences`]), so lists may only be extended at the end.
+///
+/// ```rust
+/// use std::collections::HashMap;
+/// use pretty_assertions_sorted::assert_refines_sorted;
+///
+/// let base: HashMap<_, _> = vec![("id", 1)].into_iter().collect();
+/// let enriched: HashMap<_, _> = vec![("id", 1), ("score", 10)].into_iter().collect();
+/// assert_refines_sorted!(base, enriched);
+/// ```
+#[macro_export]
+macro_rules! assert_refines_sorted {
+    ($base:expr, $enriched:expr$(,)?) => ({
+        $crate::assert_refines_sorted!(@ $base, $enriched, "", "");
+    });
+    ($base:expr, $enriched:expr, $($arg:tt)*) => ({
+        $crate::assert_refines_sorted!(@ $base, $enriched, ": ", $($arg)+);
+    });
+    (@ $base:expr, $enriched:expr, $maybe_semicolon:expr, $($arg:tt)*) => ({
+        match (&($base), &($enriched)) {
+            (base_val, enriched_val) => {
+                let violations = $crate::sorted_differences(base_val, enriched_val)
+                    .into_iter()
+                    .filter(|difference| difference.left.is_some())
+                    .collect::<::std::vec::Vec<_>>();
+                if !violations.is_empty() {
+                    // We create the comparison string outside the panic! call
+                    // because creating the comparison string could panic itself.
+                    let violations_string = violations
+                        .iter()
+                        .map(|violation| ::std::format!("  {}\n", violation))
+                        .collect::<::std::string::String>();
+                    let (left_sorted, right_sorted) =
+                        $crate::SortedDebug::pair(base_val, enriched_val, $crate::SortOptions::default());
+                    let comparison_string = $crate::LabeledComparison::new(
+                        &left_sorted,
+                        &right_sorted,
+                        "base",
+                        "enriched",
+                    ).to_string();
+                    ::core::panic!("assertion failed: `(enriched refines base)`, \
+                       {} values of base were changed or removed{}{}\
+                       \n{}\
+                       \n{}\
+                       \n",
+                       violations.len(),
+                       $maybe_semicolon,
+                       format_args!($($arg)*),
+                       violations_string,
+                       comparison_string,
+                    )
+                }
+            }
+        }
+    });
+}
+
+/// Asserts that two values are equal, showing a diff of their flattened `path = value`
+/// lines on failure (see [`sorted_flattened`]), like a diff of two properties files.
+///
+/// This is especially readable for deeply nested configs, where every changed line shows
+/// the full path to the value.
+///
+/// ```rust
+/// use std::collections::HashMap;
+/// use pretty_assertions_sorted::assert_eq_sorted_flat;
+///
+/// let left: HashMap<_, _> = vec![("a", vec![1]), ("b", vec![2])].into_iter().collect();
+/// let right: HashMap<_, _> = vec![("b", vec![2]), ("a", vec![1])].into_iter().collect();
+/// assert_eq_sorted_flat!(left, right);
+/// ```
+#[macro_export]
+macro_rules! assert_eq_sorted_flat {
+    ($left:expr, $right:expr$(,)?) => ({
+        $crate::assert_eq_sorted_flat!(@ $left, $right, "", "");
+    });
+    ($left:expr, $right:expr, $($arg:tt)*) => ({
+        $crate::assert_eq_sorted_flat!(@ $left, $right, ": ", $($arg)+);
+    });
+    (@ $left:expr, $right:expr, $maybe_semicolon:expr, $($arg:tt)*) => ({
+        match (&($left), &($right)) {
+            (left_val, right_val) => {
+                if !(*left_val == *right_val) {
+                    let lines = |flattened: ::std::vec::Vec<(::std::string::String, ::std::string::String)>| {
+                        flattened
+                            .iter()
+                            .map(|(path, value)| ::std::format!("{} = {}\n", path, value))
+                            .collect::<::std::string::String>()
+                    };
+                    let left_lines = lines($crate::sorted_flattened(left_val));
+                    let right_lines = lines($crate::sorted_flattened(right_val));
+                    // We create the comparison string outside the panic! call
+                    // because creating the comparison string could panic itself.
+                    let comparison_string = $crate::StrComparison::new(
+                        &left_lines,
+                        &right_lines,
+                    ).to_string();
+                    ::core::panic!("assertion failed: `(left == right)` with flattened paths{}{}\
+                       \n\
+                       \n{}\
+                       \n",
+                       $maybe_semicolon,
+                       format_args!($($arg)*),
+                       comparison_string,
+                    )
+                }
+            }
+        }
+    });
+}
+
+/// Asserts that two values are the same enum variant (or struct), ignoring their fields
+/// and payload, see [`debug_variant_name`].
+///
+/// This is useful for state machine tests where the payload of the variants is volatile,
+/// but the variant is what matters. On failure, the two variant names are shown.
+///
+/// ```rust
+/// use pretty_assertions_sorted::assert_eq_sorted_variant;
+///
+/// #[derive(Debug)]
+/// enum State {
+///     Connected { since: u64 },
+///     Disconnected,
+/// }
+///
+/// assert_eq_sorted_variant!(State::Connected { since: 1 }, State::Connected { since: 2 });
+/// ```
+#[macro_export]
+macro_rules! assert_eq_sorted_variant {
+    ($left:expr, $right:expr$(,)?) => ({
+        $crate::assert_eq_sorted_variant!(@ $left, $right, "", "");
+    });
+    ($left:expr, $right:expr, $($arg:tt)*) => ({
+        $crate::assert_eq_sorted_variant!(@ $left, $right, ": ", $($arg)+);
+    });
+    (@ $left:expr, $right:expr, $maybe_semicolon:expr, $($arg:tt)*) => ({
+        match (&($left), &($right)) {
+            (left_val, right_val) => {
+                let left_variant = $crate::debug_variant_name(left_val);
+                let right_variant = $crate::debug_variant_name(right_val);
+                if left_variant != right_variant {
+                    let no_variant = "<no variant>".to_string();
+                    ::core::panic!("assertion failed: `(left == right)` by variant{}{}\
+                       \n\
+                       \n left: {}\
+                       \nright: {}\
+                       \n",
+                       $maybe_semicolon,
+                       format_args!($($arg)*),
+                       left_variant.as_ref().unwrap_or(&no_variant),
+                       right_variant.as_ref().unwrap_or(&no_variant),
+                    )
+                }
+            }
+        }
+    });
+}
+
+/// Asserts that the subtrees at the given paths of the sorted [`Debug`] representations of
+/// two values are equal, ignoring all other differences.
+///
+/// Paths are dotted struct field names and map keys, with `[index]` for the elements of
+/// lists, sets, and tuples, see [`FocusedDebug`]. On failure, the sorted diff of only the
+/// focused subtrees is shown. A path that exists in neither value fails the assertion, since
+/// it's most likely a typo.
+///
+/// ```rust
+/// use pretty_assertions_sorted::assert_eq_sorted_only;
+///
+/// #[derive(Debug)]
+/// struct Response {
+///     request_id: u64,
+///     body: &'static str,
+/// }
+///
+/// assert_eq_sorted_only!(
+///     Response { request_id: 1, body: "ok" },
+///     Response { request_id: 2, body: "ok" },
+///     only = ["body"]
+/// );
+/// ```
+#[macro_export]
+macro_rules! assert_eq_sorted_only {
+    ($left:expr, $right:expr, only = [$($path:expr),+$(,)?]$(,)?) => ({
+        $crate::assert_eq_sorted_only!(@ $left, $right, [$($path),+], "", "");
+    });
+    ($left:expr, $right:expr, only = [$($path:expr),+$(,)?], $($arg:tt)*) => ({
+        $crate::assert_eq_sorted_only!(@ $left, $right, [$($path),+], ": ", $($arg)+);
+    });
+    (@ $left:expr, $right:expr, [$($path:expr),+], $maybe_semicolon:expr, $($arg:tt)*) => ({
+        match (&($left), &($right)) {
+            (left_val, right_val) => {
+                let paths: &[&str] = &[$($path),+];
+                let left_focused = $crate::FocusedDebug::new(left_val, paths);
+                let right_focused = $crate::FocusedDebug::new(right_val, paths);
+                let right_missing = right_focused.missing_paths();
+                let missing = left_focused
+                    .missing_paths()
+                    .into_iter()
+                    .filter(|path| right_missing.contains(path))
+                    .collect::<::std::vec::Vec<_>>();
+                if !missing.is_empty() {
+                    ::core::panic!("assertion failed: path not found in either value: {:?}{}{}",
+                       missing,
+                       $maybe_semicolon,
+                       format_args!($($arg)*),
+                    )
+                }
+                if ::std::format!("{:?}", left_focused) != ::std::format!("{:?}", right_focused) {
+                    // We create the comparison string outside the panic! call
+                    // because creating the comparison string could panic itself.
+                    let comparison_string = $crate::Comparison::new(
+                        &left_focused,
+                        &right_focused
+                    ).to_string();
+                    ::core::panic!("assertion failed: `(left == right)` at {:?}{}{}\
+                       \n\
+                       \n{}\
+                       \n",
+                       paths,
+                       $maybe_semicolon,
+                       format_args!($($arg)*),
+                       comparison_string,
+                    )
+                }
+            }
+        }
+    });
+}
+
+/// Asserts that the sorted [`Debug`] representations of two values are equal after pairing
+/// map keys that only exist on one side by their similarity, for keys that are volatile but
+/// correspond one to one (eg. generated identifiers).
+///
+/// Keys whose similarity is at least `threshold` (between 0 and 1) are paired, see
+/// [`sorted_pair_fuzzy_keys`]. On failure, the keys that couldn't be paired are listed
+/// above the sorted diff, in which paired keys are shown with their left name.
+///
+/// ```rust
+/// use std::collections::HashMap;
+/// use pretty_assertions_sorted::assert_eq_sorted_fuzzy_keys;
+///
+/// let left = HashMap::from([("job-7f3a9c", "done"), ("job-1b2c3d", "queued")]);
+/// let right = HashMap::from([("job-7f3a9d", "done"), ("job-1b2c3e", "queued")]);
+/// assert_eq_sorted_fuzzy_keys!(left, right, threshold = 0.8);
+/// ```
+#[macro_export]
+macro_rules! assert_eq_sorted_fuzzy_keys {
+    ($left:expr, $right:expr, threshold = $threshold:expr$(,)?) => ({
+        $crate::assert_eq_sorted_fuzzy_keys!(@ $left, $right, $threshold, "", "");
+    });
+    ($left:expr, $right:expr, threshold = $threshold:expr, $($arg:tt)*) => ({
+        $crate::assert_eq_sorted_fuzzy_keys!(@ $left, $right, $threshold, ": ", $($arg)+);
+    });
+    (@ $left:expr, $right:expr, $threshold:expr, $maybe_semicolon:expr, $($arg:tt)*) => ({
+        match (&($left), &($right)) {
+            (left_val, right_val) => {
+                let pairing = $crate::sorted_pair_fuzzy_keys(left_val, right_val, $threshold);
+                if pairing.left != pairing.right {
+                    // We create the comparison string outside the panic! call
+                    // because creating the comparison string could panic itself.
+                    let unpaired_string = pairing
+                        .unpaired
+                        .iter()
+                        .map(|key| ::std::format!("  {}\n", key))
+                        .collect::<::std::string::String>();
+                    let comparison_string = $crate::StrComparison::new(
+                        &pairing.left,
+                        &pairing.right,
+                    ).to_string();
+                    ::core::panic!("assertion failed: `(left == right)` with fuzzy keys, \
+                       {} keys couldn't be paired{}{}\
+                       \n{}\
+                       \n{}\
+                       \n",
+                       pairing.unpaired.len(),
+                       $maybe_semicolon,
+                       format_args!($($arg)*),
+                       unpaired_string,
+                       comparison_string,
+                    )
+                }
+            }
+        }
+    });
+}
+
+/// Asserts that two values agree on the given top-level map keys or struct fields of their
+/// sorted [`Debug`] representations, ignoring all other keys.
+///
+/// This is an alias of [`assert_eq_sorted_only`] for top-level keys: `keys = [..]` is
+/// forwarded as `only = [..]` unchanged, so each key is read as a path, and keys that
+/// contain a `.` or `[` can't be named.
+///
+/// ```rust
+/// use std::collections::HashMap;
+/// use pretty_assertions_sorted::assert_eq_sorted_on_keys;
+///
+/// let left = HashMap::from([("id", "1"), ("name", "a"), ("updated_at", "monday")]);
+/// let right = HashMap::from([("id", "1"), ("name", "a"), ("updated_at", "friday")]);
+/// assert_eq_sorted_on_keys!(left, right, keys = ["id", "name"]);
+/// ```
+#[macro_export]
+macro_rules! assert_eq_sorted_on_keys {
+    ($left:expr, $right:expr, keys = [$($key:expr),+$(,)?]$(,)?) => ({
+        $crate::assert_eq_sorted_only!($left, $right, only = [$($key),+]);
+    });
+    ($left:expr, $right:expr, keys = [$($key:expr),+$(,)?], $($arg:tt)*) => ({
+        $crate::assert_eq_sorted_only!($left, $right, only = [$($key),+], $($arg)+);
+    });
+}
+
+/// Asserts that the sorted [`Debug`] representations of two values are equal, for types
+/// that don't implement [`PartialEq`] (eg. third-party types that forgot to derive it).
+///
+/// The values can be of different types, and the same [`SortOptions`] as for
+/// [`assert_eq_sorted`] can be given as `option = value` pairs. On failure, the usual
+/// sorted diff is shown.
+///
+/// ```rust
+/// use std::collections::HashMap;
+/// use pretty_assertions_sorted::assert_eq_sorted_debug;
+///
+/// #[derive(Debug)]
+/// struct Snapshot {
+///     counts: HashMap<&'static str, u32>,
+/// }
+///
+/// let left = Snapshot { counts: HashMap::from([("a", 1), ("b", 2)]) };
+/// let right = Snapshot { counts: HashMap::from([("b", 2), ("a", 1)]) };
+/// assert_eq_sorted_debug!(left, right);
+/// ```
+#[macro_export]
+macro_rules! assert_eq_sorted_debug {
+    ($left:expr, $right:expr$(,)?) => ({
+        $crate::assert_eq_sorted_debug!(@ $left, $right, $crate::SortOptions::default(), "", "");
+    });
+    ($left:expr, $right:expr, $($key:ident = $value:expr),+$(,)?) => ({
+        $crate::assert_eq_sorted_debug!(@ $left, $right, $crate::SortOptions::default()$(.$key($value))+, "", "");
+    });
+    ($left:expr, $right:expr, $($arg:tt)*) => ({
+        $crate::assert_eq_sorted_debug!(@ $left, $right, $crate::SortOptions::default(), ": ", $($arg)+);
+    });
+    (@ $left:expr, $right:expr, $options:expr, $maybe_semicolon:expr, $($arg:tt)*) => ({
+        match (&($left), &($right)) {
+            (left_val, right_val) => {
+                let options: $crate::SortOptions = $options;
+                let (left_sorted, right_sorted) =
+                    $crate::SortedDebug::pair(left_val, right_val, options.clone());
+                if ::std::format!("{:?}", left_sorted) != ::std::format!("{:?}", right_sorted) {
+                    // We create the comparison string outside the panic! call
+                    // because creating the comparison string could panic itself.
+                    let comparison_string = $crate::LabeledComparison::new(
+                        &left_sorted,
+                        &right_sorted,
+                        "left",
+                        "right",
+                    )
+                    .with_options(&options)
+                    .with_unsorted(left_val, right_val)
+                    .to_string();
+                    ::core::panic!("assertion failed: `(left == right)` by sorted Debug{}{}\
+                       \n\
+                       \n{}\
+                       \n",
+                       $maybe_semicolon,
+                       format_args!($($arg)*),
+                       comparison_string,
+                    )
+                }
+            }
+        }
+    });
+}
+
+/// Options for which parts of the [`Debug`](fmt::Debug) output get sorted by [`SortedDebug`],
+/// and how the sorted output is rendered.
+///
+/// Maps are always sorted by key, and sets are sorted by default. Everything else keeps its
+/// original ordering unless enabled here, since the ordering of lists is often meaningful.
+///
+/// ```rust
+/// use pretty_assertions_sorted::SortOptions;
+///
+/// let options = SortOptions::default().sort_lists(true);
+/// ```
+///
+/// # Equality
+///
+/// [`assert_eq_sorted`] compares the values with [`PartialEq`] first. Options that change
+/// the compared values rather than only their ordering (eg. [`SortOptions::sort_tuples`]),
+/// and term normalizers that change a string of either value (see
+/// [`set_global_term_normalizer`]), also make it consider two values equal when their sorted
+/// representations are equal, even if they aren't equal by [`PartialEq`].
+#[derive(Debug, Clone, Default)]
+pub struct SortOptions {
+    sort_lists: bool,
+    unordered_lists: bool,
+    unsorted_sets: bool,
+    highlight_keys: bool,
+    annotate_unsortable: bool,
+    sort_by_length_then_lexical: bool,
+    case_insensitive_keys: bool,
+    max_diff_bytes: Option<usize>,
+    max_lines: Option<usize>,
+    sort_enum_variant_fields: BTreeSet<String>,
+    normalize_path_keys: bool,
+    hide_empty_containers: bool,
+    sort_tuples: bool,
+    show_unsorted_too: bool,
+    sort_chain: Vec<SortCriterion>,
+    visible_control_chars: bool,
+    fold_markers: bool,
+    term_aliases: Vec<Vec<String>>,
+    min_terminal_width: Option<usize>,
+    terminal_width: Option<usize>,
+    key_sort_render: Option<sort::TextFn>,
+    key_comparator: Option<sort::KeyComparator>,
+    key_normalizer: Option<sort::TextFn>,
+    sort_keys: BTreeMap<String, String>,
+    numeric_key_normalization: bool,
+    mask_addresses: bool,
+    normalize_numbers: bool,
+    unwrap_redundant_options: bool,
+    max_nodes: Option<usize>,
+    case_insensitive_fields: bool,
+    term_normalizer: Option<sort::TextFn>,
+    pretty_print_strings: bool,
+    skip_fields: BTreeSet<String>,
+    preserve_order_fields: BTreeSet<String>,
+    sort_only: BTreeSet<String>,
+    none_equals_absent: bool,
+    order: SortOrder,
+    redact: BTreeSet<String>,
+    leading_color_reset: bool,
+    only_fields: BTreeSet<String>,
+    no_color: bool,
+}
+
+impl SortOptions {
+    /// The default options, same as [`SortOptions::default`]: only maps and sets are sorted.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sort the elements of lists (`Vec`, slices, arrays, etc.).
+    #[must_use]
+    pub fn sort_lists(mut self, sort_lists: bool) -> Self {
+        self.sort_lists = sort_lists;
+        self
+    }
+
+    /// Treat every list as a multiset: lists are sorted (like [`SortOptions::sort_lists`]),
+    /// and [`assert_eq_sorted`] considers two values equal when they only differ in the
+    /// order of the elements of their lists. Useful for functions that return items in an
+    /// arbitrary order.
+    ///
+    /// ```rust
+    /// use pretty_assertions_sorted::assert_eq_sorted;
+    ///
+    /// assert_eq_sorted!(vec![3, 1, 2], vec![1, 2, 3], unordered_lists = true);
+    /// // The option can also be given as a bare flag.
+    /// assert_eq_sorted!(vec![3, 1, 2], vec![1, 2, 3], unordered_lists);
+    /// ```
+    #[must_use]
+    pub fn unordered_lists(mut self, unordered_lists: bool) -> Self {
+        self.unordered_lists = unordered_lists;
+        self
+    }
+
+    /// Sort the elements of sets (`HashSet`, etc.) by their Debug representation, with
+    /// integers sorted by their value. Enabled by default, since the ordering of most sets
+    /// is arbitrary. Disable it to keep the ordering of sets that are ordered on purpose.
+    #[must_use]
+    pub fn sort_sets(mut self, sort_sets: bool) -> Self {
+        self.unsorted_sets = !sort_sets;
+        self
+    }
+
+    /// Sort the elements of tuples, eg. for tuples that represent unordered pairs like the
+    /// edges of an undirected graph. Only anonymous tuples are sorted, tuple structs (eg.
+    /// `Point(2, 1)`) keep their order.
+    ///
+    /// **Warning**: the position of a tuple element is usually meaningful, this clobbers it
+    /// for every tuple in the value. Values whose tuples only differ in the order of their
+    /// elements are equal, see [equality](SortOptions#equality).
+    #[must_use]
+    pub fn sort_tuples(mut self, sort_tuples: bool) -> Self {
+        self.sort_tuples = sort_tuples;
+        self
+    }
+
+    /// Sort string map keys by their length first (shorter keys first), then lexically.
+    /// This groups keys of the same length together in a tiered layout. Other keys are
+    /// sorted as usual.
+    #[must_use]
+    pub fn sort_by_length_then_lexical(mut self, sort_by_length_then_lexical: bool) -> Self {
+        self.sort_by_length_then_lexical = sort_by_length_then_lexical;
+        self
+    }
+
+    /// Sort string map keys ignoring their case, so that eg. `"Backoff"`, `"retries"` and
+    /// `"Timeout"` are ordered alphabetically rather than with all uppercase keys first.
+    /// Keys are rendered with their original casing, and keys that only differ by their
+    /// case are ordered by their original text.
+    #[must_use]
+    pub fn case_insensitive_keys(mut self, case_insensitive_keys: bool) -> Self {
+        self.case_insensitive_keys = case_insensitive_keys;
+        self
+    }
+
+    /// Sort the fields of the given enum struct variants by field name, for variants whose
+    /// Debug output doesn't list the fields in a consistent order (eg. variants generated by
+    /// macros). The fields of other structs keep their declaration order.
+    ///
+    /// Enum variants can't be told apart from structs in the Debug output, so the variants
+    /// are identified by name, eg. `["Created", "Updated"]` for `Event::Created { .. }` and
+    /// `Event::Updated { .. }`.
+    #[must_use]
+    pub fn sort_enum_variant_fields<I, S>(mut self, variants: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.sort_enum_variant_fields = variants.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Normalize the separators of path-like string map keys (`PathBuf`, `Path`, etc.) to
+    /// `/` before sorting them, so Windows-style and Unix-style paths sort and compare the
+    /// same way. A string key is considered path-like if it contains a `\`. Values that only
+    /// differ in the separators of their path keys are equal, see
+    /// [equality](SortOptions#equality).
+    #[must_use]
+    pub fn normalize_path_keys(mut self, normalize_path_keys: bool) -> Self {
+        self.normalize_path_keys = normalize_path_keys;
+        self
+    }
+
+    /// Elide struct fields and map entries whose value is an empty map, set, or list, which
+    /// reduces the noise in large structs with many empty collections. The elision is
+    /// applied to both sides, so an empty collection on one side compares equal to an absent
+    /// entry on the other side (see [equality](SortOptions#equality)).
+    #[must_use]
+    pub fn hide_empty_containers(mut self, hide_empty_containers: bool) -> Self {
+        self.hide_empty_containers = hide_empty_containers;
+        self
+    }
+
+    /// Sort map keys by a chain of criteria: each criterion is tried in order and keys that
+    /// are tied are passed on to the next criterion. Keys that are tied by every criterion
+    /// are sorted as usual. This takes precedence over
+    /// [`SortOptions::sort_by_length_then_lexical`], which is the same as the chain
+    /// `[Length, Lexical]` for string keys.
+    ///
+    /// ```rust
+    /// use pretty_assertions_sorted::{SortCriterion, SortOptions};
+    ///
+    /// let options = SortOptions::default().sort_chain(vec![
+    ///     SortCriterion::Numeric,
+    ///     SortCriterion::Natural,
+    ///     SortCriterion::CaseInsensitive,
+    /// ]);
+    /// ```
+    #[must_use]
+    pub fn sort_chain(mut self, sort_chain: Vec<SortCriterion>) -> Self {
+        self.sort_chain = sort_chain;
+        self
+    }
+
+    /// The direction that map keys are sorted in, [`SortOrder::Ascending`] by default. Keys
+    /// that can't be meaningfully sorted against the other keys stay last either way.
+    ///
+    /// ```rust
+    /// use std::collections::HashMap;
+    /// use pretty_assertions_sorted::{assert_eq_sorted, SortOrder};
+    ///
+    /// let releases = HashMap::from([(1, "alpha"), (2, "beta")]);
+    /// assert_eq_sorted!(releases.clone(), releases, order = SortOrder::Descending);
+    /// ```
+    #[must_use]
+    pub fn order(mut self, order: SortOrder) -> Self {
+        self.order = order;
+        self
+    }
+
+    /// Sort map keys by the text that `render` returns for them, while still displaying the
+    /// original keys. `render` is given the text of a key: the contents of string keys, or
+    /// the single-line Debug output of other keys. The rendered texts are compared with the
+    /// [`SortOptions::sort_chain`] if there is one.
+    ///
+    /// ```rust
+    /// use pretty_assertions_sorted::SortOptions;
+    ///
+    /// // Sort domain names by their top-level domain first.
+    /// let options = SortOptions::default()
+    ///     .key_sort_render(|key| key.rsplit('.').collect::<Vec<_>>().join("."));
+    /// ```
+    #[must_use]
+    pub fn key_sort_render<F>(mut self, render: F) -> Self
+    where
+        F: Fn(&str) -> String + Send + Sync + 'static,
+    {
+        self.key_sort_render = Some(sort::TextFn(std::sync::Arc::new(render)));
+        self
+    }
+
+    /// Sort map keys of type `K` by the field given by its [`SortedDebugKey`] implementation
+    /// (eg. an id), instead of by all of its fields in order. Keys with equal fields are
+    /// ordered by their other fields.
+    ///
+    /// ```rust
+    /// use pretty_assertions_sorted::{SortOptions, SortedDebugKey};
+    ///
+    /// #[derive(Debug)]
+    /// struct Key {
+    ///     name: String,
+    ///     id: u32,
+    /// }
+    ///
+    /// impl SortedDebugKey for Key {
+    ///     const NAME: &'static str = "Key";
+    ///     const SORT_KEY: &'static str = "id";
+    /// }
+    ///
+    /// let options = SortOptions::default().sort_key::<Key>();
+    /// ```
+    #[must_use]
+    pub fn sort_key<K: SortedDebugKey>(mut self) -> Self {
+        self.sort_keys
+            .insert(K::NAME.to_string(), K::SORT_KEY.to_string());
+        self
+    }
+
+    /// Normalize string map keys (eg. by trimming or lowercasing them) before they're sorted
+    /// and matched, so that maps whose keys only differ before normalization (eg. `"Name "`
+    /// and `"name"`) are equal. The keys are displayed as they are, and keys that normalize
+    /// the same are ordered by their original text.
+    ///
+    /// ```rust
+    /// use pretty_assertions_sorted::assert_eq_sorted;
+    /// use std::collections::HashMap;
+    ///
+    /// assert_eq_sorted!(
+    ///     HashMap::from([("Name ", 1)]),
+    ///     HashMap::from([("name", 1)]),
+    ///     key_normalizer = |key: &str| key.trim().to_lowercase()
+    /// );
+    /// ```
+    #[must_use]
+    pub fn key_normalizer<F>(mut self, normalizer: F) -> Self
+    where
+        F: Fn(&str) -> String + Send + Sync + 'static,
+    {
+        self.key_normalizer = Some(sort::TextFn(std::sync::Arc::new(normalizer)));
+        self
+    }
+
+    /// Sort map keys with a custom comparison, for domain-specific orderings that can't be
+    /// expressed by a [`SortOptions::sort_chain`] or a [`SortOptions::key_sort_render`]
+    /// (which this takes precedence over). `comparator` is given the texts of two keys: the
+    /// contents of string keys, or the single-line Debug output of other keys. Keys that
+    /// compare equal are ordered by their Debug output.
+    ///
+    /// ```rust
+    /// use pretty_assertions_sorted::SortOptions;
+    ///
+    /// // Sort keys like `"team/3"` by the number after the slash.
+    /// let options = SortOptions::default().key_comparator(|a, b| {
+    ///     let number = |key: &str| key.rsplit('/').next().and_then(|n| n.parse::<u32>().ok());
+    ///     number(a).cmp(&number(b))
+    /// });
+    /// ```
+    #[must_use]
+    pub fn key_comparator<F>(mut self, comparator: F) -> Self
+    where
+        F: Fn(&str, &str) -> std::cmp::Ordering + Send + Sync + 'static,
+    {
+        self.key_comparator = Some(sort::KeyComparator(std::sync::Arc::new(comparator)));
+        self
+    }
+
+    /// Color map keys and struct field names (cyan) in the rendered output, which makes
+    /// large sorted structures easier to scan. Nothing is colored if the `NO_COLOR`
+    /// environment variable is set.
+    ///
+    /// The coloring of the diff takes precedence over the highlighting: keys are only
+    /// highlighted on the lines that are unchanged between the two sides of a
+    /// [`LabeledComparison`] (which is what the assertion macros use), lines that were
+    /// added or removed are colored green or red as a whole.
+    #[must_use]
+    pub fn highlight_keys(mut self, highlight_keys: bool) -> Self {
+        self.highlight_keys = highlight_keys;
+        self
+    }
+
+    /// After the diff of a [`LabeledComparison`] (which is what [`assert_eq_sorted`] uses),
+    /// also show the original, unsorted Debug output of both sides on a single line each.
+    /// This helps to verify that the sorting didn't misrepresent the data. It can also be
+    /// enabled for every assertion with the `SORTED_SHOW_UNSORTED` environment variable
+    /// (see [`SHOW_UNSORTED_ENV_VAR`]).
+    #[must_use]
+    pub fn show_unsorted_too(mut self, show_unsorted_too: bool) -> Self {
+        self.show_unsorted_too = show_unsorted_too;
+        self
+    }
+
+    /// Render the diff of a [`LabeledComparison`] (which is what [`assert_eq_sorted`] uses)
+    /// with colors, `true` by default. Disable it for CI systems that capture the assertion
+    /// messages into plain-text reports, where the escape sequences would be garbled.
+    #[must_use]
+    pub fn color(mut self, color: bool) -> Self {
+        self.no_color = !color;
+        self
+    }
+
+    /// Start the diff of a [`LabeledComparison`] (which is what [`assert_eq_sorted`] uses)
+    /// with a color reset, in case the output it's embedded in left colors enabled. The
+    /// diff always ends with a color reset when colors are enabled.
+    #[must_use]
+    pub fn leading_color_reset(mut self, leading_color_reset: bool) -> Self {
+        self.leading_color_reset = leading_color_reset;
+        self
+    }
+
+    /// Render control characters in strings (eg. `\0` or `\u{7}`) as visible symbols like
+    /// `␀` and `␇`, which are easier to spot in a diff than their escapes. This only
+    /// changes the rendering, values are still compared by their escaped form.
+    #[must_use]
+    pub fn visible_control_chars(mut self, visible_control_chars: bool) -> Self {
+        self.visible_control_chars = visible_control_chars;
+        self
+    }
+
+    /// Wrap every multi-line map or struct directly inside the top-level value in vim-style
+    /// fold markers (`// {{{` and `// }}}`), so that editors can collapse them when reading
+    /// a large diff.
+    #[must_use]
+    pub fn fold_markers(mut self, fold_markers: bool) -> Self {
+        self.fold_markers = fold_markers;
+        self
+    }
+
+    /// Switch to a more vertical layout when the terminal is narrower than
+    /// `min_terminal_width` columns: values that would make a line wider than the terminal
+    /// are moved onto a line of their own below their field name or map key, instead of
+    /// being wrapped awkwardly by the terminal.
+    ///
+    /// The width of the terminal is read from the `COLUMNS` environment variable, unless
+    /// it's configured with [`SortOptions::terminal_width`].
+    #[must_use]
+    pub fn min_terminal_width(mut self, min_terminal_width: usize) -> Self {
+        self.min_terminal_width = Some(min_terminal_width);
+        self
+    }
+
+    /// The width of the terminal in columns, see [`SortOptions::min_terminal_width`].
+    #[must_use]
+    pub fn terminal_width(mut self, terminal_width: usize) -> Self {
+        self.terminal_width = Some(terminal_width);
+        self
+    }
+
+    /// Treat struct fields and map entries whose value is `None` as equal to a missing field
+    /// or entry on the other side (in either direction), for comparing serialized structs
+    /// whose optional fields may or may not be emitted. Such entries are still displayed as
+    /// they are when other values differ.
+    #[must_use]
+    pub fn none_equals_absent(mut self, none_equals_absent: bool) -> Self {
+        self.none_equals_absent = none_equals_absent;
+        self
+    }
+
+    /// Only keep the fields with the given names of a top-level struct, eg. `["name",
+    /// "config"]`, and ignore all other fields (eg. timestamps or caches). Values are
+    /// compared and displayed with the other fields removed. Nested structs and values that
+    /// aren't structs are kept as they are.
+    #[must_use]
+    pub fn only_fields<I, S>(mut self, fields: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.only_fields = fields.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Replace the values of struct fields with the given names by `<redacted>` at any
+    /// depth, eg. `["token", "id"]` for secrets and per-run identifiers, so that they're
+    /// neither compared nor printed in the diff.
+    #[must_use]
+    pub fn redact<I, S>(mut self, fields: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.redact = fields.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Leave the values of struct fields with the given names as they are, eg. `["events"]`
+    /// for a map that's deliberately kept in insertion order. Nothing inside of these
+    /// fields is sorted or normalized, while the rest of the value is sorted as usual.
+    #[must_use]
+    pub fn skip_fields<I, S>(mut self, fields: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.skip_fields = fields.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Keep the entries of the maps in struct fields with the given names in their original
+    /// order, eg. `["history"]` for a `BTreeMap` or `IndexMap` (or an `Option` of one) whose
+    /// order is meaningful. Unlike [`SortOptions::skip_fields`], the keys and values of the entries are still
+    /// sorted.
+    #[must_use]
+    pub fn preserve_order_fields<I, S>(mut self, fields: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.preserve_order_fields = fields.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Only sort the maps at the given dotted paths of field names and map keys, eg.
+    /// `["config.limits"]`, and leave every other map in its original order to keep the diff
+    /// minimal. Maps inside of the given maps are left in their original order as well,
+    /// unless their paths are given too. All maps are sorted if this is empty (the default).
+    ///
+    /// ```rust
+    /// use pretty_assertions_sorted::assert_eq_sorted;
+    /// use std::collections::HashMap;
+    ///
+    /// #[derive(Debug, PartialEq)]
+    /// struct Config {
+    ///     limits: HashMap<&'static str, u32>,
+    /// }
+    ///
+    /// let config = Config { limits: HashMap::from([("cpu", 2), ("memory", 512)]) };
+    /// assert_eq_sorted!(&config, &config, sort_only = ["limits"]);
+    /// ```
+    #[must_use]
+    pub fn sort_only<I, S>(mut self, paths: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.sort_only = paths.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Render strings that contain newlines as indented `r#"..."#` blocks with one line
+    /// per line of the string, instead of on a single line with escaped newlines. Strings
+    /// that contain a JSON object or array are sorted and reindented as well. This only
+    /// changes the rendering and is a heuristic, so it's off by default.
+    #[must_use]
+    pub fn pretty_print_strings(mut self, pretty_print_strings: bool) -> Self {
+        self.pretty_print_strings = pretty_print_strings;
+        self
+    }
+
+    /// Normalize the contents of every string in the sorted Debug output (eg. by trimming
+    /// them), so values that only differ before normalization are equal. This is applied
+    /// after the global normalizer (see [`set_global_term_normalizer`]), if there is one.
+    #[must_use]
+    pub fn term_normalizer<F>(mut self, normalizer: F) -> Self
+    where
+        F: Fn(&str) -> String + Send + Sync + 'static,
+    {
+        self.term_normalizer = Some(sort::TextFn(std::sync::Arc::new(normalizer)));
+        self
+    }
+
+    /// Match struct fields by their name ignoring case and underscores (eg. `userId`,
+    /// `UserId`, and `user_id` are the same field), for comparing values whose Debug output
+    /// comes from different implementations, eg. a manual `debug_struct` and a derived
+    /// Debug. Values that only differ in the casing of their field names are equal, but
+    /// are still displayed as they are when other values differ.
+    ///
+    /// This is the only normalization of field names, struct names still have to match
+    /// exactly.
+    #[must_use]
+    pub fn case_insensitive_fields(mut self, case_insensitive_fields: bool) -> Self {
+        self.case_insensitive_fields = case_insensitive_fields;
+        self
+    }
+
+    /// Stop sorting after visiting `max_nodes` values (including map keys), leaving the
+    /// remaining subtrees unsorted (but still redacted and normalized). This bounds the cost of sorting huge values, and the
+    /// output is followed by a `… (sorting budget exhausted)` line when the limit is hit.
+    #[must_use]
+    pub fn max_nodes(mut self, max_nodes: usize) -> Self {
+        self.max_nodes = Some(max_nodes);
+        self
+    }
+
+    /// Collapse chains of nested `Some`s or `Ok`s into a single level (eg. `Some(Some(x))`
+    /// becomes `Some(x)`), for values that are wrapped redundantly by generic code. This is
+    /// opt-in since it changes the displayed representation of the values, and wrappers of
+    /// different kinds (eg. `Some(Ok(x))`) are left alone.
+    #[must_use]
+    pub fn unwrap_redundant_options(mut self, unwrap_redundant_options: bool) -> Self {
+        self.unwrap_redundant_options = unwrap_redundant_options;
+        self
+    }
+
+    /// Replace pointer addresses (eg. `0x7ffd5e8c1a2c`, as printed by the Debug output of
+    /// raw pointers) with `<address>`, so that values are compared by their contents instead
+    /// of where they happen to be allocated.
+    ///
+    /// This pairs well with graph structures built with `Rc` and `Weak`: `Rc` prints the
+    /// value it points to and `Weak` prints `(Weak)`, so the Debug output of a cycle is
+    /// finite as long as its back references are `Weak`. A cycle made of `Rc`s only can't
+    /// be printed at all, since its Debug output never ends.
+    #[must_use]
+    pub fn mask_addresses(mut self, mask_addresses: bool) -> Self {
+        self.mask_addresses = mask_addresses;
+        self
+    }
+
+    /// Reformat floating point numbers consistently, so that numbers that are printed
+    /// differently but are equal (eg. `1e3` and `1000.0`, `1.50` and `1.5`, or `-0.0` and
+    /// `0.0`) are displayed the same way and compared as equal (see
+    /// [equality](SortOptions#equality)). Integers are left as they are.
+    #[must_use]
+    pub fn normalize_numbers(mut self, normalize_numbers: bool) -> Self {
+        self.normalize_numbers = normalize_numbers;
+        self
+    }
+
+    /// Match numeric map keys by their value when walking two values in tandem (eg. in
+    /// [`sorted_differences_with_options`]), so that keys that are formatted differently on
+    /// each side (eg. `1` and `1.0`) are paired up instead of being reported as a removed
+    /// and an added entry. This only affects how differences are reported, not whether
+    /// two values are equal.
+    #[must_use]
+    pub fn numeric_key_normalization(mut self, numeric_key_normalization: bool) -> Self {
+        self.numeric_key_normalization = numeric_key_normalization;
+        self
+    }
+
+    /// Groups of string or identifier terms that are considered equal, eg. `[["OK", "Ok",
+    /// "success"]]` for a status that's serialized inconsistently. Aliased terms don't fail
+    /// the assertion, but are still displayed as they are when other values differ.
+    #[must_use]
+    pub fn term_aliases<G, S>(mut self, groups: impl IntoIterator<Item = G>) -> Self
+    where
+        G: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.term_aliases = groups
+            .into_iter()
+            .map(|group| group.into_iter().map(Into::into).collect())
+            .collect();
+        self
+    }
+
+    /// Mark map entries whose keys couldn't be meaningfully sorted with a trailing comment.
+    ///
+    /// Keys of a map are expected to be of the same kind. If they're not (eg. the keys are
+    /// variants of an enum where some variants are unit variants and others are struct
+    /// variants), the keys of the most common kind are sorted as usual and the remaining keys
+    /// are grouped after them, ordered by their rendered Debug output. This option makes that
+    /// fallback explicit in the output.
+    #[must_use]
+    pub fn annotate_unsortable(mut self, annotate_unsortable: bool) -> Self {
+        self.annotate_unsortable = annotate_unsortable;
+        self
+    }
+
+    /// Cap the diff of a [`LabeledComparison`] (which is what the assertion macros use) to
+    /// roughly this many bytes, for CI systems that truncate long logs.
+    ///
+    /// When the diff doesn't fit, the changed lines are kept first, then the unchanged lines
+    /// closest to them. The omitted lines are replaced by `…` and a final `… (diff
+    /// truncated, N bytes omitted)` line is appended, which aren't counted against the cap.
+    #[must_use]
+    pub fn max_diff_bytes(mut self, max_diff_bytes: usize) -> Self {
+        self.max_diff_bytes = Some(max_diff_bytes);
+        self
+    }
+
+    /// Cap the diff of a [`LabeledComparison`] to roughly this many lines, so that the
+    /// differences between large values don't scroll off the terminal.
+    ///
+    /// The changed lines are always kept, along with the unchanged lines closest to them.
+    /// Each run of omitted unchanged lines is replaced by a `... N unchanged lines ...` line.
+    ///
+    /// ```rust
+    /// use pretty_assertions_sorted::assert_eq_sorted;
+    ///
+    /// assert_eq_sorted!(vec![1, 2], vec![1, 2], max_lines = 200);
+    /// ```
+    #[must_use]
+    pub fn max_lines(mut self, max_lines: usize) -> Self {
+        self.max_lines = Some(max_lines);
+        self
+    }
+}
+
+impl SortOptions {
+    /// Returns true if the options normalize the values (eg. [`SortOptions::sort_tuples`] or
+    /// [`SortOptions::hide_empty_containers`]) and the normalized, sorted representations
+    /// of the values are equal, or if the values only differ in aliased terms (see
+    /// [`SortOptions::term_aliases`]), in the casing of field names (see
+    /// [`SortOptions::case_insensitive_fields`]), in `None` entries that are missing on the
+    /// other side (see [`SortOptions::none_equals_absent`]), or in map keys that normalize
+    /// the same (see [`SortOptions::key_normalizer`]).
+    #[doc(hidden)]
+    pub fn normalized_eq<L, R>(&self, left: &L, right: &R) -> bool
+    where
+        L: fmt::Debug + ?Sized,
+        R: fmt::Debug + ?Sized,
+    {
+        if !self.term_aliases.is_empty()
+            || self.case_insensitive_fields
+            || self.none_equals_absent
+            || self.key_normalizer.is_some()
+        {
+            let (left, right) = sorted_values(left, right, self);
+            let options = SortOptions {
+                numeric_key_normalization: false,
+                ..self.clone()
+            };
+            return diff::diff(&left, &right, &options).is_empty();
+        }
+
+        (self.normalize_path_keys
+            || self.hide_empty_containers
+            || self.sort_tuples
+            || self.unordered_lists
+            || self.mask_addresses
+            || self.normalize_numbers
+            || self.unwrap_redundant_options
+            || !self.redact.is_empty()
+            || !self.only_fields.is_empty()
+            || self.changes_terms(left)
+            || self.changes_terms(right))
+            && {
+                let (left, right) = SortedDebug::pair(left, right, self.clone());
+                format!("{:?}", left) == format!("{:?}", right)
+            }
+    }
+
+    /// Returns true if a term normalizer applies and changes any string of the value, see
+    /// [`set_global_term_normalizer`].
+    fn changes_terms<T: fmt::Debug + ?Sized>(&self, value: &T) -> bool {
+        sort::normalizes_terms(self)
+            && parse_unsorted(&format!("{:?}", value))
+                .is_ok_and(|parsed| sort::changes_terms(&parsed, self))
+    }
+}
+
+/// New-type wrapper around an object that sorts the fmt::Debug output when displayed for
+/// deterministic output.
+///
+/// This works through parsing the output and sorting the `debug_map()` type. Other
+/// collections can be sorted as well by constructing the wrapper with [`SortOptions`].
+///
+/// If the inner value's Debug representation can't be sorted (eg. a custom Debug
+/// implementation that doesn't conform to the format that #[derive(Debug)] uses), the
+/// unsorted Debug representation is displayed instead. If it parses, but the parsed value
+/// doesn't reproduce it (so sorting it could change its meaning), it's displayed unsorted
+/// with a `// note: output not sorted (unrecognized Debug format)` line appended. Wrap both
+/// sides of a comparison with [`SortedDebug::pair`] to display them both unsorted when
+/// either of them can't be sorted.
+///
+/// The inner value is formatted, parsed and sorted once, when the wrapper is first
+/// displayed, and the sorted result is reused after that (eg. when `pretty_assertions`
+/// requests both the `{:?}` and `{:#?}` output).
+///
+/// Potential use-cases that aren't implemented yet:
+/// * Sorting struct fields
+pub struct SortedDebug<T> {
+    value: T,
+    options: SortOptions,
+    sorted: OnceLock<Result<Sorted, Unsortable>>,
+}
+
+impl<T> SortedDebug<T> {
+    pub fn new(v: T) -> Self {
+        Self::with_options(v, SortOptions::default())
+    }
+
+    pub fn with_options(v: T, options: SortOptions) -> Self {
+        Self {
+            value: v,
+            options,
+            sorted: OnceLock::new(),
+        }
+    }
+}
+
+impl<T: fmt::Debug> SortedDebug<T> {
+    /// Wraps both sides of a comparison, deciding together whether they're sorted: if
+    /// either side's Debug representation can't be sorted, both sides are displayed
+    /// unsorted, so that they stay comparable.
+    ///
+    /// Unlike [`SortedDebug::with_options`], both values are formatted, parsed and sorted
+    /// right away.
+    pub fn pair<U: fmt::Debug>(left: T, right: U, options: SortOptions) -> (Self, SortedDebug<U>) {
+        let (left_sorted, right_sorted) = try_sorted_values(&left, &right, &options);
+        (
+            Self {
+                value: left,
+                options: options.clone(),
+                sorted: OnceLock::from(left_sorted),
+            },
+            SortedDebug {
+                value: right,
+                options,
+                sorted: OnceLock::from(right_sorted),
+            },
+        )
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for SortedDebug<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let sorted = match self
+            .sorted
+            .get_or_init(|| try_sorted_value(&self.value, &self.options))
+        {
+            Ok(sorted) => sorted,
+            Err(reason) => {
+                fmt::Debug::fmt(&self.value, f)?;
+                return match reason {
+                    Unsortable::Unrecognized => write!(f, "\n{}", UNRECOGNIZED_NOTE),
+                    Unsortable::Unparseable | Unsortable::Counterpart => Ok(()),
+                };
+            }
+        };
+        let renderer = render::Renderer::new(&self.options);
+        fmt::Display::fmt(&renderer.render(&sorted.value), f)?;
+        if sorted.budget_exhausted {
+            f.write_str("\n… (sorting budget exhausted)")?;
+        }
+        Ok(())
+    }
+}
+
+/// The note appended to Debug output that was parsed, but that the parsed value doesn't
+/// reproduce, see [`sorted_value`].
+const UNRECOGNIZED_NOTE: &str = "// note: output not sorted (unrecognized Debug format)";
+
+/// Why the Debug output of a value isn't sorted, see [`sorted_value`].
+#[derive(Clone, Copy)]
+enum Unsortable {
+    /// The Debug output failed to parse.
+    Unparseable,
+    /// The Debug output parsed, but the parsed value renders differently from it (eg. a
+    /// custom Debug implementation that only looks like the derived format).
+    Unrecognized,
+    /// The Debug output could be sorted, but the value it's compared with can't be, see
+    /// [`sorted_values`].
+    Counterpart,
+}
+
+/// Parses the Debug output of the value and sorts it according to the options.
+///
+/// If the Debug output can't be parsed, the unsorted (pretty) Debug output is returned as a
+/// single opaque term instead. The same happens if the parsed value doesn't render the
+/// same as the Debug output (so that sorting it could change its meaning), with a note
+/// that the output isn't sorted appended. Use [`sorted_values`] for both sides of a
+/// comparison.
+fn sorted_value<T: fmt::Debug + ?Sized>(value: &T, options: &SortOptions) -> Value {
+    try_sorted_value(value, options).map_or_else(
+        |reason| unsorted_value(value, reason),
+        |sorted| sorted.value,
+    )
+}
+
+/// Like [`sorted_value`] for both sides of a comparison, but if either side can't be
+/// sorted, both sides are returned unsorted. This keeps the two sides consistent when only
+/// one of them happens to be sortable (eg. values of a type whose Debug output only
+/// sometimes parses).
+fn sorted_values<L: fmt::Debug + ?Sized, R: fmt::Debug + ?Sized>(
+    left: &L,
+    right: &R,
+    options: &SortOptions,
+) -> (Value, Value) {
+    let (left_sorted, right_sorted) = try_sorted_values(left, right, options);
+    (
+        left_sorted.map_or_else(|reason| unsorted_value(left, reason), |sorted| sorted.value),
+        right_sorted.map_or_else(
+            |reason| unsorted_value(right, reason),
+            |sorted| sorted.value,
+        ),
+    )
+}
+
+/// Parses and sorts the Debug output of both sides of a comparison, or why they can't be
+/// sorted, see [`sorted_values`].
+fn try_sorted_values<L: fmt::Debug + ?Sized, R: fmt::Debug + ?Sized>(
+    left: &L,
+    right: &R,
+    options: &SortOptions,
+) -> (Result<Sorted, Unsortable>, Result<Sorted, Unsortable>) {
+    match (
+        try_sorted_value(left, options),
+        try_sorted_value(right, options),
+    ) {
+        (Ok(left), Ok(right)) => (Ok(left), Ok(right)),
+        (left, right) => (
+            Err(left.err().unwrap_or(Unsortable::Counterpart)),
+            Err(right.err().unwrap_or(Unsortable::Counterpart)),
+        ),
+    }
+}
+
+/// A value whose Debug output was parsed and sorted.
+struct Sorted {
+    value: Value,
+    /// Whether the [`SortOptions::max_nodes`] budget ran out before the whole value was
+    /// sorted.
+    budget_exhausted: bool,
+}
+
+/// Parses and sorts the Debug output of the value, or why it can't be sorted.
+fn try_sorted_value<T: fmt::Debug + ?Sized>(
+    value: &T,
+    options: &SortOptions,
+) -> Result<Sorted, Unsortable> {
+    let debug = format!("{:?}", value);
+    let normalized = preprocess::normalize(&debug);
+    let mut parsed = parse(&normalized).map_err(|_| Unsortable::Unparseable)?;
+    // What the preprocessing rewrote (eg. comments) only matches before restoring the
+    // parsed value, what it marked (eg. escapes) only matches after restoring.
+    let recognized = matches!(normalized, Cow::Owned(_)) && is_recognized(&normalized, &parsed);
+    preprocess::restore(&mut parsed);
+    if !(recognized || is_recognized(&debug, &parsed)) {
+        return Err(Unsortable::Unrecognized);
+    }
+    let budget_exhausted = sort::sort_maps(&mut parsed, options);
+    Ok(Sorted {
+        value: parsed,
+        budget_exhausted,
+    })
+}
+
+/// The unsorted (pretty) Debug output of the value as a single opaque term.
+fn unsorted_value<T: fmt::Debug + ?Sized>(value: &T, reason: Unsortable) -> Value {
+    Value::Term(Term::UnquotedRawString(match reason {
+        Unsortable::Unparseable | Unsortable::Counterpart => format!("{:#?}", value),
+        Unsortable::Unrecognized => format!("{:#?}\n{}", value, UNRECOGNIZED_NOTE),
+    }))
+}
+
+/// Returns true if the parsed value renders the same as the (normalized) Debug output it
+/// was parsed from, ie. the parser understood the output and sorting it keeps its meaning.
+/// Whitespace and commas are ignored, since they're formatting rather than content (eg.
+/// the trailing comma of a tuple with a single element).
+fn is_recognized(debug: &str, parsed: &Value) -> bool {
+    let significant = |text: &str| {
+        text.chars()
+            .filter(|c| !c.is_whitespace() && *c != ',')
+            .collect::<String>()
+    };
+    significant(&render::render_inline(parsed)) == significant(debug)
+}
+
+/// Parses and sorts a [`Debug`](fmt::Debug) representation, `None` if it can't be parsed.
+fn parse_sorted_str(debug: &str, options: &SortOptions) -> Option<Value> {
+    try_parse_sorted(debug, options).ok()
+}
+
+/// Parses and sorts a [`Debug`](fmt::Debug) representation, with the parser's error if it
+/// can't be parsed.
+fn try_parse_sorted(debug: &str, options: &SortOptions) -> Result<Value, ParseError> {
+    let mut parsed = parse_unsorted(debug)?;
+    sort::sort_maps(&mut parsed, options);
+    Ok(parsed)
+}
+
+/// Parses a [`Debug`](fmt::Debug) representation without sorting it.
+fn parse_unsorted(debug: &str) -> Result<Value, ParseError> {
+    let mut parsed = parse(&preprocess::normalize(debug)).map_err(|error| ParseError {
+        message: format!("{:#}", error),
+    })?;
+    preprocess::restore(&mut parsed);
+    Ok(parsed)
+}
+
+/// Sorts a [`Debug`](fmt::Debug) representation that was captured as a string (eg.
+/// archived in a fixture), returning it in the pretty (`{:#?}`) format. Returns `None` if
+/// the string can't be parsed as Debug output.
+///
+/// Both the compact (`{:?}`) and the pretty (`{:#?}`) format are accepted, so strings
+/// captured in either format sort to the same canonical form.
+///
+/// ```rust
+/// use pretty_assertions_sorted::sort_debug_str;
+///
+/// assert_eq!(
+///     sort_debug_str(r#"{"b": 2, "a": 1}"#).unwrap(),
+///     "{\n    \"a\": 1,\n    \"b\": 2,\n}"
+/// );
+/// assert_eq!(sort_debug_str("not { debug"), None);
+/// ```
+pub fn sort_debug_str(debug: &str) -> Option<String> {
+    let options = SortOptions::default();
+    let value = parse_sorted_str(debug, &options)?;
+    Some(render::Renderer::new(&options).render(&value))
+}
+
+/// Environment variable that enables [`SortOptions::show_unsorted_too`] for every
+/// assertion, when it's set to anything other than an empty string.
+pub const SHOW_UNSORTED_ENV_VAR: &str = "SORTED_SHOW_UNSORTED";
+
+/// The ANSI escape sequence that resets all colors and styles.
+const COLOR_RESET: &str = "\u{1b}[0m";
+
+/// A [`Comparison`] where the two sides of the diff are given custom labels, eg. "actual"
+/// and "expected", instead of "left" and "right".
+///
+/// ```rust
+/// use pretty_assertions_sorted::LabeledComparison;
+///
+/// print!("{}", LabeledComparison::new(&123, &134, "actual", "expected"));
+/// ```
+pub struct LabeledComparison<'a, TLeft: ?Sized, TRight: ?Sized> {
+    left: &'a TLeft,
+    right: &'a TRight,
+    left_label: &'a str,
+    right_label: &'a str,
+    max_bytes: Option<usize>,
+    max_lines: Option<usize>,
+    show_unsorted: bool,
+    leading_color_reset: bool,
+    color: bool,
+    unsorted: Option<(&'a dyn fmt::Debug, &'a dyn fmt::Debug)>,
+}
+
+impl<'a, TLeft: ?Sized, TRight: ?Sized> LabeledComparison<'a, TLeft, TRight> {
+    pub fn new(
+        left: &'a TLeft,
+        right: &'a TRight,
+        left_label: &'a str,
+        right_label: &'a str,
+    ) -> Self {
+        Self {
+            left,
+            right,
+            left_label,
+            right_label,
+            max_bytes: None,
+            max_lines: None,
+            show_unsorted: false,
+            leading_color_reset: false,
+            color: true,
+            unsorted: None,
+        }
+    }
+
+    /// Applies the options that affect the comparison rather than the compared values, eg.
+    /// [`SortOptions::max_diff_bytes`].
+    #[must_use]
+    pub fn with_options(mut self, options: &SortOptions) -> Self {
+        self.max_bytes = options.max_diff_bytes;
+        self.max_lines = options.max_lines;
+        self.show_unsorted = options.show_unsorted_too;
+        self.leading_color_reset = options.leading_color_reset;
+        self.color = !options.no_color;
+        self
+    }
+
+    /// The original values, whose unsorted Debug output is shown after the diff if enabled
+    /// with [`SortOptions::show_unsorted_too`].
+    #[must_use]
+    pub fn with_unsorted(mut self, left: &'a dyn fmt::Debug, right: &'a dyn fmt::Debug) -> Self {
+        self.unsorted = Some((left, right));
+        self
+    }
+}
+
+impl<'a, TLeft, TRight> fmt::Display for LabeledComparison<'a, TLeft, TRight>
+where
+    TLeft: fmt::Debug + ?Sized,
+    TRight: fmt::Debug + ?Sized,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.color {
+            return self.write_colored(f);
+        }
+        let mut out = String::new();
+        self.write_colored(&mut out)?;
+        f.write_str(&render::strip_ansi(&out))
+    }
+}
+
+impl<'a, TLeft, TRight> LabeledComparison<'a, TLeft, TRight>
+where
+    TLeft: fmt::Debug + ?Sized,
+    TRight: fmt::Debug + ?Sized,
+{
+    fn write_colored(&self, f: &mut impl fmt::Write) -> fmt::Result {
+        // The header is the first line of the comparison, replace it with one using our
+        // labels (styled the same way pretty_assertions styles it).
+        let comparison = Comparison::new(self.left, self.right).to_string();
+        let lines = comparison
+            .split_once('\n')
+            .map_or("", |(_header, lines)| lines);
+        if self.leading_color_reset && render::colors_enabled() {
+            f.write_str(COLOR_RESET)?;
+        }
+        writeln!(
+            f,
+            "{} {} {} / {} {} :",
+            "Diff".bold(),
+            '<'.red().linger(),
+            self.left_label.resetting(),
+            self.right_label.green().linger(),
+            '>'.resetting(),
+        )?;
+
+        // Unchanged lines are prefixed by a space, added and removed lines are colored by
+        // the diff, which takes precedence over any key highlighting.
+        let lines = lines
+            .split_inclusive('\n')
+            .map(|line| {
+                if line.starts_with(' ') {
+                    line.to_string()
+                } else {
+                    render::strip_key_highlights(line)
+                }
+            })
+            .collect::<String>();
+        let lines = match self.max_lines {
+            Some(max_lines) => render::elide_unchanged_lines(&lines, max_lines),
+            None => Cow::Borrowed(lines.as_str()),
+        };
+        match self.max_bytes {
+            Some(max_bytes) => f.write_str(&render::truncate_diff(&lines, max_bytes))?,
+            None => f.write_str(&lines)?,
+        }
+
+        let show_unsorted = self.show_unsorted
+            || std::env::var_os(SHOW_UNSORTED_ENV_VAR).is_some_and(|v| !v.is_empty());
+        if let (true, Some((left, right))) = (show_unsorted, self.unsorted) {
+            writeln!(f)?;
+            writeln!(f, "Unsorted {}: {:?}", self.left_label, left)?;
+            writeln!(f, "Unsorted {}: {:?}", self.right_label, right)?;
+        }
+
+        // Leave the terminal in a known state, so that a diff embedded in other colored
+        // output (eg. by a custom test reporter) doesn't bleed its colors into it.
+        if render::colors_enabled() {
+            f.write_str(COLOR_RESET)?;
+        }
+        Ok(())
+    }
+}
+
+/// The error of [`sorted_eq`]: the values weren't equal.
+///
+/// Both its [`Display`](fmt::Display) and [`Debug`](fmt::Debug) output are the assertion
+/// message with the (colored) sorted diff, since tests that return a `Result` report
+/// errors with their Debug output.
+#[derive(Clone, PartialEq, Eq)]
+pub struct SortedDiffError {
+    message: String,
+}
+
+impl fmt::Display for SortedDiffError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl fmt::Debug for SortedDiffError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for SortedDiffError {}
+
+impl From<SortedDiffError> for String {
+    fn from(error: SortedDiffError) -> String {
+        error.message
+    }
+}
+
+/// Compares two values like [`assert_eq_sorted`], but returns the sorted diff as an error
+/// instead of panicking.
+///
+/// This is meant for tests that return a `Result`, eg. parametrized tests generated with
+/// the [`test-case`](https://docs.rs/test-case) crate:
+///
+/// ```rust
+/// use std::collections::HashMap;
+/// use pretty_assertions_sorted::{sorted_eq, SortedDiffError};
+///
+/// fn parse_counts(input: &str) -> HashMap<&str, usize> {
+///     let mut counts = HashMap::new();
+///     for word in input.split_whitespace() {
+///         *counts.entry(word).or_insert(0) += 1;
+///     }
+///     counts
+/// }
+///
+/// # fn main() -> Result<(), SortedDiffError> {
+/// sorted_eq(&parse_counts("a b a"), &HashMap::from([("a", 2), ("b", 1)]))?;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// With [`rstest`](https://docs.rs/rstest), the expected value can be provided by a
+/// fixture:
+///
+/// ```rust,ignore
+/// use rstest::{fixture, rstest};
+///
+/// #[fixture]
+/// fn expected_counts() -> HashMap<&'static str, usize> {
+///     HashMap::from([("a", 2), ("b", 1)])
+/// }
+///
+/// #[rstest]
+/// #[case("a b a")]
+/// #[case("b a a")]
+/// fn counts_words(
+///     #[case] input: &str,
+///     expected_counts: HashMap<&'static str, usize>,
+/// ) -> Result<(), SortedDiffError> {
+///     sorted_eq(&parse_counts(input), &expected_counts)
+/// }
+/// ```
+pub fn sorted_eq<T>(actual: &T, expected: &T) -> Result<(), SortedDiffError>
+where
+    T: fmt::Debug + PartialEq + ?Sized,
+{
+    if actual == expected {
+        return Ok(());
+    }
+
+    let (actual, expected) = SortedDebug::pair(actual, expected, SortOptions::default());
+    Err(SortedDiffError {
+        message: format!(
+            "assertion failed: `(actual == expected)`\n\n{}",
+            LabeledComparison::new(&actual, &expected, "actual", "expected",)
+        ),
+    })
+}
+
+/// Returns the sorted diff of two values as a plain (colored) [`Comparison`], or `None` if
+/// their sorted [`Debug`](fmt::Debug) representations are equal. Unlike the diff that
+/// [`assert_eq_sorted`] panics with (a [`LabeledComparison`]), it has no labels.
+///
+/// This is meant for building custom assertion helpers or error types that embed the diff.
+///
+/// ```rust
+/// use std::collections::HashMap;
+/// use pretty_assertions_sorted::diff_sorted;
+///
+/// let left: HashMap<_, _> = vec![("a", 1), ("b", 2)].into_iter().collect();
+/// let right: HashMap<_, _> = vec![("b", 2), ("a", 1)].into_iter().collect();
+/// assert_eq!(diff_sorted(&left, &right), None);
+/// assert!(diff_sorted(&left, &HashMap::from([("a", 2)])).is_some());
+/// ```
+pub fn diff_sorted<L, R>(left: &L, right: &R) -> Option<String>
+where
+    L: fmt::Debug + ?Sized,
+    R: fmt::Debug + ?Sized,
+{
+    let (left, right) = SortedDebug::pair(left, right, SortOptions::default());
+    if format!("{:?}", left) == format!("{:?}", right) {
+        return None;
+    }
+    Some(Comparison::new(&left, &right).to_string())
+}
+
+/// Returns the sorted, pretty [`Debug`](fmt::Debug) representation of `value`, the same
+/// output as formatting a [`SortedDebug`] of it.
+///
+/// This is useful for feeding a stable Debug string into other tools, eg. snapshot tests:
+///
+/// ```rust
+/// use std::collections::HashMap;
+/// use pretty_assertions_sorted::sorted_debug_string;
+///
+/// let map: HashMap<_, _> = vec![("b", 2), ("a", 1)].into_iter().collect();
+/// assert_eq!(sorted_debug_string(&map), "{\n    \"a\": 1,\n    \"b\": 2,\n}");
+/// ```
+pub fn sorted_debug_string<T: fmt::Debug + ?Sized>(value: &T) -> String {
+    format!("{:#?}", SortedDebug::new(value))
+}
+
+/// Same as [`sorted_debug_string`], but sorted and rendered with the given [`SortOptions`],
+/// which can be configured once and reused for many values.
+///
+/// ```rust
+/// use pretty_assertions_sorted::{sorted_with, SortOptions};
+///
+/// let options = SortOptions::new().sort_lists(true);
+/// assert_eq!(sorted_with(&vec![2, 1], &options), "[\n    1,\n    2,\n]");
+/// ```
+pub fn sorted_with<T: fmt::Debug + ?Sized>(value: &T, options: &SortOptions) -> String {
+    format!("{:#?}", SortedDebug::with_options(value, options.clone()))
+}
+
+/// The error of [`try_sorted_debug`]: the [`Debug`](fmt::Debug) output couldn't be parsed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    message: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "failed to parse Debug output: {}", self.message)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// Returns the sorted [`Debug`](fmt::Debug) representation of `value`, in the pretty
+/// (`{:#?}`) format if `alternate` is set and on a single line otherwise, or the parser's
+/// error if the Debug output can't be parsed.
+///
+/// Unlike [`SortedDebug`], which falls back to the unsorted output, this never hides a
+/// parse failure, for tools that normalize Debug dumps outside of tests.
+///
+/// ```rust
+/// use std::collections::HashMap;
+/// use pretty_assertions_sorted::try_sorted_debug;
+///
+/// let map: HashMap<_, _> = vec![("b", 2), ("a", 1)].into_iter().collect();
+/// assert_eq!(try_sorted_debug(&map, false).unwrap(), r#"{"a": 1, "b": 2}"#);
+/// assert_eq!(
+///     try_sorted_debug(&map, true).unwrap(),
+///     "{\n    \"a\": 1,\n    \"b\": 2,\n}"
+/// );
+/// ```
+pub fn try_sorted_debug<T: fmt::Debug + ?Sized>(
+    value: &T,
+    alternate: bool,
+) -> Result<String, ParseError> {
+    let options = SortOptions::default();
+    let value = try_parse_sorted(&format!("{:?}", value), &options)?;
+    Ok(if alternate {
+        render::Renderer::new(&options).render(&value)
+    } else {
+        render::render_inline(&value)
+    })
+}
+
+/// Parses the [`Debug`](fmt::Debug) representation of `value` and returns the sorted
+/// [`Value`] tree, or the parser's error if it can't be parsed. This is for tools that walk
+/// the sorted structure (eg. to extract fields or serialize it) instead of printing it.
+///
+/// ```rust
+/// use std::collections::HashMap;
+/// use pretty_assertions_sorted::{parse_sorted, Term, Value};
+///
+/// let map = HashMap::from([("b", 2), ("a", 1)]);
+/// let Value::Map(map) = parse_sorted(&map).unwrap() else {
+///     panic!("expected a map");
+/// };
+/// let keys = map
+///     .values
+///     .iter()
+///     .map(|entry| match &entry.key {
+///         Value::Term(Term::String(key)) => key.as_str(),
+///         _ => panic!("expected a string key"),
+///     })
+///     .collect::<Vec<_>>();
+/// assert_eq!(keys, vec!["a", "b"]);
+/// ```
+pub fn parse_sorted<T: fmt::Debug + ?Sized>(value: &T) -> Result<Value, ParseError> {
+    try_parse_sorted(&format!("{:?}", value), &SortOptions::default())
+}
+
+/// Renders the sorted, pretty [`Debug`](fmt::Debug) representation of `value`, but only
+/// shows the first `max_keys` entries of every map (after sorting), followed by a
+/// `… (+N more)` line. Nested maps are capped at the same limit.
+///
+/// This is meant for quickly inspecting huge structures without dumping everything.
+///
+/// ```rust
+/// use std::collections::HashMap;
+/// use pretty_assertions_sorted::preview_sorted;
+///
+/// let map: HashMap<_, _> = (0..100).map(|i| (i, i * 2)).collect();
+/// assert_eq!(preview_sorted(&map, 2), "{\n    0: 0,\n    1: 2,\n    … (+98 more)\n}");
+/// ```
+pub fn preview_sorted<T: fmt::Debug>(value: &T, max_keys: usize) -> String {
+    let options = SortOptions::default();
+    let value = sorted_value(value, &options);
+    render::Renderer::new(&options)
+        .max_map_entries(max_keys)
+        .render(&value)
+}
+
+/// Returns the name of the enum variant (or struct) in the [`Debug`](fmt::Debug)
+/// representation of `value`, eg. `Some` for `Some(1)` or `Connected` for
+/// `State::Connected { since: 1 }`.
+///
+/// Values that aren't enum variants or structs (eg. numbers, tuples, or collections)
+/// don't have a variant name.
+///
+/// ```rust
+/// use pretty_assertions_sorted::debug_variant_name;
+///
+/// assert_eq!(debug_variant_name(&Some(1)).as_deref(), Some("Some"));
+/// assert_eq!(debug_variant_name(&None::<i32>).as_deref(), Some("None"));
+/// assert_eq!(debug_variant_name(&vec![1]), None);
+/// ```
+pub fn debug_variant_name<T: fmt::Debug + ?Sized>(value: &T) -> Option<String> {
+    match sorted_value(value, &SortOptions::default()) {
+        Value::Struct(s) => Some(s.name),
+        Value::Tuple(tuple) => tuple.name,
+        Value::Term(Term::Ident(ident)) => Some(ident),
+        _ => None,
+    }
+}
+
+/// Returns a stable hash of the sorted [`Debug`](fmt::Debug) representation of `value`.
+///
+/// Two values with equal sorted representations produce the same hash, regardless of the
+/// iteration order of the maps inside of them. A fixed hash function (64-bit FNV-1a) is used
+/// instead of the randomly seeded [`std::collections::hash_map::DefaultHasher`], so the hash
+/// is also stable across runs and can be used to fingerprint test expectations.
+///
+/// ```rust
+/// use std::collections::HashMap;
+/// use pretty_assertions_sorted::sorted_debug_hash;
+///
+/// let a: HashMap<_, _> = vec![(1, "a"), (2, "b")].into_iter().collect();
+/// let b: HashMap<_, _> = vec![(2, "b"), (1, "a")].into_iter().collect();
+/// assert_eq!(sorted_debug_hash(&a), sorted_debug_hash(&b));
+/// ```
+pub fn sorted_debug_hash<T: fmt::Debug>(value: &T) -> u64 {
+    const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+    format!("{:?}", SortedDebug::new(value))
+        .bytes()
+        .fold(FNV_OFFSET_BASIS, |hash, byte| {
+            (hash ^ u64::from(byte)).wrapping_mul(FNV_PRIME)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use indoc::indoc;
+    use std::collections::{HashMap, HashSet};
+    use std::path::PathBuf;
+    use std::sync::Mutex;
+    use std::{assert_eq, assert_ne};
+
+    const TEST_RERUNS_FOR_DETERMINISM: u32 = 100;
+
+    fn sorted_debug<T: fmt::Debug>(v: T) -> String {
+        format!("{:#?}", SortedDebug::new(v))
+    }
+
+    #[test]
+    fn noop_sorts() {
+        for _ in 0..TEST_RERUNS_FOR_DETERMINISM {
+            assert_eq!(sorted_debug(2), "2");
+        }
+    }
+
+    #[test]
+    fn sorts_hashmap() {
+        for _ in 0..TEST_RERUNS_FOR_DETERMINISM {
+            // Note that we have to create the HashMaps each try
+            // in order to induce non-determinism in the debug output.
+            let item = {
+                let mut map = HashMap::new();
+                map.insert(1, true);
+                map.insert(2, true);
+                map.insert(3, true);
+                map.insert(10, true);
+                map.insert(20, true);
+                map
+            };
+
+            let expected = indoc!(
+                "{
+                    1: true,
+                    2: true,
+                    3: true,
+                    10: true,
+                    20: true,
+                }"
+            );
+            assert_eq!(sorted_debug(item), expected);
+        }
+    }
+
+    #[test]
+    fn sorts_hashmaps_with_non_exhaustive_object_values() {
+        #[allow(unused)]
+        struct Foo {
+            value: f32,
+        }
+
+        impl fmt::Debug for Foo {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.debug_struct("Foo")
+                    .field("value", &self.value)
+                    .finish_non_exhaustive()
+            }
+        }
+
+        for _ in 0..TEST_RERUNS_FOR_DETERMINISM {
+            let item = {
+                let mut map = HashMap::new();
+                map.insert(1, Foo { value: 10.1 });
+                map.insert(32, Foo { value: 2.0 });
+                map.insert(2, Foo { value: -1.5 });
+                map
+            };
+
+            let expected = indoc!(
+                "{
+                    1: Foo {
+                        value: 10.1,
+                        ..
+                    },
+                    2: Foo {
+                        value: -1.5,
+                        ..
+                    },
+                    32: Foo {
+                        value: 2.0,
+                        ..
+                    },
+                }"
+            );
+            assert_eq!(sorted_debug(item), expected);
+        }
+    }
+
+    #[test]
+    fn sorts_object_with_hashmap() {
+        #[derive(Debug)]
+        #[allow(unused)]
+        struct Foo {
+            bar: Bar,
+        }
+
+        #[derive(Debug)]
+        #[allow(unused)]
+        struct Bar {
+            count: HashMap<&'static str, Zed>,
+            value: usize,
+        }
+
+        #[derive(Debug)]
+        struct Zed;
+
+        for _ in 0..TEST_RERUNS_FOR_DETERMINISM {
+            let item = Foo {
+                bar: Bar {
+                    count: {
+                        let mut map = HashMap::new();
+                        map.insert("hello world", Zed);
+                        map.insert("lorem ipsum", Zed);
+                        map
+                    },
+                    value: 200,
+                },
+            };
+
+            let expected = indoc!(
+                "Foo {
+                    bar: Bar {
+                        count: {
+                            \"hello world\": Zed,
+                            \"lorem ipsum\": Zed,
+                        },
+                        value: 200,
+                    },
+                }"
+            );
+            assert_eq!(sorted_debug(item), expected);
+        }
+    }
+
+    #[test]
+    fn hashmap_with_object_values() {
+        #[derive(Debug)]
+        #[allow(unused)]
+        struct Foo {
+            value: f32,
+            bar: Vec<Bar>,
+        }
+
+        #[derive(Debug)]
+        #[allow(unused)]
+        struct Bar {
+            elo: i32,
+        }
+
+        for _ in 0..TEST_RERUNS_FOR_DETERMINISM {
+            let item = {
+                let mut map = HashMap::new();
+                map.insert(
+                    "foo",
+                    Foo {
+                        value: 12.2,
+                        bar: vec![Bar { elo: 200 }, Bar { elo: -12 }],
+                    },
+                );
+                map.insert(
+                    "foo2",
+                    Foo {
+                        value: -0.2,
+                        bar: vec![],
+                    },
+                );
+                map
+            };
+
+            let expected = indoc!(
+                "{
+                    \"foo\": Foo {
+                        value: 12.2,
+                        bar: [
+                            Bar {
+                                elo: 200,
+                            },
+                            Bar {
+                                elo: -12,
+                            },
+                        ],
+                    },
+                    \"foo2\": Foo {
+                        value: -0.2,
+                        bar: [],
+                    },
+                }"
+            );
+            assert_eq!(sorted_debug(item), expected);
+        }
+    }
+
+    #[test]
+    fn hashmap_with_object_keys() {
+        #[derive(Debug, PartialEq, Eq, Hash)]
+        struct Foo {
+            value: i32,
+            bar: Vec<Bar>,
+        }
+
+        #[derive(Debug, PartialEq, Eq, Hash)]
+        struct Bar {
+            elo: i32,
+        }
+
+        for _ in 0..TEST_RERUNS_FOR_DETERMINISM {
+            let item = {
+                let mut map = HashMap::new();
+                map.insert(
+                    Foo {
+                        value: 12,
+                        bar: vec![Bar { elo: 200 }, Bar { elo: -12 }],
+                    },
+                    "foo",
+                );
+                map.insert(
+                    Foo {
+                        value: -2,
+                        bar: vec![],
+                    },
+                    "foo2",
+                );
+                map
+            };
+
+            let expected = indoc!(
+                "{
+                    Foo {
+                        value: -2,
+                        bar: [],
+                    }: \"foo2\",
+                    Foo {
+                        value: 12,
+                        bar: [
+                            Bar {
+                                elo: 200,
+                            },
+                            Bar {
+                                elo: -12,
+                            },
+                        ],
+                    }: \"foo\",
+                }"
+            );
+            assert_eq!(sorted_debug(item), expected);
+        }
+    }
+
+    #[test]
+    fn hashmap_with_chrono_naivedate() {
+        for _ in 0..TEST_RERUNS_FOR_DETERMINISM {
+            let item = {
+                let mut map = HashMap::new();
+                map.insert(chrono::NaiveDate::from_ymd_opt(2000, 2, 14).unwrap(), "foo");
+                map.insert(chrono::NaiveDate::from_ymd_opt(2001, 4, 2).unwrap(), "foo");
+                map
+            };
+
+            dbg!(&item);
+
+            let expected = indoc!(
+                "{
+                    2000-02-14: \"foo\",
+                    2001-04-02: \"foo\",
+                }"
+            );
+            assert_eq!(sorted_debug(item), expected);
+        }
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "assertion failed: `(left == right)`\n\n\u{1b}[1mDiff\u{1b}[0m \
+                               \u{1b}[31m< left\u{1b}[0m / \u{1b}[32mright >\u{1b}[0m :\n \
+                               Object {\n"
+    )]
+    fn falls_back_to_unsorted_output_when_expression_cant_be_sorted() {
+        assert_eq_sorted!(serde_json::json!({"a":0}), serde_json::json!({"a":1}));
+    }
+
+    #[test]
+    fn passes_when_expression_cant_be_sorted_but_is_equal() {
+        assert_eq_sorted!(serde_json::json!({"a":0}), serde_json::json!({"a":0}));
+    }
+
+    #[test]
+    fn leaves_both_sides_unsorted_if_either_cant_be_sorted() {
+        struct SometimesParseable(bool);
+
+        impl fmt::Debug for SometimesParseable {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                if self.0 {
+                    f.write_str("{2: 0, 1: 0}")
+                } else {
+                    f.write_str("{2: 0, 1: 0} (not parseable)")
+                }
+            }
+        }
+
+        for _ in 0..TEST_RERUNS_FOR_DETERMINISM {
+            let (left, right) = SortedDebug::pair(
+                SometimesParseable(false),
+                SometimesParseable(true),
+                SortOptions::default(),
+            );
+            assert_eq!(format!("{:?}", left), "{2: 0, 1: 0} (not parseable)");
+            assert_eq!(format!("{:?}", right), "{2: 0, 1: 0}");
+            // Whether a value is sorted on its own doesn't depend on other comparisons.
+            assert_eq!(
+                sorted_debug(SometimesParseable(true)),
+                indoc!(
+                    "{
+                        1: 0,
+                        2: 0,
+                    }"
+                )
+            );
+        }
+    }
+
+    #[derive(PartialEq)]
+    #[allow(unused)]
+    struct FooWithOptionalField {
+        value: Option<f32>,
+    }
+
+    impl fmt::Debug for FooWithOptionalField {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            let mut foo = f.debug_struct("FooWithOptionalField");
+            if let Some(value) = &self.value {
+                foo.field("value", value);
+                foo.finish()
+            } else {
+                foo.finish_non_exhaustive()
+            }
+        }
+    }
+
+    /// Test that the value field is displayed as missing (colored red) for optional fields
+    /// on non-exhaustive Debug implementations.
+    #[test]
+    #[should_panic(expected = "FooWithOptionalField {\n\u{1b}[31m<    value: 2.0,\u{1b}[0m\n }")]
+    fn ui_looks_right_for_non_exhaustive_optional_fields() {
+        assert_eq_sorted!(
+            FooWithOptionalField { value: Some(2.0) },
+            FooWithOptionalField { value: None }
+        );
+    }
+
+    #[test]
+    fn outputs_nice_output_for_non_exhaustive_objects_with_optional_fields() {
+        assert_eq!(
+            sorted_debug(FooWithOptionalField { value: Some(10.0) }),
+            indoc!(
+                "FooWithOptionalField {
+                    value: 10.0,
+                }"
+            )
+        );
+
+        assert_eq!(
+            sorted_debug(FooWithOptionalField { value: None }),
+            indoc!(
+                "FooWithOptionalField {
+                }"
+            )
+        );
+    }
+
+    #[test]
+    fn deep_unordered_sorts_lists_and_sets_at_all_levels() {
+        #[derive(Debug, PartialEq)]
+        struct Foo {
+            groups: HashMap<&'static str, Vec<HashSet<i32>>>,
+        }
+
+        // Building the lists from HashMap iteration makes the ordering of the lists (and
+        // the sets inside of them) non-deterministic, in addition to the map ordering.
+        fn unordered_vec<T>(values: Vec<T>) -> Vec<T> {
+            values
+                .into_iter()
+                .enumerate()
+                .collect::<HashMap<_, _>>()
+                .into_values()
+                .collect()
+        }
+
+        fn build() -> Foo {
+            let mut groups = HashMap::new();
+            groups.insert(
+                "a",
+                unordered_vec(vec![
+                    vec![3, 1, 2].into_iter().collect(),
+                    vec![10].into_iter().collect(),
+                ]),
+            );
+            groups.insert("b", unordered_vec(vec![vec![5, 4].into_iter().collect()]));
+            Foo { groups }
+        }
+
+        let options = SortOptions::default().sort_lists(true);
+        for _ in 0..TEST_RERUNS_FOR_DETERMINISM {
+            assert_eq_sorted_deep_unordered!(build(), build());
+
+            let expected = indoc!(
+                "Foo {
+                    groups: {
+                        \"a\": [
+                            {
+                                1,
+                                2,
+                                3,
+                            },
+                            {
+                                10,
+                            },
+                        ],
+                        \"b\": [
+                            {
+                                4,
+                                5,
+                            },
+                        ],
+                    },
+                }"
+            );
+            assert_eq!(
+                format!("{:#?}", SortedDebug::with_options(build(), options.clone())),
+                expected
+            );
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "assertion failed: `(left == right)`: lists differ")]
+    fn deep_unordered_panics_on_different_contents() {
+        assert_eq_sorted_deep_unordered!(vec![1, 2, 3], vec![3, 2], "lists differ");
+    }
+
+    #[test]
+    fn sorts_hashmap_with_mixed_enum_variant_values() {
+        #[derive(Debug)]
+        #[allow(unused)]
+        enum Shape {
+            Empty,
+            Newtype(HashMap<&'static str, i32>),
+            Pair(i32, HashMap<i32, bool>),
+            Named {
+                tags: HashMap<&'static str, Shape>,
+                size: f32,
+            },
+        }
+
+        for _ in 0..TEST_RERUNS_FOR_DETERMINISM {
+            let item = {
+                let mut map = HashMap::new();
+                map.insert("empty", Shape::Empty);
+                map.insert(
+                    "newtype",
+                    Shape::Newtype({
+                        let mut map = HashMap::new();
+                        map.insert("z", 1);
+                        map.insert("a", 2);
+                        map
+                    }),
+                );
+                map.insert(
+                    "pair",
+                    Shape::Pair(3, {
+                        let mut map = HashMap::new();
+                        map.insert(20, true);
+                        map.insert(10, false);
+                        map
+                    }),
+                );
+                map.insert(
+                    "named",
+                    Shape::Named {
+                        tags: {
+                            let mut map = HashMap::new();
+                            map.insert("y", Shape::Empty);
+                            map.insert("x", Shape::Pair(-1, HashMap::new()));
+                            map
+                        },
+                        size: 1.5,
+                    },
+                );
+                map
+            };
+
+            let expected = indoc!(
+                "{
+                    \"empty\": Empty,
+                    \"named\": Named {
+                        tags: {
+                            \"x\": Pair(
+                                -1,
+                                {},
+                            ),
+                            \"y\": Empty,
+                        },
+                        size: 1.5,
+                    },
+                    \"newtype\": Newtype(
+                        {
+                            \"a\": 2,
+                            \"z\": 1,
+                        },
+                    ),
+                    \"pair\": Pair(
+                        3,
+                        {
+                            10: false,
+                            20: true,
+                        },
+                    ),
+                }"
+            );
+            assert_eq!(sorted_debug(item), expected);
+        }
+    }
+
+    #[test]
+    fn sorts_hashmap_with_mixed_enum_variant_keys() {
+        #[derive(Debug, PartialEq, Eq, Hash)]
+        enum Key {
+            Unit,
+            Tuple(i32),
+            Struct { id: i32 },
+        }
+
+        for _ in 0..TEST_RERUNS_FOR_DETERMINISM {
+            let item = {
+                let mut map = HashMap::new();
+                map.insert(Key::Unit, 1);
+                map.insert(Key::Tuple(2), 2);
+                map.insert(Key::Tuple(1), 3);
+                map.insert(Key::Struct { id: 1 }, 4);
+                map
+            };
+
+            // The tuple variants are the most common kind of key and are sorted first, the
+            // keys of other kinds are ordered by their Debug output.
+            let expected = indoc!(
+                "{
+                    Tuple(
+                        1,
+                    ): 3,
+                    Tuple(
+                        2,
+                    ): 2,
+                    Struct {
+                        id: 1,
+                    }: 4,
+                    Unit: 1,
+                }"
+            );
+            assert_eq!(sorted_debug(item), expected);
+        }
+    }
+
+    #[test]
+    fn sorted_debug_hash_is_order_independent() {
+        #[derive(Debug)]
+        #[allow(unused)]
+        struct Foo {
+            values: HashMap<&'static str, HashMap<i32, bool>>,
+        }
+
+        fn build() -> Foo {
+            let mut values = HashMap::new();
+            for key in ["a", "b", "c", "d"] {
+                let mut inner = HashMap::new();
+                inner.insert(1, true);
+                inner.insert(2, false);
+                inner.insert(30, true);
+                values.insert(key, inner);
+            }
+            Foo { values }
+        }
+
+        let expected = sorted_debug_hash(&build());
+        for _ in 0..TEST_RERUNS_FOR_DETERMINISM {
+            assert_eq!(sorted_debug_hash(&build()), expected);
+        }
+    }
+
+    #[test]
+    fn sorted_debug_hash_is_stable_across_runs() {
+        let item = {
+            let mut map = HashMap::new();
+            map.insert(1, true);
+            map.insert(2, false);
+            map
+        };
+
+        assert_eq!(sorted_debug_hash(&item), 0x36c3_d5e5_910f_78ed);
+        assert_ne!(sorted_debug_hash(&item), sorted_debug_hash(&2));
+    }
+
+    #[test]
+    fn labeled_comparison_with_default_labels_matches_comparison() {
+        let reset = if render::colors_enabled() {
+            COLOR_RESET
+        } else {
+            ""
+        };
+        assert_eq!(
+            LabeledComparison::new(&SortedDebug::new(1), &SortedDebug::new(2), "left", "right")
+                .to_string(),
+            Comparison::new(&SortedDebug::new(1), &SortedDebug::new(2)).to_string() + reset
+        );
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "assertion failed: `(actual == expected)`: labeled\n\n\u{1b}[1mDiff\u{1b}[0m \u{1b}[31m< actual\u{1b}[0m / \u{1b}[32mexpected >\u{1b}[0m :\n"
+    )]
+    fn labels_appear_in_the_output() {
+        assert_eq_sorted!(1 => "actual", 2 => "expected", "labeled");
+    }
+
+    #[test]
+    fn sorts_hashmap_with_unit_struct_keys() {
+        #[derive(Debug, PartialEq, Eq, Hash)]
+        struct Foo;
+
+        for _ in 0..TEST_RERUNS_FOR_DETERMINISM {
+            let item = {
+                let mut map = HashMap::new();
+                map.insert(Foo, 1);
+                // All unit struct keys are equal, so this replaces the existing entry.
+                map.insert(Foo, 2);
+                map
+            };
+
+            let expected = indoc!(
+                "{
+                    Foo: 2,
+                }"
+            );
+            assert_eq!(sorted_debug(item), expected);
+        }
+    }
+
+    #[test]
+    fn sorts_hashmap_with_empty_tuple_keys() {
+        for _ in 0..TEST_RERUNS_FOR_DETERMINISM {
+            let item = {
+                let mut map = HashMap::new();
+                map.insert((), vec![()]);
+                map
+            };
+
+            let expected = indoc!(
+                "{
+                    (): [
+                        (),
+                    ],
+                }"
+            );
+            assert_eq!(sorted_debug(item), expected);
+        }
+    }
+
+    #[test]
+    fn sorts_nested_hashmaps_with_degenerate_keys() {
+        #[derive(Debug, PartialEq, Eq, Hash)]
+        struct Foo;
+
+        for _ in 0..TEST_RERUNS_FOR_DETERMINISM {
+            let item = {
+                let mut map = HashMap::new();
+                map.insert(Some(()), HashMap::from([(Foo, ())]));
+                map.insert(None, HashMap::new());
+                map
+            };
+
+            let expected = indoc!(
+                "{
+                    Some(
+                        (),
+                    ): {
+                        Foo: (),
+                    },
+                    None: {},
+                }"
+            );
+            assert_eq!(sorted_debug(item), expected);
+        }
+    }
+
+    #[test]
+    fn highlighted_keys_only_on_unchanged_lines_of_a_diff() {
+        let options = SortOptions::default().highlight_keys(true);
+        let comparison = LabeledComparison::new(
+            &SortedDebug::with_options(HashMap::from([("a", 1), ("b", 2)]), options.clone()),
+            &SortedDebug::with_options(HashMap::from([("a", 1), ("b", 3)]), options),
+            "left",
+            "right",
+        )
+        .to_string();
+
+        if render::colors_enabled() {
+            assert!(comparison.contains(" {\n     \u{1b}[36m\"a\"\u{1b}[39m: 1,\n"));
+        }
+        assert!(!comparison.contains("\u{1b}[36m\"b\""));
+    }
+
+    #[test]
+    fn sorted_any_passes_if_any_alternative_matches() {
+        for _ in 0..TEST_RERUNS_FOR_DETERMINISM {
+            let item = HashMap::from([(1, "a"), (2, "b")]);
+            assert_eq_sorted_any!(
+                item,
+                [
+                    HashMap::from([(1, "a")]),
+                    HashMap::from([(2, "b"), (1, "a")]),
+                ]
+            );
+        }
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "assertion failed: `left` matches none of the 3 alternatives: \
+                               no match\n\nDiff against the closest alternative (#2):\n"
+    )]
+    fn sorted_any_shows_diff_against_closest_alternative() {
+        assert_eq_sorted_any!(
+            HashMap::from([(1, "a"), (2, "b"), (3, "c")]),
+            [
+                HashMap::from([(1, "x")]),
+                HashMap::from([(1, "a"), (2, "b"), (3, "d")]),
+                HashMap::from([(4, "a"), (5, "b")]),
+            ],
+            "no match"
+        );
+    }
+
+    #[test]
+    fn sorts_maps_with_fat_arrow_separators() {
+        struct ArrowMap(HashMap<&'static str, i32>);
+
+        impl fmt::Debug for ArrowMap {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str("{")?;
+                for (i, (key, value)) in self.0.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(", ")?;
+                    }
+                    write!(f, "{:?} => {:?}", key, value)?;
+                }
+                f.write_str("}")
+            }
+        }
+
+        for _ in 0..TEST_RERUNS_FOR_DETERMINISM {
+            let item = ArrowMap(HashMap::from([("b", 2), ("a => c", 1), ("c", 3)]));
+
+            let expected = indoc!(
+                "{
+                    \"a => c\": 1,
+                    \"b\": 2,
+                    \"c\": 3,
+                }"
+            );
+            assert_eq!(sorted_debug(item), expected);
+        }
+    }
+
+    #[test]
+    fn groups_and_annotates_unsortable_keys() {
+        #[derive(Debug, PartialEq, Eq, Hash)]
+        enum Key {
+            Zed,
+            Alpha,
+            Id(i32),
+            Named { name: &'static str },
+        }
+
+        let options = SortOptions::default().annotate_unsortable(true);
+        for _ in 0..TEST_RERUNS_FOR_DETERMINISM {
+            let item = {
+                let mut map = HashMap::new();
+                map.insert(Key::Id(2), 1);
+                map.insert(Key::Zed, 2);
+                map.insert(Key::Named { name: "foo" }, 3);
+                map.insert(Key::Alpha, 4);
+                map.insert(Key::Id(1), 5);
+                map
+            };
+
+            let expected = indoc!(
+                "{
+                    Id(
+                        1,
+                    ): 5,
+                    Id(
+                        2,
+                    ): 1,
+                    Alpha: 4, // unsortable key, ordered by its Debug output
+                    Named {
+                        name: \"foo\",
+                    }: 3, // unsortable key, ordered by its Debug output
+                    Zed: 2, // unsortable key, ordered by its Debug output
+                }"
+            );
+            assert_eq!(
+                format!("{:#?}", SortedDebug::with_options(item, options.clone())),
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn sorted_multiset_passes_for_reordered_elements() {
+        for _ in 0..TEST_RERUNS_FOR_DETERMINISM {
+            let events = HashMap::from([(1, "created"), (2, "updated"), (3, "updated")]);
+            assert_eq_sorted_multiset!(events.into_values(), vec!["updated", "created", "updated"]);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "assertion failed: `(left == right)` as multisets \
+                               (3 elements in left, 2 elements in right): events\n\n")]
+    fn sorted_multiset_reports_counts() {
+        assert_eq_sorted_multiset!(vec!["a", "b", "b"], vec!["b", "a"], "events");
+    }
+
+    #[test]
+    fn sorts_string_keys_by_length_then_lexical() {
+        let options = SortOptions::default().sort_by_length_then_lexical(true);
+        for _ in 0..TEST_RERUNS_FOR_DETERMINISM {
+            let item = {
+                let mut map = HashMap::new();
+                for key in ["ccc", "a", "bb", "aaa", "b", "aa"] {
+                    map.insert(key, key.len());
+                }
+                map
+            };
+
+            let expected = indoc!(
+                "{
+                    \"a\": 1,
+                    \"b\": 1,
+                    \"aa\": 2,
+                    \"bb\": 2,
+                    \"aaa\": 3,
+                    \"ccc\": 3,
+                }"
+            );
+            assert_eq!(
+                format!("{:#?}", SortedDebug::with_options(item, options.clone())),
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn truncated_diff_keeps_the_differences() {
+        let left = (0..100).map(|i| (i, i)).collect::<HashMap<_, _>>();
+        let mut right = left.clone();
+        right.insert(50, -50);
+
+        let options = SortOptions::default().max_diff_bytes(200);
+        let comparison = LabeledComparison::new(
+            &SortedDebug::with_options(&left, options.clone()),
+            &SortedDebug::with_options(&right, options.clone()),
+            "left",
+            "right",
+        )
+        .with_options(&options)
+        .to_string();
+
+        let (_header, diff) = comparison.split_once('\n').unwrap();
+        assert!(diff.starts_with("…\n"), "{}", diff);
+        assert!(diff.contains("    49: 49,\n"), "{}", diff);
+        assert!(!diff.contains("    1: 1,\n"), "{}", diff);
+        assert!(diff.contains("… (diff truncated, "), "{}", diff);
+        assert!(diff.len() < 300, "{}", diff);
+    }
+
+    #[test]
+    #[should_panic(expected = "bytes omitted)")]
+    fn sorted_assertion_truncates_diff() {
+        let left = (0..100).map(|i| (i, i)).collect::<HashMap<_, _>>();
+        let right = (0..100).map(|i| (i, -i)).collect::<HashMap<_, _>>();
+        assert_eq_sorted!(left, right, max_diff_bytes = 100);
+    }
+
+    #[test]
+    #[should_panic(expected = "... 98 unchanged lines ...")]
+    fn sorted_assertion_elides_unchanged_lines() {
+        let left = (0..100).map(|i| (i, i)).collect::<HashMap<_, _>>();
+        let mut right = left.clone();
+        right.insert(99, -99);
+        assert_eq_sorted!(left, right, max_lines = 5);
+    }
+
+    #[test]
+    fn sorts_fields_of_enum_variants() {
+        // Emulates a macro-generated Debug implementation that lists the fields of the
+        // variant in a non-deterministic order.
+        struct Event(Vec<(&'static str, i32)>);
+
+        impl fmt::Debug for Event {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                let mut variant = f.debug_struct("Moved");
+                for (name, value) in &self.0 {
+                    variant.field(name, value);
+                }
+                variant.finish()
+            }
+        }
+
+        #[derive(Debug)]
+        #[allow(unused)]
+        struct Position {
+            y: i32,
+            x: i32,
+        }
+
+        let options = SortOptions::default().sort_enum_variant_fields(["Moved"]);
+        for _ in 0..TEST_RERUNS_FOR_DETERMINISM {
+            let fields = vec![("z", 3), ("x", 1), ("y", 2)]
+                .into_iter()
+                .collect::<HashMap<_, _>>();
+            let item = (Event(fields.into_iter().collect()), Position { y: 2, x: 1 });
+
+            let expected = indoc!(
+                "(
+                    Moved {
+                        x: 1,
+                        y: 2,
+                        z: 3,
+                    },
+                    Position {
+                        y: 2,
+                        x: 1,
+                    },
+                )"
+            );
+            assert_eq!(
+                format!("{:#?}", SortedDebug::with_options(item, options.clone())),
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn sorted_within_passes_up_to_max_differences() {
+        for _ in 0..TEST_RERUNS_FOR_DETERMINISM {
+            let left = HashMap::from([("a", 1), ("b", 2), ("c", 3)]);
+            let right = HashMap::from([("c", 3), ("b", 20), ("a", 10)]);
+            assert_eq_sorted_within!(left.clone(), right.clone(), max_differences = 2);
+            assert_eq_sorted_within!(left.clone(), left, max_differences = 0);
+        }
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "assertion failed: `(left == right)` within 1 differences, \
+                               found 2 differences: flaky\n\n"
+    )]
+    fn sorted_within_reports_differences() {
+        let left = HashMap::from([("a", 1), ("b", 2), ("c", 3)]);
+        let right = HashMap::from([("c", 3), ("b", 20), ("a", 10)]);
+        assert_eq_sorted_within!(left, right, max_differences = 1, "flaky");
+    }
+
+    #[test]
+    fn normalizes_path_keys() {
+        let options = SortOptions::default().normalize_path_keys(true);
+        for _ in 0..TEST_RERUNS_FOR_DETERMINISM {
+            let windows = HashMap::from([
+                (PathBuf::from("src\\main.rs"), 1),
+                (PathBuf::from("src\\lib.rs"), 2),
+                (PathBuf::from("README.md"), 3),
+            ]);
+            let unix = HashMap::from([
+                (PathBuf::from("src/main.rs"), 1),
+                (PathBuf::from("README.md"), 3),
+                (PathBuf::from("src/lib.rs"), 2),
+            ]);
+
+            let expected = indoc!(
+                "{
+                    \"README.md\": 3,
+                    \"src/lib.rs\": 2,
+                    \"src/main.rs\": 1,
+                }"
+            );
+            assert_eq!(
+                format!(
+                    "{:#?}",
+                    SortedDebug::with_options(&windows, options.clone())
+                ),
+                expected
+            );
+            assert_eq!(
+                format!("{:#?}", SortedDebug::with_options(&unix, options.clone())),
+                expected
+            );
+            assert_eq_sorted!(windows, unix, normalize_path_keys = true);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "assertion failed: `(left == right)`")]
+    fn path_keys_differ_without_normalizing() {
+        assert_eq_sorted!(
+            HashMap::from([(PathBuf::from("src\\main.rs"), 1)]),
+            HashMap::from([(PathBuf::from("src/main.rs"), 1)])
+        );
+    }
+
+    fn write_temp_file(name: &str, contents: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!(
+            "pretty_assertions_sorted_{}_{}",
+            std::process::id(),
+            name
+        ));
+        std::fs::write(&path, contents).unwrap();
+        path
+    }
+
+    #[test]
+    fn sorted_files_pass_when_only_map_ordering_differs() {
+        for index in 0..TEST_RERUNS_FOR_DETERMINISM {
+            let item = HashMap::from([("a", vec![1, 2]), ("b", vec![3]), ("c", vec![])]);
+            let left = write_temp_file(
+                &format!("left_{}.txt", index),
+                &format!("header\n{:#?}\nfooter\n", item),
+            );
+            let right = write_temp_file(
+                &format!("right_{}.txt", index),
+                &format!("header\n{:#?}\nfooter\n", item.clone()),
+            );
+            assert_files_eq_sorted!(&left, &right);
+            std::fs::remove_file(left).unwrap();
+            std::fs::remove_file(right).unwrap();
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "with sorted map sections: generated")]
+    fn sorted_files_fail_when_contents_differ() {
+        let left = write_temp_file("differ_left.txt", "{\n    b: 2,\n    a: 1,\n}\n");
+        let right = write_temp_file("differ_right.txt", "{\n    a: 1,\n    b: 3,\n}\n");
+        assert_files_eq_sorted!(left, right, "generated");
+    }
+
+    #[test]
+    fn hides_empty_containers() {
+        #[derive(Debug, PartialEq)]
+        struct Inventory {
+            name: &'static str,
+            tags: Vec<&'static str>,
+            counts: HashMap<&'static str, HashSet<i32>>,
+            extra: Option<HashMap<i32, i32>>,
+        }
+
+        let options = SortOptions::default().hide_empty_containers(true);
+        for _ in 0..TEST_RERUNS_FOR_DETERMINISM {
+            let item = Inventory {
+                name: "shelf",
+                tags: vec![],
+                counts: HashMap::from([("a", HashSet::new()), ("b", HashSet::from([1]))]),
+                extra: Some(HashMap::new()),
+            };
+
+            let expected = indoc!(
+                "Inventory {
+                    name: \"shelf\",
+                    counts: {
+                        \"b\": {
+                            1,
+                        },
+                    },
+                    extra: Some(
+                        {},
+                    ),
+                }"
+            );
+            assert_eq!(
+                format!("{:#?}", SortedDebug::with_options(item, options.clone())),
+                expected
+            );
+        }
+
+        let left = HashMap::from([("a", vec![]), ("b", vec![1])]);
+        let right = HashMap::from([("b", vec![1])]);
+        assert_eq_sorted!(left, right, hide_empty_containers = true);
+    }
+
+    #[test]
+    fn previews_first_keys_of_nested_maps() {
+        for _ in 0..TEST_RERUNS_FOR_DETERMINISM {
+            let item = (0..5)
+                .map(|i| (i, (0..i * 2).map(|j| (j, ())).collect::<HashMap<_, _>>()))
+                .collect::<HashMap<_, _>>();
+
+            let expected = indoc!(
+                "{
+                    0: {},
+                    1: {
+                        0: (),
+                        1: (),
+                    },
+                    2: {
+                        0: (),
+                        1: (),
+                        2: (),
+                        … (+1 more)
+                    },
+                    … (+2 more)
+                }"
+            );
+            assert_eq!(preview_sorted(&item, 3), expected);
+        }
+    }
+
+    #[test]
+    fn sorts_output_with_comments() {
+        struct Annotated(HashMap<i32, &'static str>);
+
+        impl fmt::Debug for Annotated {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str("{")?;
+                for (key, value) in &self.0 {
+                    writeln!(f, "{}: {:?}, // inserted by {}", key, value, value)?;
+                }
+                f.write_str("} // generated")
+            }
+        }
+
+        for _ in 0..TEST_RERUNS_FOR_DETERMINISM {
+            let item = Annotated(HashMap::from([(2, "b"), (1, "a"), (3, "c")]));
+            let expected = indoc!(
+                "{
+                    1: \"a\",
+                    2: \"b\",
+                    3: \"c\",
+                }"
+            );
+            assert_eq!(sorted_debug(item), expected);
+        }
+    }
+
+    #[test]
+    fn permutation_passes_for_reordered_elements() {
+        for _ in 0..TEST_RERUNS_FOR_DETERMINISM {
+            let original = vec![
+                HashMap::from([(1, "a"), (2, "b")]),
+                HashMap::from([(3, "c")]),
+                HashMap::from([(1, "a"), (2, "b")]),
+            ];
+            let mut shuffled = original.clone();
+            shuffled.rotate_left(1);
+            assert_permutation_sorted!(original, shuffled.as_slice());
+            // The values are only borrowed.
+            assert_eq!(original.len(), shuffled.len());
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "assertion failed: `right` is a permutation of `left` \
+                               (3 elements in left, 3 elements in right)\n\n")]
+    fn permutation_reports_mismatched_counts() {
+        assert_permutation_sorted!([1, 2, 2], [2, 1, 1]);
+    }
+
+    #[test]
+    fn sorts_path_qualified_type_names() {
+        // Emulates Debug implementations that print fully-qualified type names.
+        struct Qualified(&'static str);
+
+        impl fmt::Debug for Qualified {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str(self.0)
+            }
+        }
+
+        let options = SortOptions::default().sort_enum_variant_fields(["events::Event::Moved"]);
+        for _ in 0..TEST_RERUNS_FOR_DETERMINISM {
+            let item = HashMap::from([
+                ("b", Qualified("events::Event::Moved { y: 2, x: 1 }")),
+                (
+                    "a",
+                    Qualified("std::option::Option::Some(core::cmp::Ordering::Less)"),
+                ),
+            ]);
+
+            let expected = indoc!(
+                "{
+                    \"a\": std::option::Option::Some(
+                        core::cmp::Ordering::Less,
+                    ),
+                    \"b\": events::Event::Moved {
+                        x: 1,
+                        y: 2,
+                    },
+                }"
+            );
+            assert_eq!(
+                format!("{:#?}", SortedDebug::with_options(item, options.clone())),
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn sorts_tuples() {
+        let options = SortOptions::default().sort_tuples(true);
+        for _ in 0..TEST_RERUNS_FOR_DETERMINISM {
+            let edges = HashSet::from([(2, 1), (3, 2)]);
+            let expected = indoc!(
+                "{
+                    (
+                        1,
+                        2,
+                    ),
+                    (
+                        2,
+                        3,
+                    ),
+                }"
+            );
+            assert_eq!(
+                format!("{:#?}", SortedDebug::with_options(&edges, options.clone())),
+                expected
+            );
+        }
+
+        #[derive(Debug, PartialEq)]
+        struct Point(i32, i32);
+        assert_eq!(
+            format!("{:?}", SortedDebug::with_options(Point(2, 1), options)),
+            "Point(\n    2,\n    1,\n)"
+        );
+
+        assert_eq_sorted!((2, 1), (1, 2), sort_tuples = true);
+    }
+
+    #[test]
+    #[should_panic(expected = "assertion failed: `(left == right)`")]
+    fn tuples_are_ordered_by_default() {
+        assert_eq_sorted!((2, 1), (1, 2));
+    }
+
+    #[test]
+    fn sorts_maps_inside_unsorted_tuples() {
+        for _ in 0..TEST_RERUNS_FOR_DETERMINISM {
+            let pair = (
+                HashMap::from([(2, "b"), (1, "a")]),
+                HashMap::from([(0, "z")]),
+            );
+            assert_eq!(
+                format!("{:#?}", SortedDebug::new(&pair)),
+                indoc!(
+                    r#"
+                    (
+                        {
+                            1: "a",
+                            2: "b",
+                        },
+                        {
+                            0: "z",
+                        },
+                    )"#
+                )
+            );
+            assert_eq_sorted!(
+                (pair.1.clone(), pair.0.clone()),
+                pair.clone(),
+                sort_tuples = true
+            );
+        }
+    }
+
+    #[test]
+    fn tolerances_pass_for_close_values() {
+        for _ in 0..TEST_RERUNS_FOR_DETERMINISM {
+            let left = HashMap::from([("latency", 100.0), ("rate", 0.5), ("count", 3.0)]);
+            let right = HashMap::from([("count", 3.0), ("rate", 0.505), ("latency", 95.0)]);
+            assert_eq_sorted_tol!(left, right, { "latency" => 5.0, "rate" => 0.01 });
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "assertion failed: `(left == right)` within tolerances, \
+                               2 values exceeded their tolerance: metrics\n  \
+                               count: 3.0 vs. 4.0 (must match exactly)\n  \
+                               latency: 100.0 vs. 106.0 (tolerance 5)\n\n")]
+    fn tolerances_report_exceeded_keys() {
+        let left = HashMap::from([("latency", 100.0), ("count", 3.0)]);
+        let right = HashMap::from([("count", 4.0), ("latency", 106.0)]);
+        assert_eq_sorted_tol!(left, right, { "latency" => 5.0 }, "metrics");
+    }
+
+    #[test]
+    fn shows_unsorted_output_too() {
+        // A map with a known (unsorted) iteration order.
+        struct Entries(Vec<(&'static str, i32)>);
+
+        impl fmt::Debug for Entries {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.debug_map()
+                    .entries(self.0.iter().map(|(k, v)| (k, v)))
+                    .finish()
+            }
+        }
+
+        let (left, right) = (Entries(vec![("b", 2), ("a", 1)]), Entries(vec![("a", 1)]));
+        let options = SortOptions::default().show_unsorted_too(true);
+        let comparison = LabeledComparison::new(
+            &SortedDebug::with_options(&left, options.clone()),
+            &SortedDebug::with_options(&right, options.clone()),
+            "actual",
+            "expected",
+        )
+        .with_options(&options)
+        .with_unsorted(&left, &right)
+        .to_string();
+
+        let (diff, unsorted) = comparison.split_once("\n\nUnsorted").unwrap();
+        assert!(diff.contains("\"a\": 1,"), "{}", diff);
+        assert_eq!(
+            unsorted.trim_end_matches(COLOR_RESET),
+            " actual: {\"b\": 2, \"a\": 1}\nUnsorted expected: {\"a\": 1}\n"
+        );
+    }
+
+    #[derive(Debug)]
+    #[allow(unused)]
+    enum Connection {
+        Connecting(u32),
+        Connected { peers: HashMap<&'static str, u32> },
+        Disconnected,
+    }
+
+    #[test]
+    fn variants_pass_with_different_payloads() {
+        assert_eq_sorted_variant!(
+            Connection::Connected {
+                peers: HashMap::from([("a", 1)])
+            },
+            Connection::Connected {
+                peers: HashMap::new()
+            }
+        );
+        assert_eq_sorted_variant!(Connection::Connecting(1), Connection::Connecting(2));
+        assert_eq_sorted_variant!(Connection::Disconnected, Connection::Disconnected);
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "assertion failed: `(left == right)` by variant: state\n\n \
+                               left: Connecting\nright: Disconnected\n"
+    )]
+    fn variants_report_names() {
+        assert_eq_sorted_variant!(Connection::Connecting(1), Connection::Disconnected, "state");
+    }
+
+    #[test]
+    fn sorts_keys_by_criteria_chain() {
+        let options = SortOptions::default().sort_chain(vec![
+            SortCriterion::Numeric,
+            SortCriterion::Length,
+            SortCriterion::CaseInsensitive,
+            SortCriterion::Lexical,
+        ]);
+        for _ in 0..TEST_RERUNS_FOR_DETERMINISM {
+            let item = ["item10", "Item2", "item2", "b", "10", "9.5", "A"]
+                .iter()
+                .map(|&key| (key, ()))
+                .collect::<HashMap<_, _>>();
+
+            let expected = indoc!(
+                "{
+                    \"9.5\": (),
+                    \"10\": (),
+                    \"A\": (),
+                    \"b\": (),
+                    \"Item2\": (),
+                    \"item2\": (),
+                    \"item10\": (),
+                }"
+            );
+            assert_eq!(
+                format!("{:#?}", SortedDebug::with_options(item, options.clone())),
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn sorted_eq_returns_diff() {
+        for _ in 0..TEST_RERUNS_FOR_DETERMINISM {
+            let actual = HashMap::from([("a", 1), ("b", 2)]);
+            assert_eq!(sorted_eq(&actual, &actual.clone()), Ok(()));
+
+            let expected = HashMap::from([("b", 3), ("a", 1)]);
+            let error = sorted_eq(&actual, &expected).unwrap_err();
+            assert_eq!(format!("{:?}", error), error.to_string());
+            assert_eq!(
+                error.to_string(),
+                format!(
+                    "assertion failed: `(actual == expected)`\n\n{}",
+                    LabeledComparison::new(
+                        &SortedDebug::new(&actual),
+                        &SortedDebug::new(&expected),
+                        "actual",
+                        "expected"
+                    )
+                )
+            );
+        }
+    }
+
+    #[test]
+    fn sorts_strings_with_control_chars() {
+        for _ in 0..TEST_RERUNS_FOR_DETERMINISM {
+            let item = HashMap::from([("b\0", "\x07bell"), ("a", "it's\u{1b}[31m")]);
+
+            let expected = indoc!(
+                r#"{
+                    "a": "it's\u{1b}[31m",
+                    "b\0": "\u{7}bell",
+                }"#
+            );
+            assert_eq!(sorted_debug(&item), expected);
+
+            let options = SortOptions::default().visible_control_chars(true);
+            let expected = indoc!(
+                r#"{
+                    "a": "it's␛[31m",
+                    "b␀": "␇bell",
+                }"#
+            );
+            assert_eq!(
+                format!("{:#?}", SortedDebug::with_options(&item, options)),
+                expected
+            );
+        }
+    }
+
+    #[derive(Debug, Clone, PartialEq)]
+    #[allow(unused)]
+    struct Response {
+        request_id: u64,
+        headers: HashMap<&'static str, &'static str>,
+        body: Body,
+    }
+
+    #[derive(Debug, Clone, PartialEq)]
+    #[allow(unused)]
+    struct Body {
+        items: Vec<HashMap<&'static str, i32>>,
+        generated_at: u64,
+    }
+
+    fn response(request_id: u64, count: i32, generated_at: u64) -> Response {
+        Response {
+            request_id,
+            headers: HashMap::from([("content-type", "json"), ("date", "today")]),
+            body: Body {
+                items: vec![HashMap::from([("count", count), ("id", 1)])],
+                generated_at,
+            },
+        }
+    }
+
+    #[test]
+    fn only_compares_focused_subtrees() {
+        for _ in 0..TEST_RERUNS_FOR_DETERMINISM {
+            assert_eq_sorted_only!(
+                response(1, 10, 100),
+                response(2, 10, 200),
+                only = ["body.items", "headers.content-type"]
+            );
+            assert_eq_sorted_only!(
+                response(1, 10, 100),
+                response(2, 10, 200),
+                only = ["body.items[0].count"]
+            );
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "assertion failed: `(left == right)` at [\"body.items[0]\"]")]
+    fn only_fails_when_focused_subtree_differs() {
+        assert_eq_sorted_only!(
+            response(1, 10, 100),
+            response(1, 11, 100),
+            only = ["body.items[0]"]
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "assertion failed: path not found in either value: [\"bdy\"]")]
+    fn only_fails_when_path_is_missing_on_both_sides() {
+        assert_eq_sorted_only!(
+            response(1, 10, 100),
+            response(1, 11, 100),
+            only = ["body.items[0]", "bdy"]
+        );
+    }
+
+    #[test]
+    fn only_compares_paths_missing_on_one_side() {
+        let left = HashMap::from([("a", 1)]);
+        let right = HashMap::from([("b", 1)]);
+        let result = std::panic::catch_unwind(|| {
+            assert_eq_sorted_only!(left, right, only = ["a"]);
+        });
+        let message = *result.unwrap_err().downcast::<String>().unwrap();
+        assert!(message.contains("<missing>"), "{}", message);
+    }
+
+    #[test]
+    fn focused_debug_shows_missing_paths() {
+        let expected = indoc!(
+            "{
+                \"body.items[0].count\": 10,
+                \"body.missing\": <missing>,
+            }"
+        );
+        assert_eq!(
+            format!(
+                "{:?}",
+                FocusedDebug::new(
+                    &response(1, 10, 100),
+                    &["body.items[0].count", "body.missing"]
+                )
+            ),
+            expected
+        );
+    }
+
+    #[test]
+    fn wraps_top_level_containers_in_fold_markers() {
+        for _ in 0..TEST_RERUNS_FOR_DETERMINISM {
+            let options = SortOptions::default().fold_markers(true);
+            let expected = indoc!(
+                r#"Response {
+                    request_id: 1,
+                    headers: { // {{{
+                        "content-type": "json",
+                        "date": "today",
+                    }, // }}}
+                    body: Body { // {{{
+                        items: [
+                            {
+                                "count": 10,
+                                "id": 1,
+                            },
+                        ],
+                        generated_at: 100,
+                    }, // }}}
+                }"#
+            );
+            assert_eq!(
+                format!(
+                    "{:#?}",
+                    SortedDebug::with_options(&response(1, 10, 100), options)
+                ),
+                expected
+            );
+        }
+    }
+
+    #[derive(Debug, PartialEq)]
+    #[allow(unused)]
+    enum Status {
+        Ok,
+        Success,
+        Failed,
+    }
+
+    #[test]
+    fn treats_aliased_terms_as_equal() {
+        for _ in 0..TEST_RERUNS_FOR_DETERMINISM {
+            let left = HashMap::from([("a", "OK"), ("b", "done"), ("c", "Ok")]);
+            let right = HashMap::from([("a", "success"), ("b", "finished"), ("c", "Ok")]);
+            assert_eq_sorted!(
+                left,
+                right,
+                term_aliases = [vec!["OK", "Ok", "success"], vec!["done", "finished"]]
+            );
+
+            let left = vec![Status::Ok, Status::Failed];
+            let right = vec![Status::Success, Status::Failed];
+            assert_eq_sorted!(left, right, term_aliases = [["Ok", "Success"]]);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "assertion failed: `(left == right)`")]
+    fn fails_on_terms_from_different_alias_groups() {
+        let left = HashMap::from([("a", "OK"), ("b", "done")]);
+        let right = HashMap::from([("a", "done"), ("b", "done")]);
+        assert_eq_sorted!(
+            left,
+            right,
+            term_aliases = [["OK", "success"], ["done", "finished"]]
+        );
+    }
+
+    #[test]
+    fn report_passes_for_equal_values() {
+        for _ in 0..TEST_RERUNS_FOR_DETERMINISM {
+            assert_eq_sorted_report!(response(1, 10, 100), response(1, 10, 100));
+        }
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "Differences:\n1. request_id: 1 -> 2\n2. body.items[0].count: 10 -> 11\n"
+    )]
+    fn report_lists_numbered_differences() {
+        assert_eq_sorted_report!(response(1, 10, 100), response(2, 11, 100));
+    }
+
+    #[test]
+    fn moves_values_onto_their_own_line_in_narrow_terminals() {
+        for _ in 0..TEST_RERUNS_FOR_DETERMINISM {
+            let item = response(1, 10, 100);
+            let options = SortOptions::default().min_terminal_width(80);
+            assert_eq!(
+                format!(
+                    "{:#?}",
+                    SortedDebug::with_options(&item, options.clone().terminal_width(120))
+                ),
+                sorted_debug(&item)
+            );
+
+            let expected = indoc!(
+                r#"Response {
+                    request_id: 1,
+                    headers: {
+                        "content-type":
+                            "json",
+                        "date":
+                            "today",
+                    },
+                    body: Body {
+                        items: [
+                            {
+                                "count":
+                                    10,
+                                "id":
+                                    1,
+                            },
+                        ],
+                        generated_at:
+                            100,
+                    },
+                }"#
+            );
+            assert_eq!(
+                format!(
+                    "{:#?}",
+                    SortedDebug::with_options(&item, options.terminal_width(20))
+                ),
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn lazy_calls_the_expected_closure_once() {
+        for _ in 0..TEST_RERUNS_FOR_DETERMINISM {
+            let calls = std::cell::Cell::new(0);
+            let expected = || {
+                calls.set(calls.get() + 1);
+                response(1, 10, 100)
+            };
+            assert_eq_sorted_lazy!(response(1, 10, 100), expected);
+            assert_eq!(calls.get(), 1);
+
+            assert_eq_sorted_lazy!(
+                HashMap::from([("a", 1), ("b", 2)]),
+                || HashMap::from([("b", 2), ("a", 1)]),
+                "with a {}",
+                "message"
+            );
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "assertion failed: `(left == right)`")]
+    fn lazy_fails_when_not_equal() {
+        assert_eq_sorted_lazy!(vec![1, 2], || vec![2, 1]);
+    }
+
+    #[test]
+    fn compares_captured_debug_strings() {
+        for _ in 0..TEST_RERUNS_FOR_DETERMINISM {
+            let stored = r#"Response { request_id: 1, headers: {"date": "today", "content-type": "json"}, body: Body { items: [{"id": 1, "count": 10}], generated_at: 100 } }"#;
+            let fresh = format!("{:#?}", response(1, 10, 100));
+            assert_debug_str_eq_sorted!(fresh, stored);
+            assert_debug_str_eq_sorted!("not { debug", "not { debug");
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "assertion failed: `(left == right)` with sorted Debug strings")]
+    fn fails_on_different_debug_strings() {
+        assert_debug_str_eq_sorted!(format!("{:?}", vec![1, 2]), "[2, 1]");
+    }
+
+    #[test]
+    #[should_panic(expected = "assertion failed: `(left == right)` with unparseable Debug strings")]
+    fn compares_unparseable_debug_strings_as_they_are() {
+        assert_debug_str_eq_sorted!("{\"a\": 1}", "not { debug");
+    }
+
+    #[test]
+    fn sorts_keys_by_their_rendered_text() {
+        for _ in 0..TEST_RERUNS_FOR_DETERMINISM {
+            let map = HashMap::from([("ab", 1), ("ba", 2), ("ca", 3), ("zz", 4)]);
+            let options = SortOptions::default().key_sort_render(|key| key.chars().rev().collect());
+            let expected = indoc!(
+                r#"{
+                    "ba": 2,
+                    "ca": 3,
+                    "ab": 1,
+                    "zz": 4,
+                }"#
+            );
+            assert_eq!(
+                format!("{:#?}", SortedDebug::with_options(&map, options)),
+                expected
+            );
+        }
+    }
+
+    /// A map with float keys, which can't be put into a `HashMap`.
+    struct FloatKeys(Vec<(f64, &'static str)>);
+
+    impl fmt::Debug for FloatKeys {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.debug_map()
+                .entries(self.0.iter().map(|(key, value)| (key, value)))
+                .finish()
+        }
+    }
+
+    #[test]
+    fn pairs_numeric_keys_with_different_formatting() {
+        for _ in 0..TEST_RERUNS_FOR_DETERMINISM {
+            let left = HashMap::from([(1, "a"), (2, "b")]);
+            let right = FloatKeys(vec![(2.0, "c"), (1.0, "a")]);
+            assert_eq!(sorted_differences(&left, &right).len(), 4);
+
+            let options = SortOptions::default().numeric_key_normalization(true);
+            let differences = sorted_differences_with_options(&left, &right, &options);
+            assert_eq!(
+                differences
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>(),
+                vec!["2: \"b\" -> \"c\""]
+            );
+        }
+    }
+
+    #[test]
+    fn allows_refinements_that_add_values() {
+        for _ in 0..TEST_RERUNS_FOR_DETERMINISM {
+            let base = HashMap::from([("id", vec![1]), ("tags", vec![])]);
+            let enriched = HashMap::from([("tags", vec![2, 3]), ("id", vec![1]), ("new", vec![4])]);
+            assert_refines_sorted!(base, enriched);
+            assert_refines_sorted!(response(1, 10, 100), response(1, 10, 100));
+        }
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "2 values of base were changed or removed\n  id[0]: 1 -> 2\n  name: [] -> <missing>\n"
+    )]
+    fn fails_on_changed_or_removed_values() {
+        let base = HashMap::from([("id", vec![1]), ("name", vec![])]);
+        let enriched = HashMap::from([("id", vec![2]), ("new", vec![4])]);
+        assert_refines_sorted!(base, enriched);
+    }
+
+    #[derive(Debug)]
+    #[allow(unused)]
+    struct Node {
+        name: &'static str,
+        parent: std::cell::RefCell<std::rc::Weak<Node>>,
+        children: std::cell::RefCell<Vec<std::rc::Rc<Node>>>,
+        data: *const u8,
+    }
+
+    fn graph(data: &[u8; 2]) -> std::rc::Rc<Node> {
+        let root = std::rc::Rc::new(Node {
+            name: "root",
+            parent: Default::default(),
+            children: Default::default(),
+            data: &data[0],
+        });
+        let child = std::rc::Rc::new(Node {
+            name: "child",
+            parent: std::cell::RefCell::new(std::rc::Rc::downgrade(&root)),
+            children: Default::default(),
+            data: &data[1],
+        });
+        root.children.borrow_mut().push(child);
+        root
+    }
+
+    #[test]
+    fn masks_addresses_in_graphs() {
+        for _ in 0..TEST_RERUNS_FOR_DETERMINISM {
+            let (left_data, right_data) = ([1, 2], [1, 2]);
+            let (left, right) = (graph(&left_data), graph(&right_data));
+            let options = SortOptions::default().mask_addresses(true);
+            assert_ne!(format!("{:?}", left), format!("{:?}", right));
+            assert_eq!(
+                format!("{:?}", SortedDebug::with_options(&left, options.clone())),
+                format!("{:?}", SortedDebug::with_options(&right, options.clone()))
+            );
+
+            let expected = indoc!(
+                r#"Node {
+                    name: "root",
+                    parent: RefCell {
+                        value: (
+                            Weak,
+                        ),
+                    },
+                    children: RefCell {
+                        value: [
+                            Node {
+                                name: "child",
+                                parent: RefCell {
+                                    value: (
+                                        Weak,
+                                    ),
+                                },
+                                children: RefCell {
+                                    value: [],
+                                },
+                                data: <address>,
+                            },
+                        ],
+                    },
+                    data: <address>,
+                }"#
+            );
+            assert_eq!(
+                format!("{:#?}", SortedDebug::with_options(&left, options)),
+                expected
+            );
+        }
+    }
+
+    /// A hasher that differs from the default one, like the hashers of `fxhash` or `ahash`.
+    #[derive(Default)]
+    struct XorHasher(u64);
+
+    impl std::hash::Hasher for XorHasher {
+        fn finish(&self) -> u64 {
+            self.0
+        }
+
+        fn write(&mut self, bytes: &[u8]) {
+            for byte in bytes {
+                self.0 = (self.0.rotate_left(5) ^ u64::from(*byte)).wrapping_mul(0x51_7cc1_b727);
+            }
+        }
+    }
+
+    #[test]
+    fn sorts_maps_with_different_hashers_the_same() {
+        for _ in 0..TEST_RERUNS_FOR_DETERMINISM {
+            let entries = (0..20).map(|i| (format!("key{}", i), i));
+            let std_map = entries.clone().collect::<HashMap<_, _>>();
+            let xor_map =
+                entries.collect::<HashMap<_, _, std::hash::BuildHasherDefault<XorHasher>>>();
+
+            assert_eq!(sorted_debug(&std_map), sorted_debug(&xor_map));
+            assert!(sorted_differences(&std_map, &xor_map).is_empty());
+        }
+    }
+
+    #[derive(Debug)]
+    #[allow(unused)]
+    struct Wrapped<T> {
+        value: T,
+        result: Result<Result<Option<i32>, ()>, ()>,
+    }
+
+    #[test]
+    fn unwraps_redundant_options() {
+        for _ in 0..TEST_RERUNS_FOR_DETERMINISM {
+            let once = Wrapped {
+                value: Some(HashMap::from([("a", 1), ("b", 2)])),
+                result: Ok(Ok(Some(1))),
+            };
+            let twice = Wrapped {
+                value: Some(Some(Some(HashMap::from([("b", 2), ("a", 1)])))),
+                result: Ok(Ok(Some(1))),
+            };
+            assert_ne!(sorted_debug(&once), sorted_debug(&twice));
+
+            let options = SortOptions::default().unwrap_redundant_options(true);
+            let expected = indoc!(
+                r#"Wrapped {
+                    value: Some(
+                        {
+                            "a": 1,
+                            "b": 2,
+                        },
+                    ),
+                    result: Ok(
+                        Some(
+                            1,
+                        ),
+                    ),
+                }"#
+            );
+            assert_eq!(
+                format!("{:#?}", SortedDebug::with_options(&once, options.clone())),
+                expected
+            );
+            assert_eq!(
+                format!("{:#?}", SortedDebug::with_options(&twice, options)),
+                expected
+            );
+        }
+    }
+
+    #[derive(Debug)]
+    #[allow(unused)]
+    struct Container<T> {
+        label: &'static str,
+        inner: T,
+    }
+
+    #[derive(Debug)]
+    #[allow(unused)]
+    struct WithMap {
+        map: HashMap<&'static str, i32>,
+    }
+
+    #[test]
+    fn sorts_generic_containers() {
+        let map = || HashMap::from([("b", 2), ("a", 1), ("c", 3)]);
+        for _ in 0..TEST_RERUNS_FOR_DETERMINISM {
+            let expected = indoc!(
+                r#"Container {
+                    label: "map",
+                    inner: {
+                        "a": 1,
+                        "b": 2,
+                        "c": 3,
+                    },
+                }"#
+            );
+            let container = Container {
+                label: "map",
+                inner: map(),
+            };
+            assert_eq!(sorted_debug(&container), expected);
+
+            let expected = indoc!(
+                r#"Container {
+                    label: "struct",
+                    inner: WithMap {
+                        map: {
+                            "a": 1,
+                            "b": 2,
+                            "c": 3,
+                        },
+                    },
+                }"#
+            );
+            let container = Container {
+                label: "struct",
+                inner: WithMap { map: map() },
+            };
+            assert_eq!(sorted_debug(&container), expected);
+
+            let expected = indoc!(
+                r#"Container {
+                    label: "nested",
+                    inner: Container {
+                        label: "map",
+                        inner: {
+                            "a": 1,
+                            "b": 2,
+                            "c": 3,
+                        },
+                    },
+                }"#
+            );
+            let container = Container {
+                label: "nested",
+                inner: Container {
+                    label: "map",
+                    inner: map(),
+                },
+            };
+            assert_eq!(sorted_debug(&container), expected);
+        }
+    }
+
+    #[test]
+    fn redacts_beyond_the_sorting_budget() {
+        #[derive(Debug)]
+        #[allow(unused)]
+        struct Login {
+            user: &'static str,
+            session: Session,
+        }
+
+        #[derive(Debug)]
+        #[allow(unused)]
+        struct Session {
+            token: &'static str,
+        }
+
+        let options = SortOptions::default().redact(["token"]).max_nodes(2);
+        let login = Login {
+            user: "ada",
+            session: Session { token: "SECRET" },
+        };
+        let output = format!("{:#?}", SortedDebug::with_options(&login, options));
+        assert!(!output.contains("SECRET"), "{}", output);
+        assert!(output.contains("token: <redacted>"), "{}", output);
+    }
+
+    #[test]
+    fn only_notes_an_exhausted_budget_when_nodes_were_left_unsorted() {
+        #[derive(Debug)]
+        #[allow(unused)]
+        struct Credentials {
+            user: &'static str,
+            token: &'static str,
+        }
+
+        let credentials = Credentials {
+            user: "ada",
+            token: "SECRET",
+        };
+        let options = SortOptions::default().redact(["token"]).max_nodes(2);
+        let output = format!("{:#?}", SortedDebug::with_options(&credentials, options));
+        assert!(!output.contains("sorting budget exhausted"), "{}", output);
+
+        let options = SortOptions::default().max_nodes(2);
+        let output = format!("{:#?}", SortedDebug::with_options(&credentials, options));
+        assert!(
+            output.ends_with("\n… (sorting budget exhausted)"),
+            "{}",
+            output
+        );
+    }
+
+    #[test]
+    fn stops_sorting_when_the_budget_is_exhausted() {
+        let values = || {
+            vec![
+                HashMap::from([("b", 2), ("a", 1)]),
+                HashMap::from([("d", 4), ("c", 3)]),
+            ]
+        };
+        let items = (
+            values(),
+            (0..1000).map(|i| (i, i)).collect::<HashMap<_, _>>(),
+        );
+
+        // The first map (and its 4 keys and values) fits into the budget of 7 nodes, after
+        // the tuple and the list.
+        let options = SortOptions::default().max_nodes(7);
+        let output = format!("{:#?}", SortedDebug::with_options(&items, options.clone()));
+        assert!(output.starts_with(
+            "(\n    [\n        {\n            \"a\": 1,\n            \"b\": 2,\n        },\n"
+        ));
+        assert!(output.ends_with("\n… (sorting budget exhausted)"));
+
+        let options = SortOptions::default().max_nodes(10_000);
+        let output = format!("{:#?}", SortedDebug::with_options(&values(), options));
+        assert_eq!(output, sorted_debug(values()));
+    }
+
+    #[derive(Debug)]
+    #[allow(unused)]
+    struct User {
+        user_id: u64,
+        display_name: &'static str,
+    }
+
+    /// The same type as [`User`], but with a manual Debug implementation that uses
+    /// different field names.
+    #[allow(unused)]
+    struct ManualUser {
+        user_id: u64,
+        display_name: &'static str,
+    }
+
+    impl fmt::Debug for ManualUser {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.debug_struct("User")
+                .field("userId", &self.user_id)
+                .field("DisplayName", &self.display_name)
+                .finish()
+        }
+    }
+
+    #[test]
+    fn matches_fields_ignoring_their_casing() {
+        for _ in 0..TEST_RERUNS_FOR_DETERMINISM {
+            let derived = User {
+                user_id: 1,
+                display_name: "alice",
+            };
+            let manual = ManualUser {
+                user_id: 1,
+                display_name: "alice",
+            };
+            assert_eq!(sorted_differences(&derived, &manual).len(), 4);
+
+            let options = SortOptions::default().case_insensitive_fields(true);
+            assert!(options.normalized_eq(&derived, &manual));
+            assert!(sorted_differences_with_options(&derived, &manual, &options).is_empty());
+
+            let manual = ManualUser {
+                user_id: 2,
+                display_name: "alice",
+            };
+            assert!(!options.normalized_eq(&derived, &manual));
+        }
+    }
+
+    #[test]
+    fn ne_passes_for_different_values() {
+        for _ in 0..TEST_RERUNS_FOR_DETERMINISM {
+            assert_ne_sorted!(response(1, 10, 100), response(1, 11, 100));
+            assert_ne_sorted!(
+                HashMap::from([("a", 1)]),
+                HashMap::from([("a", 2)]),
+                "with a {}",
+                "message"
+            );
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "assertion failed: `(left != right)`: with a message")]
+    fn ne_fails_for_equal_values() {
+        assert_ne_sorted!(
+            HashMap::from([("a", 1), ("b", 2)]),
+            HashMap::from([("b", 2), ("a", 1)]),
+            "with a {}",
+            "message"
+        );
+    }
+
+    #[test]
+    fn normalizes_string_terms() {
+        for _ in 0..TEST_RERUNS_FOR_DETERMINISM {
+            let left = HashMap::from([("a", "  One "), ("b", "two")]);
+            let right = HashMap::from([("a", "one"), ("b", " TWO")]);
+            assert_eq_sorted!(
+                left,
+                right,
+                term_normalizer = |s: &str| s.trim().to_lowercase()
+            );
+        }
+    }
+
+    #[test]
+    fn only_compares_normalized_terms_when_the_normalizer_changes_them() {
+        for _ in 0..TEST_RERUNS_FOR_DETERMINISM {
+            // NaN isn't equal to itself, even though its Debug output is.
+            let left = HashMap::from([("a", f64::NAN)]);
+            let right = HashMap::from([("a", f64::NAN)]);
+            let identity = SortOptions::default().term_normalizer(|s: &str| s.to_string());
+            assert!(!identity.normalized_eq(&left, &right));
+
+            let trim = SortOptions::default().term_normalizer(|s: &str| s.trim().to_string());
+            assert!(!trim.normalized_eq(&left, &right));
+            assert!(trim.normalized_eq(&vec![" a "], &vec!["a"]));
+        }
+    }
+
+    #[test]
+    fn returns_sorted_debug_strings() {
+        for _ in 0..TEST_RERUNS_FOR_DETERMINISM {
+            let item = response(1, 10, 100);
+            assert_eq!(sorted_debug_string(&item), sorted_debug(&item));
+            assert_eq!(sorted_with(&item, &SortOptions::new()), sorted_debug(&item));
+        }
+    }
+
+    #[test]
+    fn pretty_prints_multiline_and_json_strings() {
+        for _ in 0..TEST_RERUNS_FOR_DETERMINISM {
+            let map = HashMap::from([
+                ("b", "first line\nsecond line\n"),
+                ("a", r#"{"z": [1, 2], "y": {"x": null}}"#),
+                ("c", "single line"),
+            ]);
+            let options = SortOptions::default().pretty_print_strings(true);
+            let expected = indoc!(
+                r##"{
+                    "a": r#"
+                        {
+                            "y": {
+                                "x": null,
+                            },
+                            "z": [
+                                1,
+                                2,
+                            ],
+                        }
+                    "#,
+                    "b": r#"
+                        first line
+                        second line
+                    "#,
+                    "c": "single line",
+                }"##
+            );
+            assert_eq!(
+                format!("{:#?}", SortedDebug::with_options(&map, options)),
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn flattens_nested_values_into_sorted_paths() {
+        for _ in 0..TEST_RERUNS_FOR_DETERMINISM {
+            let flattened = sorted_flattened(&response(1, 10, 100))
+                .into_iter()
+                .map(|(path, value)| format!("{} = {}", path, value))
+                .collect::<Vec<_>>();
+            assert_eq!(
+                flattened,
+                vec![
+                    "body.generated_at = 100",
+                    "body.items[0].count = 10",
+                    "body.items[0].id = 1",
+                    "headers.content-type = \"json\"",
+                    "headers.date = \"today\"",
+                    "request_id = 1",
+                ]
+            );
+            assert_eq!(
+                sorted_flattened(&Some(vec![1])),
+                vec![("Some[0][0]".to_string(), "1".to_string())]
+            );
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "with flattened paths")]
+    fn flat_fails_when_not_equal() {
+        assert_eq_sorted_flat!(response(1, 10, 100), response(1, 11, 100));
+    }
+
+    #[test]
+    fn skips_blocklisted_fields() {
+        #[derive(PartialEq)]
+        struct Events(Vec<(&'static str, u32)>);
+
+        impl fmt::Debug for Events {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.debug_map()
+                    .entries(self.0.iter().map(|(k, v)| (k, v)))
+                    .finish()
+            }
+        }
+
+        #[derive(Debug, PartialEq)]
+        #[allow(unused)]
+        struct Timeline {
+            events: Events,
+            tags: Events,
+            ids: Vec<u32>,
+        }
+
+        for _ in 0..TEST_RERUNS_FOR_DETERMINISM {
+            let timeline = Timeline {
+                events: Events(vec![("started", 2), ("queued", 1)]),
+                tags: Events(vec![("b", 2), ("a", 1)]),
+                ids: vec![3, 1, 2],
+            };
+            let options = SortOptions::default()
+                .sort_lists(true)
+                .skip_fields(["events", "ids"]);
+            assert_eq!(
+                format!("{:#?}", SortedDebug::with_options(&timeline, options)),
+                indoc!(
+                    r#"
+                    Timeline {
+                        events: {
+                            "started": 2,
+                            "queued": 1,
+                        },
+                        tags: {
+                            "a": 1,
+                            "b": 2,
+                        },
+                        ids: [
+                            3,
+                            1,
+                            2,
+                        ],
+                    }"#
+                )
+            );
+            assert_eq_sorted!(
+                Timeline {
+                    events: Events(vec![("queued", 1)]),
+                    tags: Events(vec![("b", 2), ("a", 1)]),
+                    ids: vec![],
+                },
+                Timeline {
+                    events: Events(vec![("queued", 1)]),
+                    tags: Events(vec![("b", 2), ("a", 1)]),
+                    ids: vec![],
+                },
+                skip_fields = ["events"]
+            );
+        }
+    }
+
+    #[test]
+    fn none_equals_absent() {
+        mod v1 {
+            #[derive(Debug)]
+            #[allow(unused)]
+            pub struct User {
+                pub name: &'static str,
+                pub nickname: Option<&'static str>,
+            }
+        }
+
+        mod v2 {
+            #[derive(Debug)]
+            #[allow(unused)]
+            pub struct User {
+                pub name: &'static str,
+            }
+        }
+
+        for _ in 0..TEST_RERUNS_FOR_DETERMINISM {
+            let options = SortOptions::default().none_equals_absent(true);
+            let with_none = HashMap::from([("a", Some(1)), ("b", None)]);
+            let without = HashMap::from([("a", Some(1))]);
+            assert!(options.normalized_eq(&with_none, &without));
+            assert!(options.normalized_eq(&without, &with_none));
+            assert!(!SortOptions::default().normalized_eq(&with_none, &without));
+            let with_some = HashMap::from([("a", Some(1)), ("b", Some(2))]);
+            assert!(!options.normalized_eq(&with_some, &without));
+
+            let user = v2::User { name: "a" };
+            let no_nickname = v1::User {
+                name: "a",
+                nickname: None,
+            };
+            assert!(options.normalized_eq(&no_nickname, &user));
+            assert!(options.normalized_eq(&user, &no_nickname));
+            let nickname = v1::User {
+                name: "a",
+                nickname: Some("b"),
+            };
+            assert_eq!(
+                sorted_differences_with_options(&nickname, &user, &options),
+                vec![Difference {
+                    path: "nickname".to_string(),
+                    left: Some("Some(\"b\")".to_string()),
+                    right: None,
+                }]
+            );
+        }
+    }
+
+    #[test]
+    fn sorts_descending() {
+        for _ in 0..TEST_RERUNS_FOR_DETERMINISM {
+            let item = HashMap::from([(1, "alpha"), (10, "stable"), (2, "beta")]);
+            let options = SortOptions::default().order(SortOrder::Descending);
+            assert_eq!(
+                format!("{:#?}", SortedDebug::with_options(item, options)),
+                indoc!(
+                    r#"
+                    {
+                        10: "stable",
+                        2: "beta",
+                        1: "alpha",
+                    }"#
+                )
             );
-            assert_eq!(sorted_debug(item), expected);
         }
     }
 
     #[test]
-    fn sorts_hashmaps_with_non_exhaustive_object_values() {
+    fn on_keys_ignores_unlisted_keys() {
+        for _ in 0..TEST_RERUNS_FOR_DETERMINISM {
+            let left = HashMap::from([("id", 1), ("name", 2), ("version", 3)]);
+            let right = HashMap::from([("version", 4), ("name", 2), ("id", 1)]);
+            assert_ne!(left, right);
+            assert_eq_sorted_on_keys!(left, right, keys = ["id", "name"]);
+            assert_eq_sorted_on_keys!(
+                response(1, 10, 100),
+                response(1, 11, 200),
+                keys = ["request_id", "headers"],
+                "with a {}",
+                "message"
+            );
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "assertion failed: `(left == right)` at [\"id\", \"version\"]")]
+    fn on_keys_fails_when_listed_key_differs() {
+        let left = HashMap::from([("id", 1), ("version", 3)]);
+        let right = HashMap::from([("id", 1), ("version", 4)]);
+        assert_eq_sorted_on_keys!(left, right, keys = ["id", "version"]);
+    }
+
+    #[test]
+    #[should_panic(expected = "assertion failed: path not found in either value: [\"nmae\"]")]
+    fn on_keys_fails_when_key_is_missing_on_both_sides() {
+        let left = HashMap::from([("id", 1), ("name", 2)]);
+        let right = HashMap::from([("id", 1), ("name", 3)]);
+        assert_eq_sorted_on_keys!(left, right, keys = ["id", "nmae"]);
+    }
+
+    #[test]
+    fn try_sorted_debug_returns_parse_errors() {
+        struct Unbalanced;
+
+        impl fmt::Debug for Unbalanced {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str("Foo { a: [1, 2 }")
+            }
+        }
+
+        let error = try_sorted_debug(&Unbalanced, true).unwrap_err();
+        assert!(error
+            .to_string()
+            .starts_with("failed to parse Debug output: "));
+        for _ in 0..TEST_RERUNS_FOR_DETERMINISM {
+            assert_eq!(
+                try_sorted_debug(&response(1, 10, 100), true).unwrap(),
+                sorted_debug(response(1, 10, 100))
+            );
+        }
+    }
+
+    #[test]
+    fn redacts_fields() {
+        #[derive(Debug, PartialEq)]
         #[allow(unused)]
-        struct Foo {
-            value: f32,
+        struct Session {
+            id: u64,
+            user: User,
         }
 
-        impl fmt::Debug for Foo {
+        #[derive(Debug, PartialEq)]
+        #[allow(unused)]
+        struct User {
+            name: &'static str,
+            token: &'static str,
+        }
+
+        let session = |id, token| Session {
+            id,
+            user: User { name: "a", token },
+        };
+        for _ in 0..TEST_RERUNS_FOR_DETERMINISM {
+            let options = SortOptions::default().redact(["token", "id"]);
+            assert_eq!(
+                format!(
+                    "{:#?}",
+                    SortedDebug::with_options(session(1, "secret"), options)
+                ),
+                indoc!(
+                    r#"
+                    Session {
+                        id: <redacted>,
+                        user: User {
+                            name: "a",
+                            token: <redacted>,
+                        },
+                    }"#
+                )
+            );
+            assert_eq_sorted!(
+                session(1, "secret"),
+                session(2, "other"),
+                redact = ["token", "id"]
+            );
+        }
+    }
+
+    #[test]
+    fn lcs_compares_elements_by_sorted_debug() {
+        for _ in 0..TEST_RERUNS_FOR_DETERMINISM {
+            assert_eq_sorted_lcs!(
+                vec![response(1, 10, 100), response(2, 20, 200)],
+                [response(1, 10, 100), response(2, 20, 200)]
+            );
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "(4 elements in left, 4 elements in right)")]
+    fn lcs_fails_on_edits_in_the_middle() {
+        assert_eq_sorted_lcs!(vec![1, 2, 3, 4], vec![1, 3, 5, 4]);
+    }
+
+    #[test]
+    fn comparison_resets_colors() {
+        let comparison = |options: &SortOptions| {
+            LabeledComparison::new(&1, &2, "left", "right")
+                .with_options(options)
+                .to_string()
+        };
+        let plain = comparison(&SortOptions::default());
+        let reset = comparison(&SortOptions::default().leading_color_reset(true));
+        if render::colors_enabled() {
+            assert!(plain.ends_with(COLOR_RESET));
+            assert!(!plain.starts_with(COLOR_RESET));
+            assert!(reset.starts_with(COLOR_RESET));
+            assert!(reset.ends_with(COLOR_RESET));
+        } else {
+            assert_eq!(plain, reset);
+        }
+    }
+
+    #[test]
+    fn sorts_maps_inside_enum_variants() {
+        #[derive(Debug)]
+        #[allow(unused)]
+        enum Event {
+            Updated {
+                id: u32,
+                fields: HashMap<&'static str, u32>,
+            },
+            Tagged(HashMap<&'static str, u32>),
+        }
+
+        for _ in 0..TEST_RERUNS_FOR_DETERMINISM {
+            let item = vec![
+                Event::Updated {
+                    id: 1,
+                    fields: HashMap::from([("b", 2), ("a", 1)]),
+                },
+                Event::Tagged(HashMap::from([("d", 4), ("c", 3)])),
+            ];
+            assert_eq!(
+                sorted_debug(item),
+                indoc!(
+                    r#"
+                    [
+                        Updated {
+                            id: 1,
+                            fields: {
+                                "a": 1,
+                                "b": 2,
+                            },
+                        },
+                        Tagged(
+                            {
+                                "c": 3,
+                                "d": 4,
+                            },
+                        ),
+                    ]"#
+                )
+            );
+        }
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "assertion failed: `(left == right)` within epsilon 0.001: a message"
+    )]
+    fn approx_fails_outside_epsilon() {
+        let left = HashMap::from([("total", 1.0), ("rate", 0.5)]);
+        let right = HashMap::from([("rate", 0.5), ("total", 1.01)]);
+        assert_eq_sorted_approx!(left, right, epsilon = 0.001, "a {}", "message");
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "with fuzzy keys, 2 keys couldn't be paired\n  left: admin\n  right: root\n"
+    )]
+    fn fuzzy_keys_report_unpaired_keys() {
+        for _ in 0..TEST_RERUNS_FOR_DETERMINISM {
+            let left = HashMap::from([("job-7f3a9c", 1), ("job-1b2c3d", 2)]);
+            let right = HashMap::from([("job-1b2c3e", 2), ("job-7f3a9d", 1)]);
+            assert_eq_sorted_fuzzy_keys!(left, right, threshold = 0.8);
+        }
+        let left = HashMap::from([("job-7f3a9c", 1), ("admin", 2)]);
+        let right = HashMap::from([("job-7f3a9d", 1), ("root", 2)]);
+        assert_eq_sorted_fuzzy_keys!(left, right, threshold = 0.8);
+    }
+
+    #[test]
+    fn orders_entries_with_equal_keys_by_value() {
+        #[derive(PartialEq, Eq, Hash)]
+        struct Key(u32);
+
+        impl fmt::Debug for Key {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str("key")
+            }
+        }
+
+        for _ in 0..TEST_RERUNS_FOR_DETERMINISM {
+            let item = HashMap::from([(Key(1), "b"), (Key(2), "a"), (Key(3), "c")]);
+            assert_eq!(
+                sorted_debug(item),
+                indoc!(
+                    r#"
+                    {
+                        key: "a",
+                        key: "b",
+                        key: "c",
+                    }"#
+                )
+            );
+        }
+    }
+
+    #[test]
+    fn only_compares_listed_fields() {
+        for _ in 0..TEST_RERUNS_FOR_DETERMINISM {
+            let options = SortOptions::default().only_fields(["request_id", "headers"]);
+            assert_eq!(
+                format!(
+                    "{:#?}",
+                    SortedDebug::with_options(response(1, 10, 100), options)
+                ),
+                indoc!(
+                    r#"
+                    Response {
+                        request_id: 1,
+                        headers: {
+                            "content-type": "json",
+                            "date": "today",
+                        },
+                    }"#
+                )
+            );
+            assert_eq_sorted!(
+                response(1, 10, 100),
+                response(1, 20, 200),
+                only_fields = ["request_id", "headers"]
+            );
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "request_id")]
+    fn only_fields_fails_when_listed_field_differs() {
+        assert_eq_sorted!(
+            response(1, 10, 100),
+            response(2, 20, 200),
+            only_fields = ["request_id"]
+        );
+    }
+
+    #[test]
+    fn diff_sorted_renders_the_comparison() {
+        for _ in 0..TEST_RERUNS_FOR_DETERMINISM {
+            assert_eq!(
+                diff_sorted(&response(1, 10, 100), &response(1, 10, 100)),
+                None
+            );
+            assert_eq!(
+                diff_sorted(&response(1, 10, 100), &response(1, 11, 100)),
+                Some(
+                    Comparison::new(
+                        &SortedDebug::new(response(1, 10, 100)),
+                        &SortedDebug::new(response(1, 11, 100))
+                    )
+                    .to_string()
+                )
+            );
+        }
+    }
+
+    #[test]
+    fn comparison_without_color() {
+        let comparison = LabeledComparison::new(
+            &SortedDebug::new(HashMap::from([("a", 1), ("b", 2)])),
+            &SortedDebug::new(HashMap::from([("b", 3), ("a", 1)])),
+            "left",
+            "right",
+        )
+        .with_options(&SortOptions::default().color(false))
+        .to_string();
+
+        assert!(!comparison.contains('\u{1b}'), "{:?}", comparison);
+        assert_eq!(
+            comparison,
+            "Diff < left / right > :\n {\n     \"a\": 1,\n<    \"b\": 2,\n>    \"b\": 3,\n }\n"
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Diff < left / right > :")]
+    fn assertion_without_color() {
+        assert_eq_sorted!(1, 2, color = false);
+    }
+
+    #[test]
+    fn groups_byte_list_keys_before_other_lists() {
+        for _ in 0..TEST_RERUNS_FOR_DETERMINISM {
+            let item: HashMap<Vec<u16>, u8> =
+                HashMap::from([(vec![256], 1), (vec![10], 2), (vec![9], 3), (vec![300], 4)]);
+            assert_eq!(
+                sorted_debug(item),
+                indoc!(
+                    "{
+                        [
+                            9,
+                        ]: 3,
+                        [
+                            10,
+                        ]: 2,
+                        [
+                            256,
+                        ]: 1,
+                        [
+                            300,
+                        ]: 4,
+                    }"
+                )
+            );
+        }
+    }
+
+    #[test]
+    fn sorts_byte_string_keys_bytewise() {
+        struct ByteString(&'static [u8]);
+
+        impl fmt::Debug for ByteString {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str("b\"")?;
+                for &byte in self.0 {
+                    match byte {
+                        b' '..=b'~' => write!(f, "{}", byte as char)?,
+                        _ => write!(f, "\\x{:02x}", byte)?,
+                    }
+                }
+                f.write_str("\"")
+            }
+        }
+
+        struct ByteStrings(Vec<(ByteString, u32)>);
+
+        impl fmt::Debug for ByteStrings {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.debug_map()
+                    .entries(self.0.iter().map(|(k, v)| (k, v)))
+                    .finish()
+            }
+        }
+
+        for _ in 0..TEST_RERUNS_FOR_DETERMINISM {
+            let item: HashMap<Vec<u8>, u32> = HashMap::from([
+                (vec![200], 1),
+                (vec![9, 1], 2),
+                (vec![104, 101], 3),
+                (vec![97], 4),
+            ]);
+            assert_eq!(
+                sorted_debug(item),
+                indoc!(
+                    "{
+                        [
+                            9,
+                            1,
+                        ]: 2,
+                        [
+                            97,
+                        ]: 4,
+                        [
+                            104,
+                            101,
+                        ]: 3,
+                        [
+                            200,
+                        ]: 1,
+                    }"
+                )
+            );
+
+            let item = ByteStrings(vec![
+                (ByteString(b"\xc8"), 1),
+                (ByteString(b"\x01"), 2),
+                (ByteString(b"he"), 3),
+                (ByteString(b"a"), 4),
+            ]);
+            assert_eq!(
+                sorted_debug(item),
+                indoc!(
+                    r#"
+                    {
+                        b"\x01": 2,
+                        b"a": 4,
+                        b"he": 3,
+                        b"\xc8": 1,
+                    }"#
+                )
+            );
+        }
+    }
+
+    #[test]
+    fn sorts_keys_with_custom_comparator() {
+        for _ in 0..TEST_RERUNS_FOR_DETERMINISM {
+            let item = HashMap::from([("a/3", 1), ("z/1", 2), ("m/2", 3)]);
+            // Sort by the part after the slash instead of the whole key.
+            let options = SortOptions::default()
+                .key_comparator(|a, b| a.rsplit('/').next().cmp(&b.rsplit('/').next()));
+            assert_eq!(
+                format!("{:#?}", SortedDebug::with_options(item, options)),
+                indoc!(
+                    r#"
+                    {
+                        "z/1": 2,
+                        "m/2": 3,
+                        "a/3": 1,
+                    }"#
+                )
+            );
+        }
+    }
+
+    #[test]
+    fn sorts_keys_case_insensitively() {
+        for _ in 0..TEST_RERUNS_FOR_DETERMINISM {
+            let item = HashMap::from([
+                ("Timeout", 1),
+                ("retries", 2),
+                ("Backoff", 3),
+                ("backoff", 4),
+            ]);
+            assert_eq!(
+                format!(
+                    "{:#?}",
+                    SortedDebug::with_options(
+                        item.clone(),
+                        SortOptions::default().case_insensitive_keys(true)
+                    )
+                ),
+                indoc!(
+                    r#"
+                    {
+                        "Backoff": 3,
+                        "backoff": 4,
+                        "retries": 2,
+                        "Timeout": 1,
+                    }"#
+                )
+            );
+            assert_eq_sorted!(item.clone(), item, case_insensitive_keys = true);
+        }
+    }
+
+    #[test]
+    fn strings_with_debug_syntax_round_trip() {
+        #[derive(Debug, Clone, PartialEq)]
+        struct Config {
+            template: &'static str,
+            open: char,
+            separator: char,
+        }
+
+        for _ in 0..TEST_RERUNS_FOR_DETERMINISM {
+            // A BTreeMap is already sorted, so the sorted output matches the plain Debug output.
+            let item = std::collections::BTreeMap::from([
+                ("{ , }", "a: {x},"),
+                ("}{", "[1, 2] (3)"),
+                ("a, b: c", "\"quoted\", \\ {\n}"),
+            ]);
+            assert_eq!(
+                format!("{:#?}", SortedDebug::new(&item)),
+                format!("{:#?}", item)
+            );
+
+            let config = Config {
+                template: "} , {",
+                open: '{',
+                separator: ',',
+            };
+            assert_eq!(
+                format!("{:#?}", SortedDebug::new(&config)),
+                format!("{:#?}", config)
+            );
+
+            let map = HashMap::from([("{ , }", config.clone()), ("a: {x},", config)]);
+            assert_eq_sorted!(map.clone(), map);
+        }
+    }
+
+    #[test]
+    fn sorts_maps_with_infinities_and_nan() {
+        for _ in 0..TEST_RERUNS_FOR_DETERMINISM {
+            let item = HashMap::from([
+                ("nan", f64::NAN),
+                ("negative", f64::NEG_INFINITY),
+                ("positive", f64::INFINITY),
+            ]);
+            assert_eq!(
+                sorted_debug(&item),
+                indoc!(
+                    r#"
+                    {
+                        "nan": NaN,
+                        "negative": -inf,
+                        "positive": inf,
+                    }"#
+                )
+            );
+
+            let item =
+                HashMap::from([("negative", f64::NEG_INFINITY), ("positive", f64::INFINITY)]);
+            assert_eq_sorted!(item.clone(), item);
+        }
+    }
+
+    #[test]
+    fn sorts_only_maps_at_given_paths() {
+        // A map that's printed in its given order.
+        #[derive(PartialEq)]
+        struct Ordered(Vec<(&'static str, i32)>);
+
+        impl fmt::Debug for Ordered {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.debug_map().entries(self.0.iter().cloned()).finish()
+            }
+        }
+
+        #[derive(Debug, PartialEq)]
+        struct Config {
+            limits: Ordered,
+            labels: Ordered,
+        }
+
+        #[derive(Debug, PartialEq)]
+        struct Service {
+            config: Config,
+        }
+
+        let service = Service {
+            config: Config {
+                limits: Ordered(vec![("memory", 512), ("cpu", 2)]),
+                labels: Ordered(vec![("team", 1), ("env", 2)]),
+            },
+        };
+        assert_eq!(
+            format!(
+                "{:#?}",
+                SortedDebug::with_options(
+                    &service,
+                    SortOptions::default().sort_only(["config.limits"])
+                )
+            ),
+            indoc!(
+                r#"
+                Service {
+                    config: Config {
+                        limits: {
+                            "cpu": 2,
+                            "memory": 512,
+                        },
+                        labels: {
+                            "team": 1,
+                            "env": 2,
+                        },
+                    },
+                }"#
+            )
+        );
+
+        for _ in 0..TEST_RERUNS_FOR_DETERMINISM {
+            let item = HashMap::from([("a", HashMap::from([(1, 1), (2, 2)]))]);
+            assert_eq_sorted!(item.clone(), item, sort_only = ["a"]);
+        }
+    }
+
+    #[test]
+    fn formats_the_inner_value_once() {
+        struct Counted<'a>(&'a std::cell::Cell<usize>);
+
+        impl fmt::Debug for Counted<'_> {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                self.0.set(self.0.get() + 1);
+                f.debug_map().entry(&"b", &2).entry(&"a", &1).finish()
+            }
+        }
+
+        let count = std::cell::Cell::new(0);
+        let sorted = SortedDebug::new(Counted(&count));
+        let pretty = format!("{:#?}", sorted);
+        assert_eq!(format!("{:?}", sorted), pretty);
+        assert_eq!(pretty, "{\n    \"a\": 1,\n    \"b\": 2,\n}");
+        assert_eq!(count.get(), 1);
+    }
+
+    #[test]
+    fn message_closure_is_only_called_on_failure() {
+        let calls = std::cell::Cell::new(0);
+        let context = || {
+            calls.set(calls.get() + 1);
+            "context"
+        };
+        for _ in 0..TEST_RERUNS_FOR_DETERMINISM {
+            let item = HashMap::from([(1, 1), (2, 2)]);
+            assert_eq_sorted!(item.clone(), item.clone(), || context());
+            assert_eq_sorted!(item.clone() => "actual", item => "expected", move || context());
+        }
+        assert_eq!(calls.get(), 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "assertion failed: `(left == right)`: context: 3")]
+    fn message_closure_is_appended_on_failure() {
+        let context = 3;
+        assert_eq_sorted!(1, 2, || format!("context: {}", context));
+    }
+
+    #[test]
+    fn sorts_maps_keyed_by_structs_containing_maps() {
+        #[derive(Debug, PartialEq)]
+        struct Labels {
+            labels: HashMap<&'static str, i32>,
+        }
+
+        // A map whose keys aren't `Hash`, printed in the given order.
+        struct Entries(Vec<(Labels, i32)>);
+
+        impl fmt::Debug for Entries {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.debug_map()
+                    .entries(self.0.iter().map(|(k, v)| (k, v)))
+                    .finish()
+            }
+        }
+
+        for _ in 0..TEST_RERUNS_FOR_DETERMINISM {
+            let labels = |entries: &[(&'static str, i32)]| Labels {
+                labels: entries.iter().copied().collect(),
+            };
+            let item = Entries(vec![
+                (labels(&[("b", 2), ("a", 2)]), 2),
+                (labels(&[("b", 1), ("a", 2)]), 1),
+            ]);
+            assert_eq!(
+                format!("{:#?}", SortedDebug::new(&item)),
+                indoc!(
+                    r#"
+                    {
+                        Labels {
+                            labels: {
+                                "a": 2,
+                                "b": 1,
+                            },
+                        }: 1,
+                        Labels {
+                            labels: {
+                                "a": 2,
+                                "b": 2,
+                            },
+                        }: 2,
+                    }"#
+                )
+            );
+        }
+    }
+
+    #[test]
+    fn compares_by_sorted_debug_without_partial_eq() {
+        for _ in 0..TEST_RERUNS_FOR_DETERMINISM {
+            // `Mutex` doesn't implement `PartialEq`.
+            let snapshot = || Mutex::new(HashMap::from([("a", 1), ("b", 2), ("c", 3)]));
+            assert_eq_sorted_debug!(snapshot(), snapshot());
+            assert_eq_sorted_debug!(snapshot(), snapshot(), "with a message");
+            assert_eq_sorted_debug!(vec![2, 1], vec![1, 2], sort_lists = true);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "assertion failed: `(left == right)` by sorted Debug: context")]
+    fn sorted_debug_assertion_fails_for_different_output() {
+        assert_eq_sorted_debug!(
+            HashMap::from([("a", 1)]),
+            HashMap::from([("a", 2)]),
+            "context"
+        );
+    }
+
+    #[test]
+    fn normalizes_numbers() {
+        // Prints the number as it's given.
+        struct Number(&'static str);
+
+        impl fmt::Debug for Number {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str(self.0)
+            }
+        }
+
+        let options = SortOptions::default().normalize_numbers(true);
+        for _ in 0..TEST_RERUNS_FOR_DETERMINISM {
+            let left = HashMap::from([
+                ("exponent", Number("1e3")),
+                ("negative_zero", Number("-0.0")),
+                ("trailing_zeros", Number("1.50")),
+                ("integer", Number("-0")),
+            ]);
+            let right = HashMap::from([
+                ("exponent", Number("1000.0")),
+                ("negative_zero", Number("0.0")),
+                ("trailing_zeros", Number("1.5")),
+                ("integer", Number("-0")),
+            ]);
+            assert_eq!(
+                format!("{:#?}", SortedDebug::with_options(&left, options.clone())),
+                indoc!(
+                    r#"
+                    {
+                        "exponent": 1000.0,
+                        "integer": -0,
+                        "negative_zero": 0.0,
+                        "trailing_zeros": 1.5,
+                    }"#
+                )
+            );
+            assert!(options.normalized_eq(&left, &right));
+            assert!(!SortOptions::default().normalized_eq(&left, &right));
+        }
+    }
+
+    #[test]
+    fn leaves_unrecognized_debug_output_unsorted() {
+        // Escapes a character that the derived Debug output wouldn't, which the parser
+        // accepts but can't reproduce.
+        struct Escaped;
+
+        impl fmt::Debug for Escaped {
             fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-                f.debug_struct("Foo")
-                    .field("value", &self.value)
-                    .finish_non_exhaustive()
+                f.write_str(r#"{"b": "\u{41}", "a": 1}"#)
             }
         }
 
         for _ in 0..TEST_RERUNS_FOR_DETERMINISM {
-            let item = {
-                let mut map = HashMap::new();
-                map.insert(1, Foo { value: 10.1 });
-                map.insert(32, Foo { value: 2.0 });
-                map.insert(2, Foo { value: -1.5 });
-                map
-            };
-
-            let expected = indoc!(
-                "{
-                    1: Foo {
-                        value: 10.1,
-                        ..
-                    },
-                    2: Foo {
-                        value: -1.5,
-                        ..
-                    },
-                    32: Foo {
-                        value: 2.0,
-                        ..
-                    },
-                }"
+            assert_eq!(
+                format!("{:#?}", SortedDebug::new(Escaped)),
+                indoc!(
+                    r#"
+                    {"b": "\u{41}", "a": 1}
+                    // note: output not sorted (unrecognized Debug format)"#
+                )
             );
-            assert_eq!(sorted_debug(item), expected);
         }
     }
 
     #[test]
-    fn sorts_object_with_hashmap() {
-        #[derive(Debug)]
-        #[allow(unused)]
-        struct Foo {
-            bar: Bar,
+    fn keeps_the_format_of_debug_output_that_cant_be_sorted() {
+        struct Opaque;
+
+        impl fmt::Debug for Opaque {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str("<opaque @ 0x1>")
+            }
         }
 
         #[derive(Debug)]
         #[allow(unused)]
-        struct Bar {
-            count: HashMap<&'static str, Zed>,
-            value: usize,
+        struct Wrapper {
+            inner: Opaque,
         }
 
-        #[derive(Debug)]
-        struct Zed;
-
         for _ in 0..TEST_RERUNS_FOR_DETERMINISM {
-            let item = Foo {
-                bar: Bar {
-                    count: {
-                        let mut map = HashMap::new();
-                        map.insert("hello world", Zed);
-                        map.insert("lorem ipsum", Zed);
-                        map
-                    },
-                    value: 200,
-                },
-            };
-
-            let expected = indoc!(
-                "Foo {
-                    bar: Bar {
-                        count: {
-                            \"hello world\": Zed,
-                            \"lorem ipsum\": Zed,
-                        },
-                        value: 200,
-                    },
-                }"
+            let wrapper = SortedDebug::new(Wrapper { inner: Opaque });
+            assert_eq!(
+                format!("{:?}", wrapper),
+                "Wrapper { inner: <opaque @ 0x1> }"
+            );
+            assert_eq!(
+                format!("{:#?}", wrapper),
+                indoc!(
+                    "Wrapper {
+                        inner: <opaque @ 0x1>,
+                    }"
+                )
             );
-            assert_eq!(sorted_debug(item), expected);
         }
     }
 
     #[test]
-    fn hashmap_with_object_values() {
-        #[derive(Debug)]
-        #[allow(unused)]
-        struct Foo {
-            value: f32,
-            bar: Vec<Bar>,
-        }
+    fn checks_each_value_for_an_unrecognized_debug_output() {
+        struct MaybeEscaped(bool);
 
-        #[derive(Debug)]
-        #[allow(unused)]
-        struct Bar {
-            elo: i32,
+        impl fmt::Debug for MaybeEscaped {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                if self.0 {
+                    f.write_str(r#"{"b": "\u{41}", "a": 1}"#)
+                } else {
+                    f.write_str(r#"{"b": "A", "a": 1}"#)
+                }
+            }
         }
 
         for _ in 0..TEST_RERUNS_FOR_DETERMINISM {
-            let item = {
-                let mut map = HashMap::new();
-                map.insert(
-                    "foo",
-                    Foo {
-                        value: 12.2,
-                        bar: vec![Bar { elo: 200 }, Bar { elo: -12 }],
-                    },
-                );
-                map.insert(
-                    "foo2",
-                    Foo {
-                        value: -0.2,
-                        bar: vec![],
-                    },
-                );
-                map
-            };
+            assert!(format!("{:?}", SortedDebug::new(MaybeEscaped(true)))
+                .ends_with("// note: output not sorted (unrecognized Debug format)"));
+            // A value of the same type that is recognized is still sorted.
+            assert_eq!(
+                format!("{:#?}", SortedDebug::new(MaybeEscaped(false))),
+                indoc!(
+                    r#"
+                    {
+                        "a": 1,
+                        "b": "A",
+                    }"#
+                )
+            );
+        }
+    }
 
-            let expected = indoc!(
-                "{
-                    \"foo\": Foo {
-                        value: 12.2,
-                        bar: [
-                            Bar {
-                                elo: 200,
-                            },
-                            Bar {
-                                elo: -12,
-                            },
-                        ],
-                    },
-                    \"foo2\": Foo {
-                        value: -0.2,
-                        bar: [],
-                    },
-                }"
+    #[test]
+    fn sorts_hashsets_by_default() {
+        for _ in 0..TEST_RERUNS_FOR_DETERMINISM {
+            let set = HashSet::from([20, 1, 2]);
+            assert_eq!(sorted_debug(&set), "{\n    1,\n    2,\n    20,\n}");
+            assert_eq!(
+                format!("{:?}", SortedDebug::new(&set)),
+                format!("{:?}", SortedDebug::new(HashSet::from([2, 20, 1])))
+            );
+            assert_eq!(
+                sorted_debug(HashSet::from([10, 9])),
+                "{\n    9,\n    10,\n}"
             );
-            assert_eq!(sorted_debug(item), expected);
         }
     }
 
     #[test]
-    fn hashmap_with_object_keys() {
+    fn sorts_struct_keys_by_their_sort_key() {
         #[derive(Debug, PartialEq, Eq, Hash)]
-        struct Foo {
-            value: i32,
-            bar: Vec<Bar>,
+        struct Key {
+            name: &'static str,
+            id: u32,
         }
 
-        #[derive(Debug, PartialEq, Eq, Hash)]
-        struct Bar {
-            elo: i32,
+        impl SortedDebugKey for Key {
+            const NAME: &'static str = "Key";
+            const SORT_KEY: &'static str = "id";
         }
 
+        let options = SortOptions::default().sort_key::<Key>();
         for _ in 0..TEST_RERUNS_FOR_DETERMINISM {
-            let item = {
-                let mut map = HashMap::new();
-                map.insert(
-                    Foo {
-                        value: 12,
-                        bar: vec![Bar { elo: 200 }, Bar { elo: -12 }],
-                    },
-                    "foo",
-                );
-                map.insert(
-                    Foo {
-                        value: -2,
-                        bar: vec![],
-                    },
-                    "foo2",
-                );
-                map
+            let item = HashMap::from([
+                (Key { name: "a", id: 10 }, 1),
+                (Key { name: "b", id: 9 }, 2),
+                (Key { name: "c", id: 1 }, 3),
+            ]);
+            let names = |options: SortOptions| {
+                let sorted = format!("{:?}", SortedDebug::with_options(&item, options));
+                sorted
+                    .lines()
+                    .filter_map(|line| line.trim().strip_prefix("name: "))
+                    .collect::<Vec<_>>()
+                    .join(" ")
             };
+            assert_eq!(names(options.clone()), r#""c", "b", "a","#);
+            assert_eq!(names(SortOptions::default()), r#""a", "b", "c","#);
+        }
+    }
 
-            let expected = indoc!(
-                "{
-                    Foo {
-                        value: -2,
-                        bar: [],
-                    }: \"foo2\",
-                    Foo {
-                        value: 12,
-                        bar: [
-                            Bar {
-                                elo: 200,
-                            },
-                            Bar {
-                                elo: -12,
-                            },
+    #[test]
+    #[should_panic(expected = "assertion failed: `(left == right)`\n\
+                    2 keys only in left, 1 key only in right, 2 keys with differing values\n")]
+    fn sorted_assertion_summarizes_key_differences() {
+        let left = HashMap::from([
+            ("removed", HashMap::from([("a", 1)])),
+            ("nested", HashMap::from([("changed", 1), ("removed", 2)])),
+            ("changed", HashMap::from([("a", 1)])),
+        ]);
+        let right = HashMap::from([
+            ("added", HashMap::from([("a", 1)])),
+            ("nested", HashMap::from([("changed", 2)])),
+            ("changed", HashMap::from([("a", 2)])),
+        ]);
+        assert_eq_sorted!(left, right);
+    }
+
+    #[test]
+    fn unordered_lists_are_equal() {
+        for _ in 0..TEST_RERUNS_FOR_DETERMINISM {
+            let left = HashMap::from([("a", vec![10, 9, 2])]);
+            let right = HashMap::from([("a", vec![2, 9, 10])]);
+            assert_eq_sorted!(left.clone(), right.clone(), unordered_lists);
+            assert_eq_sorted!(left.clone(), right, unordered_lists = true);
+            assert_eq!(
+                format!(
+                    "{:?}",
+                    SortedDebug::with_options(&left, SortOptions::default().unordered_lists(true))
+                ),
+                indoc!(
+                    r#"
+                    {
+                        "a": [
+                            2,
+                            9,
+                            10,
                         ],
-                    }: \"foo\",
-                }"
+                    }"#
+                )
             );
-            assert_eq!(sorted_debug(item), expected);
         }
     }
 
     #[test]
-    fn hashmap_with_chrono_naivedate() {
+    fn lists_are_ordered_by_default() {
         for _ in 0..TEST_RERUNS_FOR_DETERMINISM {
-            let item = {
-                let mut map = HashMap::new();
-                map.insert(chrono::NaiveDate::from_ymd(2000, 2, 14), "foo");
-                map.insert(chrono::NaiveDate::from_ymd(2001, 4, 2), "foo");
-                map
-            };
+            let left = HashMap::from([("a", vec![3, 1, 2])]);
+            let right = HashMap::from([("a", vec![1, 2, 3])]);
+            assert!(!SortOptions::default().normalized_eq(&left, &right));
+            assert!(SortOptions::default()
+                .unordered_lists(true)
+                .normalized_eq(&left, &right));
+        }
+    }
 
-            dbg!(&item);
+    #[test]
+    fn parses_sorted_value_trees() {
+        for _ in 0..TEST_RERUNS_FOR_DETERMINISM {
+            let item = response(1, 10, 100);
+            let value = parse_sorted(&item).unwrap();
+            assert_eq!(format!("{:#?}", value), sorted_debug(&item));
+        }
 
-            let expected = indoc!(
-                "{
-                    2000-02-14: \"foo\",
-                    2001-04-02: \"foo\",
-                }"
-            );
-            assert_eq!(sorted_debug(item), expected);
+        struct Invalid;
+
+        impl fmt::Debug for Invalid {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str("not { debug")
+            }
         }
+
+        assert!(parse_sorted(&Invalid).is_err());
     }
 
     #[test]
-    #[should_panic(
-        expected = "Failed to parse Debug output for sorting (please use `assert_eq!` instead and/or file an issue for your use-case)!
-Error: Failed to consume all of string!
-Value:
-Object
+    fn preserves_order_of_given_fields() {
+        use std::cmp::Reverse;
 
-Rest:
-\" {\\\"a\\\": Number(0)}\""
-    )]
-    fn panics_when_expression_cant_be_sorted() {
-        assert_eq_sorted!(serde_json::json!({"a":0}), "2");
-    }
+        #[derive(Debug, PartialEq)]
+        struct Index {
+            ordered: std::collections::BTreeMap<Reverse<&'static str>, HashSet<i32>>,
+            unordered: HashMap<&'static str, i32>,
+        }
 
-    #[derive(PartialEq)]
-    #[allow(unused)]
-    struct FooWithOptionalField {
-        value: Option<f32>,
+        let options = SortOptions::default().preserve_order_fields(["ordered"]);
+        for _ in 0..TEST_RERUNS_FOR_DETERMINISM {
+            let index = Index {
+                ordered: std::collections::BTreeMap::from([
+                    (Reverse("a"), HashSet::from([2, 1])),
+                    (Reverse("b"), HashSet::new()),
+                ]),
+                unordered: HashMap::from([("b", 2), ("a", 1)]),
+            };
+            assert_eq!(
+                format!("{:#?}", SortedDebug::with_options(&index, options.clone())),
+                indoc!(
+                    r#"
+                    Index {
+                        ordered: {
+                            Reverse(
+                                "b",
+                            ): {},
+                            Reverse(
+                                "a",
+                            ): {
+                                1,
+                                2,
+                            },
+                        },
+                        unordered: {
+                            "a": 1,
+                            "b": 2,
+                        },
+                    }"#
+                )
+            );
+        }
     }
 
-    impl fmt::Debug for FooWithOptionalField {
-        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-            let mut foo = f.debug_struct("FooWithOptionalField");
-            if let Some(value) = &self.value {
-                foo.field("value", value);
-                foo.finish()
-            } else {
-                foo.finish_non_exhaustive()
-            }
+    #[test]
+    fn preserves_order_of_optional_fields() {
+        #[derive(Debug, PartialEq)]
+        struct History {
+            ordered: Option<std::collections::BTreeMap<std::cmp::Reverse<i32>, Vec<i32>>>,
+        }
+
+        let options = SortOptions::default()
+            .preserve_order_fields(["ordered"])
+            .hide_empty_containers(true);
+        for _ in 0..TEST_RERUNS_FOR_DETERMINISM {
+            let history = History {
+                ordered: Some(std::collections::BTreeMap::from([
+                    (std::cmp::Reverse(1), vec![1]),
+                    (std::cmp::Reverse(2), vec![]),
+                    (std::cmp::Reverse(3), vec![3]),
+                ])),
+            };
+            assert_eq!(
+                format!(
+                    "{:#?}",
+                    SortedDebug::with_options(&history, options.clone())
+                ),
+                indoc!(
+                    r#"
+                    History {
+                        ordered: Some(
+                            {
+                                Reverse(
+                                    3,
+                                ): [
+                                    3,
+                                ],
+                                Reverse(
+                                    1,
+                                ): [
+                                    1,
+                                ],
+                            },
+                        ),
+                    }"#
+                )
+            );
         }
     }
 
-    /// Test that the value field is displayed as missing (colored red) for optional fields
-    /// on non-exhaustive Debug implementations.
     #[test]
-    #[should_panic(expected = "FooWithOptionalField {\n\u{1b}[31m<    value: 2.0,\u{1b}[0m\n }")]
-    fn ui_looks_right_for_non_exhaustive_optional_fields() {
-        assert_eq_sorted!(
-            FooWithOptionalField { value: Some(2.0) },
-            FooWithOptionalField { value: None }
-        );
+    fn normalizes_keys_before_sorting_and_matching() {
+        let trimmed = SortOptions::default().key_normalizer(|key: &str| key.trim().to_string());
+        let lowercased = SortOptions::default().key_normalizer(str::to_lowercase);
+        for _ in 0..TEST_RERUNS_FOR_DETERMINISM {
+            let padded = HashMap::from([(" b", 1), ("a ", 2)]);
+            let plain = HashMap::from([("b", 1), ("a", 2)]);
+            assert_eq!(
+                format!("{:#?}", SortedDebug::with_options(&padded, trimmed.clone())),
+                indoc!(
+                    r#"
+                    {
+                        "a ": 2,
+                        " b": 1,
+                    }"#
+                )
+            );
+            assert!(trimmed.normalized_eq(&padded, &plain));
+            assert!(!lowercased.normalized_eq(&padded, &plain));
+
+            let capitalized = HashMap::from([("Name", 1), ("age", 2)]);
+            let lowercase = HashMap::from([("name", 1), ("age", 2)]);
+            assert!(lowercased.normalized_eq(&capitalized, &lowercase));
+            assert!(!trimmed.normalized_eq(&capitalized, &lowercase));
+            assert_eq_sorted!(capitalized, lowercase, key_normalizer = str::to_lowercase);
+        }
     }
 
     #[test]
-    fn outputs_nice_output_for_non_exhaustive_objects_with_optional_fields() {
-        assert_eq!(
-            sorted_debug(FooWithOptionalField { value: Some(10.0) }),
-            indoc!(
-                "FooWithOptionalField {
-                    value: 10.0,
-                }"
-            )
-        );
+    fn keeps_keys_that_normalize_the_same() {
+        let lowercased = SortOptions::default().key_normalizer(str::to_lowercase);
+        for _ in 0..TEST_RERUNS_FOR_DETERMINISM {
+            let both = HashMap::from([("Name", 1), ("name", 1)]);
+            let one = HashMap::from([("name", 1)]);
+            assert!(!lowercased.normalized_eq(&both, &one));
+            assert!(!lowercased.normalized_eq(&one, &both));
+            assert!(lowercased.normalized_eq(&both, &both));
+        }
+    }
 
-        assert_eq!(
-            sorted_debug(FooWithOptionalField { value: None }),
-            indoc!(
-                "FooWithOptionalField {
-                }"
-            )
+    #[test]
+    #[should_panic(expected = "assertion failed: `(left == right)`")]
+    fn fails_for_extra_keys_that_normalize_the_same() {
+        assert_eq_sorted!(
+            HashMap::from([("Name", 1), ("name", 1)]),
+            HashMap::from([("name", 1)]),
+            key_normalizer = str::to_lowercase
         );
     }
 }
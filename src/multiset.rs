@@ -0,0 +1,106 @@
+//! Comparison of collections as multisets, where elements are identified by their sorted
+//! Debug output.
+use crate::SortedDebug;
+use pretty_assertions::StrComparison;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+/// Counts how many times each element occurs, keyed by the sorted [`Debug`](fmt::Debug)
+/// representation of the element (see [`SortedDebug`]).
+///
+/// Two collections with equal counts contain the same elements the same number of times,
+/// regardless of the order of the elements (and the order of any maps inside of them).
+///
+/// ```rust
+/// use pretty_assertions_sorted::sorted_counts;
+///
+/// assert_eq!(sorted_counts(vec!["a", "b", "a"]), sorted_counts(vec!["b", "a", "a"]));
+/// ```
+pub fn sorted_counts<I>(values: I) -> BTreeMap<String, usize>
+where
+    I: IntoIterator,
+    I::Item: fmt::Debug,
+{
+    let mut counts = BTreeMap::new();
+    for value in values {
+        *counts
+            .entry(format!("{:#?}", SortedDebug::new(value)))
+            .or_insert(0) += 1;
+    }
+    counts
+}
+
+/// A comparison of two multisets produced by [`sorted_counts`].
+///
+/// Only the elements that are over or under-represented are displayed, prefixed with the
+/// number of times they occur on each side.
+pub struct MultisetComparison<'a> {
+    left: &'a BTreeMap<String, usize>,
+    right: &'a BTreeMap<String, usize>,
+}
+
+impl<'a> MultisetComparison<'a> {
+    pub fn new(left: &'a BTreeMap<String, usize>, right: &'a BTreeMap<String, usize>) -> Self {
+        Self { left, right }
+    }
+}
+
+impl<'a> fmt::Display for MultisetComparison<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let elements = self
+            .left
+            .keys()
+            .chain(self.right.keys())
+            .collect::<BTreeSet<_>>();
+
+        let (mut left, mut right) = (String::new(), String::new());
+        for element in elements {
+            let left_count = self.left.get(element).copied().unwrap_or(0);
+            let right_count = self.right.get(element).copied().unwrap_or(0);
+            if left_count != right_count {
+                left.push_str(&format!("{} × {}\n", left_count, element));
+                right.push_str(&format!("{} × {}\n", right_count, element));
+            }
+        }
+
+        fmt::Display::fmt(&StrComparison::new(&left, &right), f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn counts_elements_by_sorted_debug() {
+        for _ in 0..100 {
+            let counts = sorted_counts(vec![
+                HashMap::from([(1, "a"), (2, "b")]),
+                HashMap::from([(3, "c")]),
+                HashMap::from([(2, "b"), (1, "a")]),
+            ]);
+            assert_eq!(
+                counts.into_iter().collect::<Vec<_>>(),
+                vec![
+                    ("{\n    1: \"a\",\n    2: \"b\",\n}".to_string(), 2),
+                    ("{\n    3: \"c\",\n}".to_string(), 1),
+                ]
+            );
+        }
+    }
+
+    #[test]
+    fn comparison_only_shows_differing_counts() {
+        let left = sorted_counts(vec!["a", "b", "b", "c"]);
+        let right = sorted_counts(vec!["a", "b", "d"]);
+        assert_eq!(
+            MultisetComparison::new(&left, &right).to_string(),
+            StrComparison::new(
+                "2 × \"b\"\n1 × \"c\"\n0 × \"d\"\n",
+                "1 × \"b\"\n0 × \"c\"\n1 × \"d\"\n"
+            )
+            .to_string()
+        );
+    }
+}
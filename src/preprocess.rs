@@ -0,0 +1,279 @@
+//! Normalization of Debug output before it's handed to the parser.
+//!
+//! Some custom Debug implementations use syntax that the parser doesn't understand. Where
+//! there is an equivalent syntax that the parser does understand, the Debug output is
+//! rewritten to it. Nothing inside of string literals is rewritten, except for escape
+//! sequences that the parser doesn't support.
+use darrentsung_debug_parser::{OrNonExhaustive, Term, Value};
+use std::borrow::Cow;
+use std::fmt::Write;
+
+/// Stands in for the `::` of type paths, which the parser doesn't accept in identifiers.
+/// It's made of identifier characters, so that the path parses as a single identifier.
+const PATH_SEPARATOR_PLACEHOLDER: &str = "__path_separator__";
+
+/// Marks a string literal that was a byte string literal (eg. `b"abc"`), which the parser
+/// doesn't accept.
+const BYTE_STRING_PLACEHOLDER: &str = "__byte_string__";
+
+/// Rewrites the Debug output into a form the parser understands:
+/// * `key => value` map entries are rewritten to `key: value`.
+/// * `// comments` are removed up to the end of the line. Only `//` at the start of the
+///   output or after whitespace starts a comment, so that eg. unquoted URLs are left alone.
+/// * Trailing commas before a closing bracket are removed, which is common in output that
+///   is split over multiple lines (eg. to make room for comments).
+/// * The `::` in type paths (eg. `foo::Bar { .. }`) is replaced by a placeholder, use
+///   [`restore`] on the parsed value to turn it back.
+/// * Byte string literals (eg. `b"abc"`) are turned into string literals marked by a
+///   placeholder, [`restore`] turns them back.
+/// * The `\0`, `\xNN` and `\'` escapes in string literals are rewritten to their `\u{..}`
+///   (or unescaped) form. The parsed strings are rendered with the original escapes again.
+pub(crate) fn normalize(input: &str) -> Cow<'_, str> {
+    if !input.contains("=>")
+        && !input.contains("//")
+        && !input.contains("::")
+        && !input.contains("\\0")
+        && !input.contains("\\x")
+        && !input.contains("\\'")
+        && !input.contains("b\"")
+        && !has_trailing_comma(input)
+    {
+        return Cow::Borrowed(input);
+    }
+
+    let mut out = String::with_capacity(input.len());
+    let mut chars = input.chars().peekable();
+    let mut in_string = false;
+    while let Some(c) = chars.next() {
+        if in_string {
+            match c {
+                '\\' => match chars.next() {
+                    Some('0') => out.push_str("\\u{0}"),
+                    Some('\'') => out.push('\''),
+                    Some('x') => {
+                        out.push_str("\\u{");
+                        out.extend(chars.next_if(char::is_ascii_hexdigit));
+                        out.extend(chars.next_if(char::is_ascii_hexdigit));
+                        out.push('}');
+                    }
+                    escaped => {
+                        out.push(c);
+                        out.extend(escaped);
+                    }
+                },
+                '"' => {
+                    in_string = false;
+                    out.push(c);
+                }
+                _ => out.push(c),
+            }
+            continue;
+        }
+
+        match c {
+            '"' => {
+                in_string = true;
+                out.push(c);
+            }
+            'b' if chars.peek() == Some(&'"')
+                && !out.ends_with(|c: char| c.is_alphanumeric() || c == '_') =>
+            {
+                chars.next();
+                in_string = true;
+                out.push('"');
+                out.push_str(BYTE_STRING_PLACEHOLDER);
+            }
+            '=' if chars.peek() == Some(&'>') => {
+                chars.next();
+                out.push(':');
+            }
+            '}' | ']' | ')' => {
+                // The whitespace after the comma is removed as well, since unquoted terms
+                // would otherwise include it (eg. `1\n}` in pretty Debug output).
+                let trimmed_len = out.trim_end().len();
+                if out[..trimmed_len].ends_with(',') {
+                    out.truncate(trimmed_len - 1);
+                }
+                out.push(c);
+            }
+            ':' if chars.peek() == Some(&':')
+                && out.ends_with(|c: char| c.is_alphanumeric() || c == '_') =>
+            {
+                let mut lookahead = chars.clone();
+                lookahead.next();
+                if lookahead
+                    .peek()
+                    .is_some_and(|&c| c.is_alphabetic() || c == '_')
+                {
+                    chars.next();
+                    out.push_str(PATH_SEPARATOR_PLACEHOLDER);
+                } else {
+                    out.push(c);
+                }
+            }
+            '/' if chars.peek() == Some(&'/')
+                && (out.is_empty() || out.ends_with(char::is_whitespace)) =>
+            {
+                while chars.next_if(|&c| c != '\n').is_some() {}
+            }
+            _ => out.push(c),
+        }
+    }
+    Cow::Owned(out)
+}
+
+/// Turns the placeholders inserted by [`normalize`] back into the original syntax.
+pub(crate) fn restore(value: &mut Value) {
+    if let Value::Term(Term::String(s)) = value {
+        if let Some(bytes) = s.strip_prefix(BYTE_STRING_PLACEHOLDER) {
+            *value = Value::Term(Term::UnquotedRawString(byte_string_literal(bytes)));
+            return;
+        }
+    }
+
+    let restore_path = |name: &mut String| {
+        if name.contains(PATH_SEPARATOR_PLACEHOLDER) {
+            *name = name.replace(PATH_SEPARATOR_PLACEHOLDER, "::");
+        }
+    };
+
+    match value {
+        Value::Struct(s) => {
+            restore_path(&mut s.name);
+            for v in &mut s.values {
+                if let OrNonExhaustive::Value(ident_value) = v {
+                    restore(&mut ident_value.value);
+                }
+            }
+        }
+        Value::Set(set) => set.values.iter_mut().for_each(restore),
+        Value::Map(map) => {
+            for key_value in &mut map.values {
+                restore(&mut key_value.key);
+                restore(&mut key_value.value);
+            }
+        }
+        Value::List(list) => list.values.iter_mut().for_each(restore),
+        Value::Tuple(tuple) => {
+            if let Some(name) = &mut tuple.name {
+                restore_path(name);
+            }
+            tuple.values.iter_mut().for_each(restore);
+        }
+        Value::Term(Term::Ident(name)) | Value::Term(Term::UnquotedRawString(name)) => {
+            restore_path(name)
+        }
+        Value::Term(Term::String(_)) => (),
+    }
+}
+
+/// Formats the (unescaped) contents of a byte string as a byte string literal, escaping
+/// the bytes that aren't printable ASCII characters.
+fn byte_string_literal(bytes: &str) -> String {
+    let mut literal = String::from("b\"");
+    for c in bytes.chars() {
+        match c {
+            '"' => literal.push_str("\\\""),
+            '\\' => literal.push_str("\\\\"),
+            '\n' => literal.push_str("\\n"),
+            '\r' => literal.push_str("\\r"),
+            '\t' => literal.push_str("\\t"),
+            '\0' => literal.push_str("\\0"),
+            ' '..='~' => literal.push(c),
+            _ => {
+                let _ = write!(literal, "\\x{:02x}", u32::from(c));
+            }
+        }
+    }
+    literal.push('"');
+    literal
+}
+
+/// Returns true if a comma is followed by a closing bracket (possibly separated by
+/// whitespace). This may have false positives inside of string literals.
+fn has_trailing_comma(input: &str) -> bool {
+    input
+        .match_indices(',')
+        .any(|(index, _)| input[index + 1..].trim_start().starts_with(['}', ']', ')']))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rewrites_fat_arrows_to_colons() {
+        assert_eq!(normalize("{1 => 2, 3 => 4}"), "{1 : 2, 3 : 4}");
+    }
+
+    #[test]
+    fn leaves_string_literals_untouched() {
+        assert_eq!(
+            normalize(r#"{"a => \"b => c\"" => "=>"}"#),
+            r#"{"a => \"b => c\"" : "=>"}"#
+        );
+        assert!(matches!(normalize("{1: 2}"), Cow::Borrowed(_)));
+    }
+
+    #[test]
+    fn replaces_path_separators() {
+        let normalized = normalize(r#"foo::Bar { a: ::std::X, b: "a::b", c: 1::2 }"#);
+        assert_eq!(
+            normalized,
+            format!(
+                r#"foo{0}Bar {{ a: ::std{0}X, b: "a::b", c: 1::2 }}"#,
+                PATH_SEPARATOR_PLACEHOLDER
+            )
+        );
+    }
+
+    #[test]
+    fn rewrites_unsupported_escapes() {
+        assert_eq!(
+            normalize(r#"{"a\0b": "\x07 \'c\' \\0 \n", 'x': "\u{1b}"}"#),
+            r#"{"a\u{0}b": "\u{07} 'c' \\0 \n", 'x': "\u{1b}"}"#
+        );
+    }
+
+    #[test]
+    fn removes_trailing_commas() {
+        assert_eq!(normalize("[1, [2,\n], (3, ),\n]"), "[1, [2], (3)]");
+        assert_eq!(normalize(r#"["a, ]", "b",]"#), r#"["a, ]", "b"]"#);
+    }
+
+    #[test]
+    fn removes_comments() {
+        assert_eq!(
+            normalize("{1: 2, // one\n3: \"// not a comment\"} // end"),
+            "{1: 2, \n3: \"// not a comment\"} "
+        );
+        assert_eq!(normalize("{1: 2, // one\n}"), "{1: 2}");
+        assert_eq!(
+            normalize("Link { url: https://example.com }"),
+            "Link { url: https://example.com }"
+        );
+    }
+
+    #[test]
+    fn restores_byte_strings() {
+        let input = r#"{b"a\"b\xff": 1, sub"x": 2}"#;
+        let normalized = normalize(input);
+        assert_eq!(
+            normalized,
+            format!(
+                r#"{{"{0}a\"b\u{{ff}}": 1, sub"x": 2}}"#,
+                BYTE_STRING_PLACEHOLDER
+            )
+        );
+
+        let mut value = Value::Term(Term::String(format!(
+            "{}a\"b\u{ff}\n",
+            BYTE_STRING_PLACEHOLDER
+        )));
+        restore(&mut value);
+        assert_eq!(
+            value,
+            Value::Term(Term::UnquotedRawString(r#"b"a\"b\xff\n""#.to_string()))
+        );
+    }
+}
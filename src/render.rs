@@ -0,0 +1,656 @@
+//! Rendering of a parsed [`Value`] back into pretty (`{:#?}`) Debug output.
+//!
+//! The [`Value`] type has its own Debug implementation, but it can't represent everything
+//! that the derived Debug implementations output, eg. the unit tuple `()` is rendered as
+//! an empty string. Rendering the output ourselves also lets us tweak it to diff better.
+use crate::sort::{dominant_key_kind, KeyKind};
+use crate::SortOptions;
+use darrentsung_debug_parser::{OrNonExhaustive, Term, Value};
+use std::borrow::Cow;
+use std::fmt::Write;
+
+const INDENT: &str = "    ";
+
+// Vim's default fold markers, see `:help fold-marker`.
+const FOLD_START: &str = " // {{{";
+const FOLD_END: &str = " // }}}";
+
+// Only the foreground color is reset after a highlighted key, so that other styling (eg.
+// the bold background of an inline diff) isn't clobbered.
+const KEY_HIGHLIGHT_START: &str = "\u{1b}[36m";
+const KEY_HIGHLIGHT_END: &str = "\u{1b}[39m";
+
+/// Returns false if the user has opted out of colored output, see <https://no-color.org>.
+pub(crate) fn colors_enabled() -> bool {
+    std::env::var_os("NO_COLOR")
+        .filter(|v| !v.is_empty())
+        .is_none()
+}
+
+/// Removes the key highlighting from the text, see [`SortOptions::highlight_keys`].
+pub(crate) fn strip_key_highlights(text: &str) -> String {
+    text.replace(KEY_HIGHLIGHT_START, "")
+        .replace(KEY_HIGHLIGHT_END, "")
+}
+
+/// Removes all ANSI escape sequences (eg. colors) from the text. The Debug output of
+/// strings escapes the escape character, so these only come from styling the output.
+pub(crate) fn strip_ansi(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c == '\u{1b}' {
+            chars.by_ref().find(|c| c.is_ascii_alphabetic());
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+/// Renders values in the same format as `format!("{:#?}", value)` would, with some tweaks
+/// configured by the [`SortOptions`].
+#[derive(Clone, Copy)]
+pub(crate) struct Renderer {
+    highlight_keys: bool,
+    annotate_unsortable: bool,
+    visible_control_chars: bool,
+    fold_markers: bool,
+    narrow_width: Option<usize>,
+    pretty_print_strings: bool,
+    max_map_entries: Option<usize>,
+}
+
+impl Renderer {
+    pub(crate) fn new(options: &SortOptions) -> Self {
+        Self {
+            highlight_keys: options.highlight_keys && colors_enabled(),
+            annotate_unsortable: options.annotate_unsortable,
+            visible_control_chars: options.visible_control_chars,
+            fold_markers: options.fold_markers,
+            narrow_width: narrow_width(options),
+            pretty_print_strings: options.pretty_print_strings,
+            max_map_entries: None,
+        }
+    }
+
+    /// Only render the first `max_map_entries` entries of every map, followed by a
+    /// `… (+N more)` line if any entries were left out.
+    pub(crate) fn max_map_entries(mut self, max_map_entries: usize) -> Self {
+        self.max_map_entries = Some(max_map_entries);
+        self
+    }
+
+    pub(crate) fn render(&self, value: &Value) -> String {
+        let mut out = String::new();
+        self.write_value(&mut out, value, 0);
+        out
+    }
+
+    fn write_value(&self, out: &mut String, value: &Value, depth: usize) {
+        match value {
+            Value::Struct(s) => {
+                out.push_str(&s.name);
+                if s.values.is_empty() {
+                    return;
+                }
+
+                out.push_str(" {\n");
+                let has_fields = s
+                    .values
+                    .iter()
+                    .any(|v| matches!(v, OrNonExhaustive::Value(_)));
+                for v in &s.values {
+                    match v {
+                        OrNonExhaustive::Value(ident_value) => {
+                            write_indent(out, depth + 1);
+                            self.write_key(out, |out| out.push_str(&ident_value.ident));
+                            out.push_str(": ");
+                            let folded = self.write_child(out, &ident_value.value, depth + 1);
+                            out.push(',');
+                            if folded {
+                                out.push_str(FOLD_END);
+                            }
+                            out.push('\n');
+                        }
+                        // Non-exhaustive objects without any fields are rendered as empty
+                        // brackets separated by newlines, eg. "Foo {\n}" instead of "Foo {
+                        // .. }". This is more desirable because it diffs better against
+                        // some multi-line output of "Foo { value: 10.0 }" (imagine the
+                        // newlines please).
+                        OrNonExhaustive::NonExhaustive => {
+                            if has_fields {
+                                write_indent(out, depth + 1);
+                                out.push_str("..\n");
+                            }
+                        }
+                    }
+                }
+                write_indent(out, depth);
+                out.push('}');
+            }
+            Value::Set(set) => {
+                self.write_sequence(out, &set.values, depth, "{", "}");
+            }
+            Value::Map(map) => {
+                if map.values.is_empty() {
+                    out.push_str("{}");
+                    return;
+                }
+
+                out.push_str("{\n");
+                let dominant_kind = dominant_key_kind(&map.values);
+                let shown = self.max_map_entries.unwrap_or(usize::MAX);
+                for key_value in map.values.iter().take(shown) {
+                    write_indent(out, depth + 1);
+                    // Keys that contain keys of their own (eg. structs) are highlighted as
+                    // a whole.
+                    let key_renderer = Renderer {
+                        highlight_keys: false,
+                        ..*self
+                    };
+                    self.write_key(out, |out| {
+                        key_renderer.write_value(out, &key_value.key, depth + 1)
+                    });
+                    out.push_str(": ");
+                    let folded = self.write_child(out, &key_value.value, depth + 1);
+                    out.push(',');
+                    if folded {
+                        out.push_str(FOLD_END);
+                    }
+                    if self.annotate_unsortable
+                        && Some(KeyKind::of(&key_value.key)) != dominant_kind
+                    {
+                        out.push_str(" // unsortable key, ordered by its Debug output");
+                    }
+                    out.push('\n');
+                }
+                if map.values.len() > shown {
+                    write_indent(out, depth + 1);
+                    let _ = writeln!(out, "… (+{} more)", map.values.len() - shown);
+                }
+                write_indent(out, depth);
+                out.push('}');
+            }
+            Value::List(list) => {
+                self.write_sequence(out, &list.values, depth, "[", "]");
+            }
+            Value::Tuple(tuple) => match &tuple.name {
+                Some(name) => {
+                    out.push_str(name);
+                    if !tuple.values.is_empty() {
+                        self.write_sequence(out, &tuple.values, depth, "(", ")");
+                    }
+                }
+                None => self.write_sequence(out, &tuple.values, depth, "(", ")"),
+            },
+            Value::Term(Term::String(s))
+                if self.pretty_print_strings && self.write_block(out, s, depth) => {}
+            Value::Term(Term::String(s)) if self.visible_control_chars => {
+                let _ = write!(out, "{:?}", control_pictures(s));
+            }
+            Value::Term(term) => {
+                let _ = write!(out, "{:?}", term);
+            }
+        }
+    }
+
+    /// Writes a value nested in a container, wrapped in fold markers if it's a multi-line
+    /// map or struct at the top level (see [`SortOptions::fold_markers`]). Returns true if
+    /// the closing marker still has to be written after the separator.
+    fn write_child(&self, out: &mut String, value: &Value, depth: usize) -> bool {
+        let foldable = match value {
+            Value::Struct(s) => !s.values.is_empty(),
+            Value::Map(map) => !map.values.is_empty(),
+            _ => false,
+        };
+        if !(self.fold_markers && foldable && depth == 1) {
+            let start = out.len();
+            self.write_value(out, value, depth);
+            self.wrap_term(out, value, start, depth);
+            return false;
+        }
+
+        let start = out.len();
+        self.write_value(out, value, depth);
+        if let Some(newline) = out[start..].find('\n') {
+            out.insert_str(start + newline, FOLD_START);
+        }
+        true
+    }
+
+    /// Moves a term that was just written (starting at `start`) after its field name or map
+    /// key onto a line of its own, if the line got wider than the narrow terminal (see
+    /// [`SortOptions::min_terminal_width`]).
+    fn wrap_term(&self, out: &mut String, value: &Value, start: usize, depth: usize) {
+        let width = match (self.narrow_width, value) {
+            (Some(width), Value::Term(_)) => width,
+            _ => return,
+        };
+        // Terms in sequences are already on a line of their own.
+        if !out[..start].ends_with(": ") {
+            return;
+        }
+        let line_start = out[..start].rfind('\n').map_or(0, |newline| newline + 1);
+        if out[line_start..].chars().count() <= width {
+            return;
+        }
+
+        let term = out.split_off(start);
+        out.pop();
+        out.push('\n');
+        write_indent(out, depth + 1);
+        out.push_str(&term);
+    }
+
+    /// Writes a string that spans multiple lines (or contains JSON, which is sorted and
+    /// reindented) as an indented raw string block, see [`SortOptions::pretty_print_strings`].
+    /// Returns false if the string fits on a single line, without writing anything.
+    fn write_block(&self, out: &mut String, s: &str, depth: usize) -> bool {
+        let trimmed = s.trim();
+        let looks_like_json = (trimmed.starts_with('{') && trimmed.ends_with('}'))
+            || (trimmed.starts_with('[') && trimmed.ends_with(']'));
+        let text = if looks_like_json {
+            crate::parse_sorted_str(s, &SortOptions::default())
+                .map_or_else(|| s.to_string(), |value| self.render(&value))
+        } else {
+            s.to_string()
+        };
+        if !text.contains('\n') {
+            return false;
+        }
+
+        out.push_str("r#\"\n");
+        for line in text.lines() {
+            if !line.is_empty() {
+                write_indent(out, depth + 1);
+            }
+            out.push_str(line);
+            out.push('\n');
+        }
+        write_indent(out, depth);
+        out.push_str("\"#");
+        true
+    }
+
+    fn write_key(&self, out: &mut String, write: impl FnOnce(&mut String)) {
+        if self.highlight_keys {
+            out.push_str(KEY_HIGHLIGHT_START);
+            write(out);
+            out.push_str(KEY_HIGHLIGHT_END);
+        } else {
+            write(out);
+        }
+    }
+
+    fn write_sequence(
+        &self,
+        out: &mut String,
+        values: &[Value],
+        depth: usize,
+        open: &str,
+        close: &str,
+    ) {
+        out.push_str(open);
+        if !values.is_empty() {
+            out.push('\n');
+            for v in values {
+                write_indent(out, depth + 1);
+                let folded = self.write_child(out, v, depth + 1);
+                out.push(',');
+                if folded {
+                    out.push_str(FOLD_END);
+                }
+                out.push('\n');
+            }
+            write_indent(out, depth);
+        }
+        out.push_str(close);
+    }
+}
+
+/// The width of the terminal if it's narrower than [`SortOptions::min_terminal_width`],
+/// either as configured or from the `COLUMNS` environment variable.
+fn narrow_width(options: &SortOptions) -> Option<usize> {
+    let min_width = options.min_terminal_width?;
+    let width = options.terminal_width.or_else(|| {
+        std::env::var("COLUMNS")
+            .ok()
+            .and_then(|columns| columns.parse().ok())
+    })?;
+    Some(width).filter(|width| *width < min_width)
+}
+
+/// Replaces the control characters in the text with their visible symbols from the Unicode
+/// "Control Pictures" block (eg. `␀` for `\0`), except for newlines, tabs and carriage
+/// returns, whose escapes are readable enough.
+fn control_pictures(text: &str) -> String {
+    text.chars()
+        .map(|c| match c {
+            '\n' | '\t' | '\r' => c,
+            '\u{0}'..='\u{1f}' => char::from_u32(0x2400 + c as u32).unwrap_or(c),
+            '\u{7f}' => '\u{2421}',
+            _ => c,
+        })
+        .collect()
+}
+
+/// Render the value on a single line, in the same format as `format!("{:?}", value)` would.
+pub(crate) fn render_inline(value: &Value) -> String {
+    let mut out = String::new();
+    write_inline(&mut out, value);
+    out
+}
+
+fn write_inline(out: &mut String, value: &Value) {
+    match value {
+        Value::Struct(s) => {
+            out.push_str(&s.name);
+            if s.values.is_empty() {
+                return;
+            }
+
+            out.push_str(" { ");
+            for (index, v) in s.values.iter().enumerate() {
+                if index > 0 {
+                    out.push_str(", ");
+                }
+                match v {
+                    OrNonExhaustive::Value(ident_value) => {
+                        out.push_str(&ident_value.ident);
+                        out.push_str(": ");
+                        write_inline(out, &ident_value.value);
+                    }
+                    OrNonExhaustive::NonExhaustive => out.push_str(".."),
+                }
+            }
+            out.push_str(" }");
+        }
+        Value::Set(set) => write_inline_sequence(out, &set.values, "{", "}"),
+        Value::Map(map) => {
+            out.push('{');
+            for (index, key_value) in map.values.iter().enumerate() {
+                if index > 0 {
+                    out.push_str(", ");
+                }
+                write_inline(out, &key_value.key);
+                out.push_str(": ");
+                write_inline(out, &key_value.value);
+            }
+            out.push('}');
+        }
+        Value::List(list) => write_inline_sequence(out, &list.values, "[", "]"),
+        Value::Tuple(tuple) => match &tuple.name {
+            Some(name) => {
+                out.push_str(name);
+                if !tuple.values.is_empty() {
+                    write_inline_sequence(out, &tuple.values, "(", ")");
+                }
+            }
+            None => {
+                write_inline_sequence(out, &tuple.values, "(", ")");
+                // Single element tuples need a trailing comma to be distinguished from
+                // parenthesized values.
+                if tuple.values.len() == 1 {
+                    out.insert(out.len() - 1, ',');
+                }
+            }
+        },
+        Value::Term(term) => {
+            let _ = write!(out, "{:?}", term);
+        }
+    }
+}
+
+fn write_inline_sequence(out: &mut String, values: &[Value], open: &str, close: &str) {
+    out.push_str(open);
+    for (index, v) in values.iter().enumerate() {
+        if index > 0 {
+            out.push_str(", ");
+        }
+        write_inline(out, v);
+    }
+    out.push_str(close);
+}
+
+/// Truncates the lines of a diff to at most `max_bytes`, see [`SortOptions::max_diff_bytes`].
+///
+/// The changed lines (the ones that aren't prefixed by a space) are the most relevant and
+/// are kept first, followed by the unchanged lines in order of their distance to the closest
+/// changed line. Each run of omitted lines is replaced by a single `…` line.
+pub(crate) fn truncate_diff(diff: &str, max_bytes: usize) -> Cow<'_, str> {
+    if diff.len() <= max_bytes {
+        return Cow::Borrowed(diff);
+    }
+
+    let lines = diff.split_inclusive('\n').collect::<Vec<_>>();
+    let distances = distances_to_changes(&lines);
+
+    let mut by_relevance = (0..lines.len()).collect::<Vec<_>>();
+    by_relevance.sort_by_key(|&index| (distances[index], index));
+    let mut keep = vec![false; lines.len()];
+    let mut kept_bytes = 0;
+    for index in by_relevance {
+        if kept_bytes + lines[index].len() > max_bytes {
+            break;
+        }
+        kept_bytes += lines[index].len();
+        keep[index] = true;
+    }
+
+    let mut out = String::new();
+    let mut omitting = false;
+    for (line, keep) in lines.iter().zip(keep) {
+        if keep {
+            out.push_str(line);
+            omitting = false;
+        } else if !omitting {
+            out.push_str("…\n");
+            omitting = true;
+        }
+    }
+    // The last run of omitted lines is covered by the summary.
+    if omitting {
+        out.truncate(out.len() - "…\n".len());
+    }
+    if !out.is_empty() && !out.ends_with('\n') {
+        out.push('\n');
+    }
+    let _ = writeln!(
+        out,
+        "… (diff truncated, {} bytes omitted)",
+        diff.len() - kept_bytes
+    );
+    Cow::Owned(out)
+}
+
+/// Collapses the unchanged lines of a diff so that it's at most `max_lines` long, see
+/// [`SortOptions::max_lines`].
+///
+/// The changed lines (the ones that aren't prefixed by a space) are always kept, even if
+/// they don't fit. The remaining lines go to the unchanged lines closest to a changed line,
+/// and each run of omitted lines is replaced by a `... N unchanged lines ...` line.
+pub(crate) fn elide_unchanged_lines(diff: &str, max_lines: usize) -> Cow<'_, str> {
+    let lines = diff.split_inclusive('\n').collect::<Vec<_>>();
+    if lines.len() <= max_lines {
+        return Cow::Borrowed(diff);
+    }
+
+    let distances = distances_to_changes(&lines);
+    let mut by_relevance = (0..lines.len()).collect::<Vec<_>>();
+    by_relevance.sort_by_key(|&index| (distances[index], index));
+    let mut keep = vec![false; lines.len()];
+    for (kept, index) in by_relevance.into_iter().enumerate() {
+        if distances[index] > 0 && kept >= max_lines {
+            break;
+        }
+        keep[index] = true;
+    }
+
+    let mut out = String::new();
+    let mut omitted = 0;
+    for (line, keep) in lines.iter().zip(keep) {
+        if keep {
+            write_elision(&mut out, omitted);
+            out.push_str(line);
+            omitted = 0;
+        } else {
+            omitted += 1;
+        }
+    }
+    if !out.is_empty() && !out.ends_with('\n') {
+        out.push('\n');
+    }
+    write_elision(&mut out, omitted);
+    Cow::Owned(out)
+}
+
+fn write_elision(out: &mut String, omitted: usize) {
+    match omitted {
+        0 => (),
+        1 => out.push_str("... 1 unchanged line ...\n"),
+        _ => {
+            let _ = writeln!(out, "... {} unchanged lines ...", omitted);
+        }
+    }
+}
+
+/// The distance of each line of a diff to the closest changed line (one that isn't prefixed
+/// by a space), `usize::MAX` if there are no changed lines.
+fn distances_to_changes(lines: &[&str]) -> Vec<usize> {
+    let is_changed = |line: &str| !line.starts_with(' ');
+
+    let mut distances = vec![usize::MAX; lines.len()];
+    let mut closest_changed = None;
+    for (index, line) in lines.iter().enumerate() {
+        if is_changed(line) {
+            closest_changed = Some(index);
+        }
+        if let Some(changed) = closest_changed {
+            distances[index] = index - changed;
+        }
+    }
+    closest_changed = None;
+    for (index, line) in lines.iter().enumerate().rev() {
+        if is_changed(line) {
+            closest_changed = Some(index);
+        }
+        if let Some(changed) = closest_changed {
+            distances[index] = distances[index].min(changed - index);
+        }
+    }
+    distances
+}
+
+fn write_indent(out: &mut String, depth: usize) {
+    for _ in 0..depth {
+        out.push_str(INDENT);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use darrentsung_debug_parser::parse;
+    use std::collections::HashMap;
+    use std::fmt;
+
+    #[track_caller]
+    fn assert_renders_like_debug<T: fmt::Debug>(item: &T) {
+        let value = parse(&format!("{:?}", item)).expect("can parse");
+        let renderer = Renderer::new(&SortOptions::default());
+        assert_eq!(renderer.render(&value), format!("{:#?}", item));
+        assert_eq!(render_inline(&value), format!("{:?}", item));
+    }
+
+    #[test]
+    fn renders_like_derived_debug() {
+        #[derive(Debug)]
+        #[allow(unused)]
+        struct Foo {
+            value: f32,
+            bar: Vec<Bar>,
+            map: HashMap<&'static str, (i32, &'static str)>,
+        }
+
+        #[derive(Debug)]
+        #[allow(unused)]
+        struct Bar(i32, Option<&'static str>);
+
+        #[derive(Debug)]
+        struct Unit;
+
+        assert_renders_like_debug(&Foo {
+            value: 1.5,
+            bar: vec![Bar(1, None), Bar(-2, Some("bar"))],
+            map: vec![("a", (1, "b"))].into_iter().collect(),
+        });
+        assert_renders_like_debug(&Unit);
+        assert_renders_like_debug(&Vec::<i32>::new());
+        assert_renders_like_debug(&HashMap::<i32, i32>::new());
+    }
+
+    #[test]
+    fn renders_unit_tuples() {
+        assert_renders_like_debug(&());
+        assert_renders_like_debug(&Some(()));
+        assert_renders_like_debug(&vec![(), ()]);
+    }
+
+    #[test]
+    fn truncates_diff_to_the_changed_lines_first() {
+        let diff = " a\n b\n c\n<d\n>e\n f\n g\n";
+        assert_eq!(truncate_diff(diff, diff.len()), diff);
+        assert_eq!(
+            truncate_diff(diff, 12),
+            "…\n c\n<d\n>e\n f\n… (diff truncated, 9 bytes omitted)\n"
+        );
+        assert_eq!(
+            truncate_diff(diff, 5),
+            "…\n<d\n… (diff truncated, 18 bytes omitted)\n"
+        );
+    }
+
+    #[test]
+    fn elides_unchanged_lines_furthest_from_the_changes() {
+        let diff = " a\n b\n c\n<d\n>e\n f\n g\n h\n";
+        assert_eq!(elide_unchanged_lines(diff, 8), diff);
+        assert_eq!(
+            elide_unchanged_lines(diff, 4),
+            "... 2 unchanged lines ...\n c\n<d\n>e\n f\n... 2 unchanged lines ...\n"
+        );
+        assert_eq!(
+            elide_unchanged_lines(diff, 1),
+            "... 3 unchanged lines ...\n<d\n>e\n... 3 unchanged lines ...\n"
+        );
+    }
+
+    #[test]
+    fn renders_control_chars_as_pictures() {
+        let value = parse(r#"{"a\u{0}": "\u{7}b\n\u{1b}[0m\u{7f}"}"#).unwrap();
+        let renderer = Renderer::new(&SortOptions::default().visible_control_chars(true));
+        assert_eq!(renderer.render(&value), "{\n    \"a␀\": \"␇b\\n␛[0m␡\",\n}");
+    }
+
+    #[test]
+    fn highlights_keys() {
+        let value = parse(r#"Foo { map: {"a": 1, Bar { b: 2 }: 3} }"#).unwrap();
+        let renderer = Renderer {
+            highlight_keys: true,
+            annotate_unsortable: false,
+            visible_control_chars: false,
+            fold_markers: false,
+            narrow_width: None,
+            pretty_print_strings: false,
+            max_map_entries: None,
+        };
+        assert_eq!(
+            renderer.render(&value),
+            "Foo {\n    \u{1b}[36mmap\u{1b}[39m: {\n        \u{1b}[36m\"a\"\u{1b}[39m: 1,\n        \u{1b}[36mBar {\n            b: 2,\n        }\u{1b}[39m: 3,\n    },\n}"
+        );
+        assert_eq!(
+            strip_key_highlights(&renderer.render(&value)),
+            format!("{:#?}", value)
+        );
+    }
+}
@@ -0,0 +1,200 @@
+//! Sorting of map-shaped sections in arbitrary text, eg. generated files that embed pretty
+//! Debug output or JSON.
+use std::borrow::Cow;
+use std::iter::Peekable;
+use std::str::Lines;
+
+/// A line of text, and the lines nested under it if it opens a block (`{`, `[` or `(`).
+struct Block<'a> {
+    line: Cow<'a, str>,
+    children: Vec<Block<'a>>,
+    closing: Option<Cow<'a, str>>,
+}
+
+impl<'a> Block<'a> {
+    fn is_map(&self) -> bool {
+        let line = self.line.trim_end();
+        let before_brace = line.trim_end_matches('{').trim_end();
+        line.ends_with('{')
+            && !self.children.is_empty()
+            && (before_brace.is_empty()
+                || before_brace.ends_with(':')
+                || before_brace.ends_with('='))
+    }
+
+    fn last_line_mut(&mut self) -> &mut Cow<'a, str> {
+        self.closing.as_mut().unwrap_or(&mut self.line)
+    }
+
+    fn render(&self, out: &mut String) {
+        out.push_str(&self.line);
+        out.push('\n');
+        for child in &self.children {
+            child.render(out);
+        }
+        if let Some(closing) = &self.closing {
+            out.push_str(closing);
+            out.push('\n');
+        }
+    }
+}
+
+fn indentation(line: &str) -> usize {
+    line.len() - line.trim_start().len()
+}
+
+/// Parses lines into blocks until the closing line of the parent block (indented at most
+/// `parent_indent`) is found.
+fn parse_blocks<'a>(
+    lines: &mut Peekable<Lines<'a>>,
+    parent_indent: Option<usize>,
+) -> (Vec<Block<'a>>, Option<Cow<'a, str>>) {
+    let mut blocks = Vec::new();
+    while let Some(line) = lines.next() {
+        if let Some(parent_indent) = parent_indent {
+            if indentation(line) <= parent_indent && line.trim_start().starts_with(['}', ']', ')'])
+            {
+                return (blocks, Some(Cow::Borrowed(line)));
+            }
+        }
+
+        if line.trim_end().ends_with(['{', '[', '(']) {
+            let (children, closing) = parse_blocks(lines, Some(indentation(line)));
+            blocks.push(Block {
+                line: Cow::Borrowed(line),
+                children,
+                closing,
+            });
+        } else {
+            blocks.push(Block {
+                line: Cow::Borrowed(line),
+                children: Vec::new(),
+                closing: None,
+            });
+        }
+    }
+    (blocks, None)
+}
+
+fn sort_blocks(blocks: &mut [Block]) {
+    for block in blocks {
+        sort_blocks(&mut block.children);
+        if !block.is_map() {
+            continue;
+        }
+
+        // Separators that are only missing after the last entry (eg. JSON) have to move
+        // along with the last entry.
+        let entries = &mut block.children;
+        let is_separated = |entry: &mut Block| entry.last_line_mut().trim_end().ends_with(',');
+        let (last, rest) = entries.split_last_mut().expect("maps have entries");
+        let move_separator =
+            !rest.is_empty() && !is_separated(last) && rest.iter_mut().all(is_separated);
+
+        entries.sort_by_cached_key(|entry| {
+            let mut text = String::new();
+            entry.render(&mut text);
+            text.trim_end().trim_end_matches(',').to_string()
+        });
+
+        if move_separator {
+            let last_index = entries.len() - 1;
+            for (index, entry) in entries.iter_mut().enumerate() {
+                let line = entry.last_line_mut();
+                let trimmed = line.trim_end();
+                if index == last_index {
+                    *line = Cow::Owned(trimmed.trim_end_matches(',').to_string());
+                } else if !trimmed.ends_with(',') {
+                    *line = Cow::Owned(format!("{},", trimmed));
+                }
+            }
+        }
+    }
+}
+
+/// Sorts the entries of every map-shaped section in the text, so that two texts that only
+/// differ in the ordering of their maps become equal.
+///
+/// A map-shaped section is a block of lines opened by a line ending in `{` (that is either
+/// just the `{` or a key like `key: {` or `key = {`) and closed by a line starting with `}`.
+/// Blocks opened by a name (eg. `Foo {`) are structs and are left alone, as are lines that
+/// aren't part of any block. Each entry is a line together with any block it opens, and
+/// sections nested inside of entries are sorted as well.
+///
+/// ```rust
+/// use pretty_assertions_sorted::sort_map_sections;
+///
+/// assert_eq!(
+///     sort_map_sections("config = {\n    b: 2,\n    a: 1,\n}\n"),
+///     "config = {\n    a: 1,\n    b: 2,\n}\n",
+/// );
+/// ```
+pub fn sort_map_sections(text: &str) -> String {
+    let (mut blocks, _) = parse_blocks(&mut text.lines().peekable(), None);
+    sort_blocks(&mut blocks);
+
+    let mut out = String::with_capacity(text.len());
+    for block in &blocks {
+        block.render(&mut out);
+    }
+    if !text.ends_with('\n') {
+        out.pop();
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use indoc::indoc;
+
+    #[test]
+    fn sorts_nested_map_sections() {
+        let text = indoc!(
+            "Report {
+                counts: {
+                    \"b\": [
+                        2,
+                        1,
+                    ],
+                    \"a\": {
+                        y: 2,
+                        x: 1,
+                    },
+                },
+                zeta: 1,
+                alpha: 2,
+            }
+            "
+        );
+        assert_eq!(
+            sort_map_sections(text),
+            indoc!(
+                "Report {
+                    counts: {
+                        \"a\": {
+                            x: 1,
+                            y: 2,
+                        },
+                        \"b\": [
+                            2,
+                            1,
+                        ],
+                    },
+                    zeta: 1,
+                    alpha: 2,
+                }
+                "
+            )
+        );
+    }
+
+    #[test]
+    fn moves_separators_with_the_last_entry() {
+        let text = "{\n  \"b\": 2,\n  \"c\": {\n    \"d\": 4\n  },\n  \"a\": 1\n}";
+        assert_eq!(
+            sort_map_sections(text),
+            "{\n  \"a\": 1,\n  \"b\": 2,\n  \"c\": {\n    \"d\": 4\n  }\n}"
+        );
+    }
+}
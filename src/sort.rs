@@ -0,0 +1,715 @@
+//! Sorting of a parsed [`Value`] tree.
+use crate::{render, SortOptions};
+use darrentsung_debug_parser::{KeyValue, OrNonExhaustive, Term, Value};
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::fmt;
+use std::sync::{Arc, OnceLock};
+
+/// Sorts the value according to the options. Returns true if the
+/// [`SortOptions::max_nodes`] budget ran out before the whole value was sorted.
+pub(crate) fn sort_maps(v: &mut Value, options: &SortOptions) -> bool {
+    if !options.only_fields.is_empty() {
+        prune_fields(v, options);
+    }
+    let mut budget = Budget {
+        remaining: options.max_nodes.unwrap_or(usize::MAX),
+        exhausted: false,
+    };
+    sort_within_budget(v, options, &mut budget, &mut Vec::new(), false);
+    budget.exhausted
+}
+
+/// The number of nodes that can still be sorted, see [`SortOptions::max_nodes`].
+struct Budget {
+    remaining: usize,
+    /// Whether a node was left unsorted because the budget ran out.
+    exhausted: bool,
+}
+
+impl Budget {
+    /// Takes a node from the budget, returns false if there are none left.
+    fn take(&mut self) -> bool {
+        if self.remaining == 0 {
+            self.exhausted = true;
+            return false;
+        }
+        self.remaining -= 1;
+        true
+    }
+}
+
+/// Removes the fields of a top-level struct that aren't in [`SortOptions::only_fields`].
+fn prune_fields(v: &mut Value, options: &SortOptions) {
+    if let Value::Struct(s) = v {
+        s.values.retain(|v| match v {
+            OrNonExhaustive::Value(ident_value) => options.only_fields.contains(&ident_value.ident),
+            OrNonExhaustive::NonExhaustive => false,
+        });
+    }
+}
+
+/// Sorts the value until `budget` nodes have been visited, leaving the remaining subtrees
+/// unsorted, see [`SortOptions::max_nodes`]. Redaction and the normalizations still apply
+/// to the unsorted subtrees, since they change what the value compares equal to.
+///
+/// `path` holds the field names and map keys leading to the value, which are only tracked
+/// when needed for [`SortOptions::sort_only`]. `preserve_order` keeps the entries of the map
+/// (possibly wrapped in `Some`) in their original order, see
+/// [`SortOptions::preserve_order_fields`].
+fn sort_within_budget(
+    v: &mut Value,
+    options: &SortOptions,
+    budget: &mut Budget,
+    path: &mut Vec<String>,
+    preserve_order: bool,
+) {
+    let sorts = budget.take();
+    if !sorts && !normalizes(options) {
+        return;
+    }
+    let tracks_path = !options.sort_only.is_empty();
+
+    match v {
+        Value::Struct(s) => {
+            for ident_value_or_non_exhaustive in &mut s.values {
+                match ident_value_or_non_exhaustive {
+                    OrNonExhaustive::Value(ident_value) => {
+                        if options.redact.contains(&ident_value.ident) {
+                            ident_value.value =
+                                Value::Term(Term::UnquotedRawString("<redacted>".to_string()));
+                        } else if !options.skip_fields.contains(&ident_value.ident) {
+                            if tracks_path {
+                                path.push(ident_value.ident.clone());
+                            }
+                            let preserve_order =
+                                options.preserve_order_fields.contains(&ident_value.ident);
+                            sort_within_budget(
+                                &mut ident_value.value,
+                                options,
+                                budget,
+                                path,
+                                preserve_order,
+                            );
+                            if tracks_path {
+                                path.pop();
+                            }
+                        }
+                    }
+                    OrNonExhaustive::NonExhaustive => (),
+                }
+            }
+
+            if options.hide_empty_containers {
+                s.values.retain(|v| match v {
+                    OrNonExhaustive::Value(ident_value) => !is_empty_container(&ident_value.value),
+                    OrNonExhaustive::NonExhaustive => true,
+                });
+            }
+
+            // The `..` of non-exhaustive structs stays last.
+            if sorts && options.sort_enum_variant_fields.contains(&s.name) {
+                s.values.sort_by(|a, b| match (a, b) {
+                    (OrNonExhaustive::Value(a), OrNonExhaustive::Value(b)) => a.ident.cmp(&b.ident),
+                    (OrNonExhaustive::Value(_), OrNonExhaustive::NonExhaustive) => Ordering::Less,
+                    (OrNonExhaustive::NonExhaustive, OrNonExhaustive::Value(_)) => {
+                        Ordering::Greater
+                    }
+                    (OrNonExhaustive::NonExhaustive, OrNonExhaustive::NonExhaustive) => {
+                        Ordering::Equal
+                    }
+                });
+            }
+        }
+        Value::Set(s) => {
+            for child_v in &mut s.values {
+                sort_within_budget(child_v, options, budget, path, false);
+            }
+
+            if sorts && !options.unsorted_sets {
+                s.values.sort_by(compare_elements);
+            }
+        }
+        Value::Map(map) => {
+            if options.hide_empty_containers {
+                map.values
+                    .retain(|key_value| !is_empty_container(&key_value.value));
+            }
+
+            if options.normalize_path_keys {
+                for key_value in &mut map.values {
+                    if let Value::Term(Term::String(key)) = &mut key_value.key {
+                        if key.contains('\\') {
+                            *key = key.replace('\\', "/");
+                        }
+                    }
+                }
+            }
+
+            // Keys are sorted (and normalized) first, so that they're ordered by their final
+            // form. Values are sorted before the entries as well, since they break ties
+            // between keys that are equal (eg. keys with a custom Debug implementation).
+            for key_value in &mut map.values {
+                sort_within_budget(&mut key_value.key, options, budget, path, false);
+                if tracks_path {
+                    path.push(key_text(&key_value.key));
+                }
+                sort_within_budget(&mut key_value.value, options, budget, path, false);
+                if tracks_path {
+                    path.pop();
+                }
+            }
+
+            if !sorts
+                || preserve_order
+                || (tracks_path && !options.sort_only.contains(&path.join(".")))
+            {
+                return;
+            }
+
+            // Keys that aren't of the dominant kind can't be meaningfully compared against
+            // the other keys, so they're grouped after the other keys and ordered by their
+            // rendered Debug output instead.
+            let dominant_kind = dominant_key_kind(&map.values);
+            map.values.sort_by(|a, b| {
+                let a_unsortable = Some(KeyKind::of(&a.key)) != dominant_kind;
+                let b_unsortable = Some(KeyKind::of(&b.key)) != dominant_kind;
+                a_unsortable
+                    .cmp(&b_unsortable)
+                    .then_with(|| {
+                        if a_unsortable && b_unsortable {
+                            render::render_inline(&a.key).cmp(&render::render_inline(&b.key))
+                        } else {
+                            let ordering = compare_keys(&a.key, &b.key, options);
+                            match options.order {
+                                SortOrder::Ascending => ordering,
+                                SortOrder::Descending => ordering.reverse(),
+                            }
+                        }
+                    })
+                    .then_with(|| a.value.cmp(&b.value))
+            });
+        }
+        Value::List(l) => {
+            for child_v in &mut l.values {
+                sort_within_budget(child_v, options, budget, path, false);
+            }
+
+            // Children are sorted first so that the ordering of the list doesn't depend
+            // on the (non-deterministic) ordering inside of its elements.
+            if sorts && (options.sort_lists || options.unordered_lists) {
+                l.values.sort_by(compare_elements);
+            }
+        }
+        Value::Tuple(t) => {
+            let preserve_order = preserve_order && t.name.as_deref() == Some("Some");
+            for child_v in &mut t.values {
+                sort_within_budget(child_v, options, budget, path, preserve_order);
+            }
+
+            if sorts && options.sort_tuples && t.name.is_none() {
+                t.values.sort();
+            }
+
+            if options.unwrap_redundant_options {
+                unwrap_redundant_wrappers(v);
+            }
+        }
+        Value::Term(Term::UnquotedRawString(raw)) if options.mask_addresses && is_address(raw) => {
+            *raw = MASKED_ADDRESS.to_string();
+        }
+        Value::Term(Term::UnquotedRawString(raw)) if options.normalize_numbers => {
+            if let Some(number) = normalized_number(raw) {
+                *raw = number;
+            }
+        }
+        Value::Term(Term::String(s)) => normalize_term(s, options),
+        // No need to recurse for Term variant.
+        Value::Term(_) => (),
+    }
+}
+
+/// Returns true if the options redact or normalize parts of the value, which has to happen
+/// even where the value isn't sorted.
+fn normalizes(options: &SortOptions) -> bool {
+    !options.redact.is_empty()
+        || options.hide_empty_containers
+        || options.normalize_path_keys
+        || options.mask_addresses
+        || options.normalize_numbers
+        || options.unwrap_redundant_options
+        || normalizes_terms(options)
+}
+
+/// Returns true for empty maps, sets, and lists, see [`SortOptions::hide_empty_containers`].
+fn is_empty_container(v: &Value) -> bool {
+    match v {
+        Value::Map(map) => map.values.is_empty(),
+        Value::Set(set) => set.values.is_empty(),
+        Value::List(list) => list.values.is_empty(),
+        _ => false,
+    }
+}
+
+/// Collapses chains of the same wrapper (eg. `Some(Some(x))` or `Ok(Ok(x))`) into a single
+/// level, see [`SortOptions::unwrap_redundant_options`].
+fn unwrap_redundant_wrappers(v: &mut Value) {
+    loop {
+        let inner = match v {
+            Value::Tuple(outer) => match (outer.name.as_deref(), &mut outer.values[..]) {
+                (Some(name @ ("Some" | "Ok")), [inner @ Value::Tuple(_)]) => match inner {
+                    Value::Tuple(inner_tuple)
+                        if inner_tuple.name.as_deref() == Some(name)
+                            && inner_tuple.values.len() == 1 =>
+                    {
+                        std::mem::replace(inner, Value::Term(Term::Ident(String::new())))
+                    }
+                    _ => return,
+                },
+                _ => return,
+            },
+            _ => return,
+        };
+        *v = inner;
+    }
+}
+
+/// What addresses are replaced with, see [`SortOptions::mask_addresses`].
+const MASKED_ADDRESS: &str = "<address>";
+
+/// Returns true if the term looks like a pointer address (eg. `0x7ffd5e8c1a2c`), which is
+/// how the Debug output of raw pointers and `{:p}` print them.
+fn is_address(raw: &str) -> bool {
+    raw.strip_prefix("0x")
+        .is_some_and(|digits| !digits.is_empty() && digits.chars().all(|c| c.is_ascii_hexdigit()))
+}
+
+/// Reformats a floating point number the way the Debug output of `f64` prints it, with
+/// `-0.0` folded into `0.0`, see [`SortOptions::normalize_numbers`]. Returns `None` for
+/// integers (which are left as they are to not lose precision) and everything else.
+fn normalized_number(raw: &str) -> Option<String> {
+    if raw.parse::<i128>().is_ok() {
+        return None;
+    }
+    let number = raw
+        .parse::<f64>()
+        .ok()
+        .filter(|number| number.is_finite())?;
+    Some(format!("{:?}", number + 0.0))
+}
+
+/// Compares two map keys of the same kind.
+fn compare_keys(a: &Value, b: &Value, options: &SortOptions) -> Ordering {
+    if let Some(KeyComparator(comparator)) = &options.key_comparator {
+        return comparator(&key_text(a), &key_text(b)).then_with(|| a.cmp(b));
+    }
+
+    if let (Some(a_key), Some(b_key)) = (sort_key_field(a, options), sort_key_field(b, options)) {
+        return compare_elements(a_key, b_key).then_with(|| a.cmp(b));
+    }
+
+    if let (Value::Term(Term::String(a)), Value::Term(Term::String(b)), Some(TextFn(normalizer))) =
+        (a, b, &options.key_normalizer)
+    {
+        return normalizer(a).cmp(&normalizer(b)).then_with(|| a.cmp(b));
+    }
+
+    if let Some(TextFn(render)) = &options.key_sort_render {
+        let (a_text, b_text) = (render(&key_text(a)), render(&key_text(b)));
+        return compare_key_texts(&a_text, &b_text, options).then_with(|| a.cmp(b));
+    }
+
+    if !options.sort_chain.is_empty() {
+        return compare_key_texts(&key_text(a), &key_text(b), options).then_with(|| a.cmp(b));
+    }
+
+    match (a, b) {
+        (Value::Term(Term::String(a)), Value::Term(Term::String(b)))
+            if options.sort_by_length_then_lexical =>
+        {
+            a.chars()
+                .count()
+                .cmp(&b.chars().count())
+                .then_with(|| a.cmp(b))
+        }
+        (Value::Term(Term::String(a)), Value::Term(Term::String(b)))
+            if options.case_insensitive_keys =>
+        {
+            SortCriterion::CaseInsensitive
+                .compare(a, b)
+                .then_with(|| a.cmp(b))
+        }
+        // Integer keys sort by their value, so that eg. `2` sorts before `10`. Byte strings
+        // sort bytewise, whether they're printed as a list of bytes (eg. `Vec<u8>`) or as a
+        // byte string literal (eg. `b"abc"`). Both are grouped before the other keys, so
+        // that the ordering stays consistent when only some keys are integers or bytes (eg.
+        // a `Vec<u16>` key with an element above 255).
+        _ => compare_integers(a, b).then_with(|| match (byte_string(a), byte_string(b)) {
+            (Some(a_bytes), Some(b_bytes)) => a_bytes.cmp(&b_bytes).then_with(|| a.cmp(b)),
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (None, None) => a.cmp(b),
+        }),
+    }
+}
+
+/// Compares two elements of a set (or fields of keys), integers by their value (and before
+/// other elements), like integer map keys.
+fn compare_elements(a: &Value, b: &Value) -> Ordering {
+    compare_integers(a, b).then_with(|| a.cmp(b))
+}
+
+/// Orders integers by their value and before other values, which are left equal.
+fn compare_integers(a: &Value, b: &Value) -> Ordering {
+    match (integer(a), integer(b)) {
+        (Some(a_number), Some(b_number)) => a_number.cmp(&b_number),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => Ordering::Equal,
+    }
+}
+
+/// The value of the field that a struct key is sorted by, if its type was registered with
+/// [`SortOptions::sort_key`].
+fn sort_key_field<'a>(key: &'a Value, options: &SortOptions) -> Option<&'a Value> {
+    let Value::Struct(s) = key else {
+        return None;
+    };
+    let field = options.sort_keys.get(&s.name)?;
+    s.values.iter().find_map(|v| match v {
+        OrNonExhaustive::Value(ident_value) if &ident_value.ident == field => {
+            Some(&ident_value.value)
+        }
+        _ => None,
+    })
+}
+
+fn integer(value: &Value) -> Option<i128> {
+    match value {
+        Value::Term(Term::UnquotedRawString(raw)) => raw.parse().ok(),
+        _ => None,
+    }
+}
+
+/// The bytes of a list of bytes (eg. `[104, 105]`) or a byte string literal (eg. `b"hi"`).
+fn byte_string(value: &Value) -> Option<Vec<u8>> {
+    match value {
+        Value::List(list) => list
+            .values
+            .iter()
+            .map(|v| match v {
+                Value::Term(Term::UnquotedRawString(raw)) => raw.parse::<u8>().ok(),
+                _ => None,
+            })
+            .collect(),
+        Value::Term(Term::UnquotedRawString(raw)) => {
+            let literal = raw.strip_prefix("b\"")?.strip_suffix('"')?;
+            let mut bytes = vec![];
+            let mut chars = literal.chars();
+            while let Some(c) = chars.next() {
+                let byte = match c {
+                    '\\' => match chars.next()? {
+                        'n' => b'\n',
+                        'r' => b'\r',
+                        't' => b'\t',
+                        '0' => b'\0',
+                        'x' => {
+                            let hex = chars.by_ref().take(2).collect::<String>();
+                            u8::from_str_radix(&hex, 16).ok()?
+                        }
+                        escaped => u8::try_from(escaped).ok()?,
+                    },
+                    c => u8::try_from(c).ok()?,
+                };
+                bytes.push(byte);
+            }
+            Some(bytes)
+        }
+        _ => None,
+    }
+}
+
+/// Compares the texts of two keys by the [`SortCriterion`]s of the options, if any.
+fn compare_key_texts(a: &str, b: &str, options: &SortOptions) -> Ordering {
+    if options.sort_chain.is_empty() {
+        return a.cmp(b);
+    }
+    options
+        .sort_chain
+        .iter()
+        .map(|criterion| criterion.compare(a, b))
+        .find(|ordering| ordering.is_ne())
+        .unwrap_or(Ordering::Equal)
+}
+
+/// A user-provided function that maps a text to another text, eg. the
+/// [`SortOptions::key_sort_render`] or the [`SortOptions::term_normalizer`].
+#[derive(Clone)]
+pub(crate) struct TextFn(pub(crate) Arc<dyn Fn(&str) -> String + Send + Sync>);
+
+impl fmt::Debug for TextFn {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("TextFn(..)")
+    }
+}
+
+/// A user-provided comparison of the texts of two map keys, see
+/// [`SortOptions::key_comparator`].
+#[derive(Clone)]
+pub(crate) struct KeyComparator(pub(crate) Arc<KeyComparatorFn>);
+
+type KeyComparatorFn = dyn Fn(&str, &str) -> Ordering + Send + Sync;
+
+impl fmt::Debug for KeyComparator {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("KeyComparator(..)")
+    }
+}
+
+/// The term normalizer that applies to every value, see [`set_global_term_normalizer`].
+static GLOBAL_TERM_NORMALIZER: OnceLock<TextFn> = OnceLock::new();
+
+/// Registers a normalizer for the contents of every string (including map keys) in the
+/// sorted Debug output of the test binary (eg. for stripping ANSI escapes or lowercasing
+/// UUIDs), so that it doesn't have to be configured for every assertion. Values that only
+/// differ before normalization are equal.
+///
+/// The global normalizer can only be registered once, returns false if one was already
+/// registered. It's applied before the normalizer of the [`SortOptions`] (see
+/// [`SortOptions::term_normalizer`]), so the two compose.
+///
+/// When the normalizer changes a string of either value, [`assert_eq_sorted`] compares
+/// the normalized, sorted Debug output instead of using [`PartialEq`], so values that
+/// aren't equal by [`PartialEq`] can pass. Values whose strings it leaves unchanged are
+/// still compared with [`PartialEq`].
+///
+/// [`assert_eq_sorted`]: crate::assert_eq_sorted
+///
+/// ```rust
+/// use pretty_assertions_sorted::{assert_eq_sorted, set_global_term_normalizer};
+///
+/// set_global_term_normalizer(|s| s.trim().to_string());
+/// assert_eq_sorted!(vec![" a "], vec!["a"]);
+/// ```
+pub fn set_global_term_normalizer<F>(normalizer: F) -> bool
+where
+    F: Fn(&str) -> String + Send + Sync + 'static,
+{
+    GLOBAL_TERM_NORMALIZER
+        .set(TextFn(Arc::new(normalizer)))
+        .is_ok()
+}
+
+/// Returns true if any term normalizer applies with the options.
+pub(crate) fn normalizes_terms(options: &SortOptions) -> bool {
+    options.term_normalizer.is_some() || GLOBAL_TERM_NORMALIZER.get().is_some()
+}
+
+/// Returns true if the term normalizers change any string of the (unsorted) value.
+pub(crate) fn changes_terms(v: &Value, options: &SortOptions) -> bool {
+    match v {
+        Value::Struct(s) => s.values.iter().any(|v| match v {
+            OrNonExhaustive::Value(ident_value) => changes_terms(&ident_value.value, options),
+            OrNonExhaustive::NonExhaustive => false,
+        }),
+        Value::Map(map) => map
+            .values
+            .iter()
+            .any(|kv| changes_terms(&kv.key, options) || changes_terms(&kv.value, options)),
+        Value::Set(set) => set.values.iter().any(|v| changes_terms(v, options)),
+        Value::List(list) => list.values.iter().any(|v| changes_terms(v, options)),
+        Value::Tuple(tuple) => tuple.values.iter().any(|v| changes_terms(v, options)),
+        Value::Term(Term::String(s)) => {
+            let mut normalized = s.clone();
+            normalize_term(&mut normalized, options);
+            normalized != *s
+        }
+        Value::Term(_) => false,
+    }
+}
+
+fn normalize_term(s: &mut String, options: &SortOptions) {
+    for TextFn(normalizer) in GLOBAL_TERM_NORMALIZER
+        .get()
+        .into_iter()
+        .chain(&options.term_normalizer)
+    {
+        *s = normalizer(s);
+    }
+}
+
+/// The text of a key that the [`SortCriterion`]s compare: the contents of string keys, or
+/// the single-line Debug output of other keys.
+fn key_text(key: &Value) -> String {
+    match key {
+        Value::Term(Term::String(s)) => s.clone(),
+        _ => render::render_inline(key),
+    }
+}
+
+/// A type that's used as a map key and sorted by one of its fields, instead of by all of
+/// its fields in order. Register it with [`SortOptions::sort_key`].
+///
+/// Derive it with `#[derive(SortedDebugKey)]` (with the `derive` feature), marking the
+/// field to sort by with `#[sort_key]`:
+///
+/// ```rust,ignore
+/// use pretty_assertions_sorted::SortedDebugKey;
+///
+/// #[derive(Debug, PartialEq, Eq, Hash, SortedDebugKey)]
+/// struct Key {
+///     #[sort_key]
+///     id: u32,
+///     name: String,
+/// }
+/// ```
+pub trait SortedDebugKey {
+    /// The name of the struct in its Debug output.
+    const NAME: &'static str;
+    /// The name of the field that keys are sorted by.
+    const SORT_KEY: &'static str;
+}
+
+/// The direction that map keys are sorted in, see [`SortOptions::order`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SortOrder {
+    /// Smallest keys first.
+    #[default]
+    Ascending,
+    /// Largest keys first, eg. for maps keyed by a version where the most recent version
+    /// matters most.
+    Descending,
+}
+
+/// A criterion for sorting map keys, see [`SortOptions::sort_chain`].
+///
+/// Keys are compared by their text: the contents of string keys, or the single-line Debug
+/// output of other keys.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortCriterion {
+    /// Compare the text of the keys byte by byte.
+    Lexical,
+    /// Compare keys that are numbers by their value, numbers are ordered before other keys.
+    /// Keys that aren't numbers are tied.
+    Numeric,
+    /// Compare runs of digits in the keys by their value and everything else lexically,
+    /// eg. `item2` is ordered before `item10`.
+    Natural,
+    /// Compare the text of the keys ignoring case.
+    CaseInsensitive,
+    /// Compare the number of characters of the keys, shorter keys first.
+    Length,
+}
+
+impl SortCriterion {
+    fn compare(self, a: &str, b: &str) -> Ordering {
+        match self {
+            SortCriterion::Lexical => a.cmp(b),
+            SortCriterion::Numeric => match (a.parse::<f64>(), b.parse::<f64>()) {
+                (Ok(a), Ok(b)) => a.partial_cmp(&b).unwrap_or(Ordering::Equal),
+                (Ok(_), Err(_)) => Ordering::Less,
+                (Err(_), Ok(_)) => Ordering::Greater,
+                (Err(_), Err(_)) => Ordering::Equal,
+            },
+            SortCriterion::Natural => compare_natural(a, b),
+            SortCriterion::CaseInsensitive => a.to_lowercase().cmp(&b.to_lowercase()),
+            SortCriterion::Length => a.chars().count().cmp(&b.chars().count()),
+        }
+    }
+}
+
+/// Compares the runs of digits in the texts by their value (ignoring leading zeros) and
+/// everything else character by character.
+fn compare_natural(a: &str, b: &str) -> Ordering {
+    let (mut a, mut b) = (a, b);
+    loop {
+        let (a_char, b_char) = match (a.chars().next(), b.chars().next()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(a_char), Some(b_char)) => (a_char, b_char),
+        };
+
+        if a_char.is_ascii_digit() && b_char.is_ascii_digit() {
+            let a_len = a.find(|c: char| !c.is_ascii_digit()).unwrap_or(a.len());
+            let b_len = b.find(|c: char| !c.is_ascii_digit()).unwrap_or(b.len());
+            let a_digits = a[..a_len].trim_start_matches('0');
+            let b_digits = b[..b_len].trim_start_matches('0');
+            let ordering = a_digits
+                .len()
+                .cmp(&b_digits.len())
+                .then_with(|| a_digits.cmp(b_digits));
+            if ordering.is_ne() {
+                return ordering;
+            }
+            a = &a[a_len..];
+            b = &b[b_len..];
+        } else {
+            let ordering = a_char.cmp(&b_char);
+            if ordering.is_ne() {
+                return ordering;
+            }
+            a = &a[a_char.len_utf8()..];
+            b = &b[b_char.len_utf8()..];
+        }
+    }
+}
+
+/// The kind of a map key. Keys of different kinds can't be meaningfully compared against
+/// each other, eg. a unit enum variant and a struct enum variant.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) enum KeyKind {
+    Struct,
+    Set,
+    Map,
+    List,
+    Tuple,
+    Ident,
+    String,
+    UnquotedRawString,
+}
+
+impl KeyKind {
+    pub(crate) fn of(key: &Value) -> Self {
+        match key {
+            Value::Struct(_) => KeyKind::Struct,
+            Value::Set(_) => KeyKind::Set,
+            Value::Map(_) => KeyKind::Map,
+            Value::List(_) => KeyKind::List,
+            Value::Tuple(_) => KeyKind::Tuple,
+            Value::Term(Term::Ident(_)) => KeyKind::Ident,
+            Value::Term(Term::String(_)) => KeyKind::String,
+            Value::Term(Term::UnquotedRawString(_)) => KeyKind::UnquotedRawString,
+        }
+    }
+}
+
+/// The most common kind of key in the map entries. Ties are broken by the order of the
+/// kinds so that the result doesn't depend on the order of the entries.
+pub(crate) fn dominant_key_kind(entries: &[KeyValue]) -> Option<KeyKind> {
+    let mut counts = BTreeMap::new();
+    for entry in entries {
+        *counts.entry(KeyKind::of(&entry.key)).or_insert(0) += 1;
+    }
+    counts
+        .into_iter()
+        .rev()
+        .max_by_key(|(_, count)| *count)
+        .map(|(kind, _)| kind)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compares_digit_runs_naturally() {
+        let mut keys = vec!["item10", "item2", "item02b", "item", "a1b2", "a1b10"];
+        keys.sort_by(|a, b| SortCriterion::Natural.compare(a, b));
+        assert_eq!(
+            keys,
+            vec!["a1b2", "a1b10", "item", "item2", "item02b", "item10"]
+        );
+    }
+}
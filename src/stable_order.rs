@@ -0,0 +1,142 @@
+//! Golden files of the order of map keys and struct fields, see
+//! [`assert_stable_order`](crate::assert_stable_order).
+use crate::diff::{Path, PathSegment};
+use crate::{parse_unsorted, OrNonExhaustive, Value};
+use std::fmt;
+use std::fs;
+
+/// Environment variable that enables the update mode of
+/// [`assert_stable_order`](crate::assert_stable_order). When it's set to anything other
+/// than an empty string, golden files are (re)written from the actual order instead of
+/// being compared against.
+pub const UPDATE_ORDER_ENV_VAR: &str = "UPDATE_SORTED_ORDER";
+
+fn collect_paths(value: &Value, path: &mut Vec<PathSegment>, out: &mut String) {
+    let mut visit = |segment, child: &Value, out: &mut String, keyed: bool| {
+        path.push(segment);
+        if keyed {
+            out.push_str(&Path(path.clone()).to_string());
+            out.push('\n');
+        }
+        collect_paths(child, path, out);
+        path.pop();
+    };
+    match value {
+        Value::Struct(s) => {
+            for v in &s.values {
+                if let OrNonExhaustive::Value(ident_value) = v {
+                    visit(
+                        PathSegment::Field(ident_value.ident.clone()),
+                        &ident_value.value,
+                        out,
+                        true,
+                    );
+                }
+            }
+        }
+        Value::Map(map) => {
+            for kv in &map.values {
+                visit(PathSegment::key(&kv.key), &kv.value, out, true);
+            }
+        }
+        Value::Set(set) => {
+            for (index, v) in set.values.iter().enumerate() {
+                visit(PathSegment::Index(index), v, out, false);
+            }
+        }
+        Value::List(list) => {
+            for (index, v) in list.values.iter().enumerate() {
+                visit(PathSegment::Index(index), v, out, false);
+            }
+        }
+        Value::Tuple(tuple) => {
+            for (index, v) in tuple.values.iter().enumerate() {
+                visit(PathSegment::Index(index), v, out, false);
+            }
+        }
+        Value::Term(_) => (),
+    }
+}
+
+/// Returns the paths of the map keys and struct fields of the unsorted
+/// [`Debug`](fmt::Debug) representation of `value` in the order they're printed in, one
+/// per line, eg. `headers.date`.
+///
+/// Panics if the Debug output can't be parsed, since the order can't be checked then.
+pub fn key_order<T: fmt::Debug + ?Sized>(value: &T) -> String {
+    let value = parse_unsorted(&format!("{:?}", value))
+        .unwrap_or_else(|err| panic!("Can't check the key order: {}", err));
+    let mut out = String::new();
+    collect_paths(&value, &mut vec![], &mut out);
+    out
+}
+
+/// Loads the key order stored in the golden file at `path`.
+///
+/// The golden file is written from the `actual` order first if it doesn't exist yet, or in
+/// update mode (see [`UPDATE_ORDER_ENV_VAR`]).
+///
+/// Panics if the golden file can't be read or written, since this is meant to be used in
+/// tests.
+pub fn load_golden_order(path: &std::path::Path, actual: &str) -> String {
+    let update_requested = std::env::var_os(UPDATE_ORDER_ENV_VAR).is_some_and(|v| !v.is_empty());
+    if update_requested || !path.exists() {
+        if let Some(parent) = path.parent() {
+            let _ = fs::create_dir_all(parent);
+        }
+        fs::write(path, actual).unwrap_or_else(|err| {
+            panic!("Failed to write golden order {}: {}", path.display(), err)
+        });
+    }
+
+    fs::read_to_string(path)
+        .unwrap_or_else(|err| panic!("Failed to read golden order {}: {}", path.display(), err))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug)]
+    #[allow(unused)]
+    struct Config {
+        name: &'static str,
+        limits: Vec<(&'static str, u32)>,
+        env: Env,
+    }
+
+    struct Env(Vec<(&'static str, &'static str)>);
+
+    impl fmt::Debug for Env {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.debug_map()
+                .entries(self.0.iter().map(|(k, v)| (k, v)))
+                .finish()
+        }
+    }
+
+    #[test]
+    fn lists_keys_in_printed_order() {
+        let config = Config {
+            name: "a",
+            limits: vec![("cpu", 1)],
+            env: Env(vec![("PATH", "/bin"), ("HOME", "/root")]),
+        };
+        assert_eq!(
+            key_order(&config),
+            "name\nlimits\nenv\nenv.PATH\nenv.HOME\n"
+        );
+    }
+
+    #[test]
+    fn writes_missing_golden_file() {
+        let path = std::env::temp_dir()
+            .join(format!("pretty_assertions_sorted_{}", std::process::id()))
+            .join("order.golden");
+        let _ = fs::remove_file(&path);
+
+        assert_eq!(load_golden_order(&path, "a\nb\n"), "a\nb\n");
+        assert_eq!(load_golden_order(&path, "b\na\n"), "a\nb\n");
+        let _ = fs::remove_file(&path);
+    }
+}
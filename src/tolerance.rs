@@ -0,0 +1,218 @@
+//! Comparison of numeric values with per-key tolerances, built on the tandem walk of
+//! [`diff`](crate::diff).
+use crate::diff::{diff, DiffOp, Path, PathSegment};
+use crate::{render, sorted_values, SortOptions};
+use darrentsung_debug_parser::Value;
+use std::fmt;
+
+/// A value that differs between the left and right side by more than its tolerance, see
+/// [`sorted_tolerance_violations`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct ToleranceViolation {
+    /// The path to the value, eg. `metrics.latency` or `samples[2]`.
+    pub path: String,
+    /// The value on the left side, rendered on a single line. `None` if the value only
+    /// exists on the right side.
+    pub left: Option<String>,
+    /// The value on the right side, rendered on a single line. `None` if the value only
+    /// exists on the left side.
+    pub right: Option<String>,
+    /// The tolerance of the value, `None` if the value has to match exactly.
+    pub tolerance: Option<f64>,
+}
+
+impl fmt::Display for ToleranceViolation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let missing = "<missing>";
+        write!(
+            f,
+            "{}: {} vs. {}",
+            self.path,
+            self.left.as_deref().unwrap_or(missing),
+            self.right.as_deref().unwrap_or(missing),
+        )?;
+        match self.tolerance {
+            Some(tolerance) => write!(f, " (tolerance {})", tolerance),
+            None => write!(f, " (must match exactly)"),
+        }
+    }
+}
+
+/// Looks up the tolerance of the value at `path`: either by its full path (eg.
+/// `metrics.latency`) or by the name of the closest field or map key (eg. `latency`).
+fn tolerance_for(path: &Path, tolerances: &[(&str, f64)]) -> Option<f64> {
+    let full_path = path.to_string();
+    let closest_name = path.0.iter().rev().find_map(|segment| match segment {
+        PathSegment::Field(name) | PathSegment::Key(name) => Some(name.as_str()),
+        PathSegment::Index(_) => None,
+    });
+
+    tolerances
+        .iter()
+        .find(|(key, _)| *key == full_path)
+        .or_else(|| {
+            tolerances
+                .iter()
+                .find(|(key, _)| Some(*key) == closest_name)
+        })
+        .map(|(_, tolerance)| *tolerance)
+}
+
+fn as_number(value: &Value) -> Option<f64> {
+    match value {
+        Value::Term(_) => render::render_inline(value).parse().ok(),
+        _ => None,
+    }
+}
+
+/// Returns the values that differ between the sorted [`Debug`](fmt::Debug) representations
+/// of `left` and `right` by more than their tolerance.
+///
+/// Each tolerance applies to the numeric values of the field or map key with that name (or
+/// at that dotted path), any other values have to match exactly.
+///
+/// ```rust
+/// use std::collections::HashMap;
+/// use pretty_assertions_sorted::sorted_tolerance_violations;
+///
+/// let left: HashMap<_, _> = vec![("latency", 100.0), ("rate", 0.5)].into_iter().collect();
+/// let right: HashMap<_, _> = vec![("latency", 103.0), ("rate", 0.7)].into_iter().collect();
+///
+/// let violations = sorted_tolerance_violations(&left, &right, &[("latency", 5.0)]);
+/// assert_eq!(violations.len(), 1);
+/// assert_eq!(violations[0].to_string(), "rate: 0.5 vs. 0.7 (must match exactly)");
+/// ```
+pub fn sorted_tolerance_violations<L, R>(
+    left: &L,
+    right: &R,
+    tolerances: &[(&str, f64)],
+) -> Vec<ToleranceViolation>
+where
+    L: fmt::Debug + ?Sized,
+    R: fmt::Debug + ?Sized,
+{
+    let options = SortOptions::default();
+    let (left, right) = sorted_values(left, right, &options);
+
+    diff(&left, &right, &options)
+        .into_iter()
+        .filter_map(|op| {
+            let (path, left, right) = match op {
+                DiffOp::Added { path, right } => (path, None, Some(right)),
+                DiffOp::Removed { path, left } => (path, Some(left), None),
+                DiffOp::Changed { path, left, right } => (path, Some(left), Some(right)),
+            };
+            let tolerance = tolerance_for(&path, tolerances);
+
+            let (left_number, right_number) = (left.and_then(as_number), right.and_then(as_number));
+            let within_tolerance = match (left_number, right_number, tolerance) {
+                (Some(l), Some(r), Some(tolerance)) => (l - r).abs() <= tolerance,
+                _ => false,
+            };
+            if within_tolerance {
+                return None;
+            }
+
+            Some(ToleranceViolation {
+                path: path.to_string(),
+                left: left.map(render::render_inline),
+                right: right.map(render::render_inline),
+                tolerance,
+            })
+        })
+        .collect()
+}
+
+/// Returns true if the sorted [`Debug`](fmt::Debug) representations of `left` and `right`
+/// are equal, except for numeric values that differ by at most `epsilon`.
+///
+/// ```rust
+/// use pretty_assertions_sorted::sorted_approx_eq;
+///
+/// assert!(sorted_approx_eq(&vec![0.1 + 0.2], &vec![0.3], f64::EPSILON));
+/// assert!(!sorted_approx_eq(&vec![0.1], &vec![0.3], f64::EPSILON));
+/// ```
+pub fn sorted_approx_eq<L, R>(left: &L, right: &R, epsilon: f64) -> bool
+where
+    L: fmt::Debug + ?Sized,
+    R: fmt::Debug + ?Sized,
+{
+    let options = SortOptions::default();
+    let (left, right) = sorted_values(left, right, &options);
+
+    diff(&left, &right, &options)
+        .into_iter()
+        .all(|op| match op {
+            DiffOp::Changed { left, right, .. } => match (as_number(left), as_number(right)) {
+                (Some(l), Some(r)) => (l - r).abs() <= epsilon,
+                _ => false,
+            },
+            DiffOp::Added { .. } | DiffOp::Removed { .. } => false,
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[derive(Debug)]
+    #[allow(unused)]
+    struct Metrics {
+        latency: f64,
+        samples: Vec<f64>,
+        rates: HashMap<&'static str, f64>,
+    }
+
+    #[test]
+    fn checks_values_against_their_tolerances() {
+        let left = Metrics {
+            latency: 100.0,
+            samples: vec![1.0, 2.0],
+            rates: vec![("hit", 0.5), ("miss", 0.25)].into_iter().collect(),
+        };
+        let right = Metrics {
+            latency: 104.5,
+            samples: vec![1.5, 2.0, 3.0],
+            rates: vec![("hit", 0.51), ("miss", 0.5)].into_iter().collect(),
+        };
+
+        let violations = sorted_tolerance_violations(
+            &left,
+            &right,
+            &[("latency", 5.0), ("samples", 1.0), ("rates.hit", 0.1)],
+        );
+        assert_eq!(
+            violations
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>(),
+            vec![
+                "samples[2]: <missing> vs. 3.0 (tolerance 1)",
+                "rates.miss: 0.25 vs. 0.5 (must match exactly)",
+            ]
+        );
+    }
+
+    #[test]
+    fn compares_numbers_within_epsilon() {
+        let left = Metrics {
+            latency: 0.1 + 0.2,
+            samples: vec![1.0],
+            rates: vec![("hit", 0.5)].into_iter().collect(),
+        };
+        let close = Metrics {
+            latency: 0.3,
+            samples: vec![1.0],
+            rates: vec![("hit", 0.5)].into_iter().collect(),
+        };
+        assert!(sorted_approx_eq(&left, &close, f64::EPSILON));
+        assert!(!sorted_approx_eq(&left, &close, 0.0));
+
+        let longer = Metrics {
+            samples: vec![1.0, 2.0],
+            ..close
+        };
+        assert!(!sorted_approx_eq(&left, &longer, 1.0));
+    }
+}
@@ -0,0 +1,31 @@
+#![cfg(feature = "derive")]
+
+use pretty_assertions_sorted::{SortOptions, SortedDebug, SortedDebugKey};
+use std::collections::HashMap;
+
+#[derive(Debug, PartialEq, Eq, Hash, SortedDebugKey)]
+struct Key {
+    name: &'static str,
+    #[sort_key]
+    id: u32,
+}
+
+#[test]
+fn sorts_keys_by_the_derived_sort_key() {
+    for _ in 0..100 {
+        let item = HashMap::from([
+            (Key { name: "a", id: 3 }, 1),
+            (Key { name: "b", id: 2 }, 2),
+            (Key { name: "c", id: 1 }, 3),
+        ]);
+        let sorted = format!(
+            "{:?}",
+            SortedDebug::with_options(&item, SortOptions::default().sort_key::<Key>())
+        );
+        let ids = sorted
+            .lines()
+            .filter_map(|line| line.trim().strip_prefix("id: "))
+            .collect::<Vec<_>>();
+        assert_eq!(ids, vec!["1,", "2,", "3,"]);
+    }
+}
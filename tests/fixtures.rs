@@ -0,0 +1,78 @@
+#![cfg(all(feature = "ron", feature = "json"))]
+
+use pretty_assertions_sorted::{assert_eq_sorted_fixture, load_fixture};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Config {
+    name: String,
+    limits: HashMap<String, u32>,
+}
+
+fn config() -> Config {
+    Config {
+        name: "service".to_string(),
+        limits: HashMap::from([
+            ("cpu".to_string(), 4),
+            ("memory".to_string(), 512),
+            ("disk".to_string(), 100),
+        ]),
+    }
+}
+
+#[test]
+fn passes_with_ron_fixture() {
+    assert_eq_sorted_fixture!(config(), "tests/fixtures/config.ron");
+}
+
+#[test]
+fn passes_with_json_fixture() {
+    assert_eq_sorted_fixture!(config(), "tests/fixtures/config.json");
+}
+
+#[test]
+fn passes_with_path_fixture() {
+    assert_eq_sorted_fixture!(config(), Path::new("tests/fixtures/config.ron"));
+    assert_eq_sorted_fixture!(config(), PathBuf::from("tests/fixtures/config.json"));
+}
+
+/// A copy of the checked-in fixture in a temporary directory, for tests that expect the
+/// assertion to fail (which would overwrite the fixture with `UPDATE_SORTED_FIXTURES=1`).
+fn temp_fixture(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!(
+        "pretty_assertions_sorted_fixtures_{}",
+        std::process::id()
+    ));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join(name);
+    let _ = std::fs::remove_file(&path);
+    path
+}
+
+#[test]
+#[should_panic(expected = "assertion failed: `(actual == ")]
+fn fails_when_fixture_differs() {
+    let path = temp_fixture("differs.ron");
+    std::fs::copy(
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/config.ron"),
+        &path,
+    )
+    .unwrap();
+    let mut config = config();
+    config.limits.insert("disk".to_string(), 200);
+    assert_eq_sorted_fixture!(config, path);
+}
+
+#[test]
+#[should_panic(expected = "Failed to read fixture")]
+fn fails_when_fixture_is_missing() {
+    assert_eq_sorted_fixture!(config(), temp_fixture("missing.ron"));
+}
+
+#[test]
+fn loads_fixture_into_type_of_actual_value() {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/config.json");
+    assert_eq!(load_fixture(&path, &config()), config());
+}
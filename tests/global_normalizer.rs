@@ -0,0 +1,36 @@
+//! The global term normalizer applies to the whole test binary, so it's tested in a binary
+//! of its own.
+use pretty_assertions_sorted::{
+    assert_eq_sorted, set_global_term_normalizer, SortOptions, SortedDebug,
+};
+use std::collections::HashMap;
+
+fn strip_ansi(s: &str) -> String {
+    let mut out = String::new();
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c == '\u{1b}' {
+            chars.by_ref().find(|c| c.is_ascii_alphabetic());
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+#[test]
+fn global_normalizer_applies_to_every_assertion() {
+    set_global_term_normalizer(strip_ansi);
+    assert!(!set_global_term_normalizer(|s| s.to_string()));
+
+    let colored = HashMap::from([("status", "\u{1b}[32mok\u{1b}[0m"), ("name", "a")]);
+    let plain = HashMap::from([("name", "a"), ("status", "ok")]);
+    assert_eq_sorted!(colored, plain);
+
+    // The normalizer of the options is applied after the global one, to map keys as well.
+    let options = SortOptions::default().term_normalizer(|s| s.to_uppercase());
+    assert_eq!(
+        format!("{:?}", SortedDebug::with_options(&colored, options)),
+        "{\n    \"NAME\": \"A\",\n    \"STATUS\": \"OK\",\n}"
+    );
+}
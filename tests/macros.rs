@@ -5,4 +5,62 @@ mod assert_eq {
         let a = "some value";
         ::pretty_assertions_sorted::assert_eq_sorted!(a, a);
     }
+
+    #[test]
+    fn passes_with_labels() {
+        let a = "some value";
+        ::pretty_assertions_sorted::assert_eq_sorted!(a => "actual", a => "expected");
+    }
+}
+
+mod evaluation {
+    use std::cell::Cell;
+    use std::panic::{catch_unwind, AssertUnwindSafe};
+
+    /// Returns the value, counting how often it's called.
+    fn counted<T>(calls: &Cell<usize>, value: T) -> T {
+        calls.set(calls.get() + 1);
+        value
+    }
+
+    #[test]
+    fn evaluates_operands_once() {
+        let (left, right) = (Cell::new(0), Cell::new(0));
+        ::pretty_assertions_sorted::assert_eq_sorted!(counted(&left, 1), counted(&right, 1));
+        ::pretty_assertions_sorted::assert_eq_sorted!(
+            counted(&left, 1) => "actual",
+            counted(&right, 1) => "expected",
+        );
+        ::pretty_assertions_sorted::assert_eq_sorted!(
+            counted(&left, vec![1]),
+            counted(&right, vec![1]),
+            sort_lists = true
+        );
+        ::pretty_assertions_sorted::assert_eq_sorted!(
+            counted(&left, 1),
+            counted(&right, 1),
+            "with a {}",
+            "message"
+        );
+        ::pretty_assertions_sorted::assert_eq_sorted_deep_unordered!(
+            counted(&left, vec![1, 2]),
+            counted(&right, vec![2, 1]),
+        );
+        assert_eq!((left.get(), right.get()), (5, 5));
+    }
+
+    #[test]
+    fn evaluates_operands_once_on_failure() {
+        let (left, right) = (Cell::new(0), Cell::new(0));
+        let result = catch_unwind(AssertUnwindSafe(|| {
+            ::pretty_assertions_sorted::assert_eq_sorted!(
+                counted(&left, 1) => "actual",
+                counted(&right, 2) => "expected",
+                "with a {}",
+                "message"
+            );
+        }));
+        assert!(result.is_err());
+        assert_eq!((left.get(), right.get()), (1, 1));
+    }
 }
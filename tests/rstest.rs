@@ -0,0 +1,39 @@
+use pretty_assertions_sorted::{sorted_eq, SortedDiffError};
+use rstest::{fixture, rstest};
+use std::collections::HashMap;
+
+fn word_counts(input: &str) -> HashMap<&str, usize> {
+    let mut counts = HashMap::new();
+    for word in input.split_whitespace() {
+        *counts.entry(word).or_insert(0) += 1;
+    }
+    counts
+}
+
+#[fixture]
+fn expected_counts() -> HashMap<&'static str, usize> {
+    HashMap::from([("a", 2), ("b", 1)])
+}
+
+#[rstest]
+#[case("a b a")]
+#[case("b a a")]
+#[case(" a  a b ")]
+fn counts_words(
+    #[case] input: &str,
+    expected_counts: HashMap<&'static str, usize>,
+) -> Result<(), SortedDiffError> {
+    sorted_eq(&word_counts(input), &expected_counts)
+}
+
+#[rstest]
+fn reports_the_sorted_diff(expected_counts: HashMap<&'static str, usize>) {
+    let error = sorted_eq(&word_counts("a b b"), &expected_counts).unwrap_err();
+    assert!(
+        error
+            .to_string()
+            .contains("assertion failed: `(actual == expected)`"),
+        "{}",
+        error
+    );
+}
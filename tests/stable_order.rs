@@ -0,0 +1,43 @@
+use pretty_assertions_sorted::assert_stable_order;
+use std::fmt;
+use std::panic::catch_unwind;
+
+struct Headers(Vec<(&'static str, &'static str)>);
+
+impl fmt::Debug for Headers {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map()
+            .entries(self.0.iter().map(|(k, v)| (k, v)))
+            .finish()
+    }
+}
+
+#[test]
+fn records_then_checks_order() {
+    let golden = std::env::temp_dir()
+        .join(format!("pretty_assertions_sorted_{}", std::process::id()))
+        .join("headers.golden");
+    let _ = std::fs::remove_file(&golden);
+    let golden = golden.to_str().unwrap();
+
+    assert_stable_order!(Headers(vec![("date", "today"), ("host", "a")]), golden);
+    assert_stable_order!(Headers(vec![("date", "tomorrow"), ("host", "b")]), golden);
+
+    let reordered = catch_unwind(|| {
+        assert_stable_order!(Headers(vec![("host", "a"), ("date", "today")]), golden);
+    });
+    let message = *reordered.unwrap_err().downcast::<String>().unwrap();
+    assert!(message.contains("keys were reordered"), "{}", message);
+
+    let changed = catch_unwind(|| {
+        assert_stable_order!(Headers(vec![("date", "today")]), golden);
+    });
+    let message = *changed.unwrap_err().downcast::<String>().unwrap();
+    assert!(
+        message.contains("keys were added or removed"),
+        "{}",
+        message
+    );
+
+    let _ = std::fs::remove_file(golden);
+}
@@ -0,0 +1,18 @@
+use pretty_assertions_sorted::{sorted_eq, SortedDiffError};
+use std::collections::HashMap;
+use test_case::test_case;
+
+fn word_counts(input: &str) -> HashMap<&str, usize> {
+    let mut counts = HashMap::new();
+    for word in input.split_whitespace() {
+        *counts.entry(word).or_insert(0) += 1;
+    }
+    counts
+}
+
+#[test_case("a b a", &[("a", 2), ("b", 1)] ; "repeated words")]
+#[test_case("c b a", &[("a", 1), ("b", 1), ("c", 1)] ; "unique words")]
+#[test_case("", &[] ; "empty input")]
+fn counts_words(input: &str, expected: &[(&str, usize)]) -> Result<(), SortedDiffError> {
+    sorted_eq(&word_counts(input), &expected.iter().copied().collect())
+}
@@ -0,0 +1,45 @@
+//! The Debug output of third-party collections, which should parse and sort like the std
+//! collections they mirror.
+use pretty_assertions_sorted::{assert_eq_sorted, assert_eq_sorted_debug, SortedDebug};
+
+#[test]
+fn sorts_hashbrown_map() {
+    let map: hashbrown::HashMap<_, _> = (0..20).map(|i| (i, i * 2)).collect();
+    let expected = format!(
+        "{:#?}",
+        (0..20)
+            .map(|i| (i, i * 2))
+            .collect::<std::collections::BTreeMap<_, _>>()
+    );
+    assert_eq!(format!("{:#?}", SortedDebug::new(&map)), expected);
+
+    let reversed: hashbrown::HashMap<_, _> = (0..20).rev().map(|i| (i, i * 2)).collect();
+    assert_eq_sorted!(map, reversed);
+}
+
+#[test]
+fn sorts_hashbrown_set() {
+    let set: hashbrown::HashSet<_> = vec!["c", "a", "b"].into_iter().collect();
+    assert_eq!(
+        format!("{:?}", SortedDebug::new(&set)),
+        "{\n    \"a\",\n    \"b\",\n    \"c\",\n}"
+    );
+}
+
+/// `dashmap::DashMap` implements Debug with `debug_map` over the entries of all its shards,
+/// which is the same format as std maps.
+#[test]
+fn sorts_dashmap() {
+    let map: dashmap::DashMap<_, _> = (0..20).map(|i| (i, i * 2)).collect();
+    let expected = format!(
+        "{:#?}",
+        (0..20)
+            .map(|i| (i, i * 2))
+            .collect::<std::collections::BTreeMap<_, _>>()
+    );
+    assert_eq!(format!("{:#?}", SortedDebug::new(&map)), expected);
+
+    // `DashMap` doesn't implement `PartialEq`.
+    let reversed: dashmap::DashMap<_, _> = (0..20).rev().map(|i| (i, i * 2)).collect();
+    assert_eq_sorted_debug!(map, reversed);
+}